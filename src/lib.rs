@@ -4,6 +4,8 @@
 //!
 //! - `js-tracer`: Enables a JavaScript tracer implementation. This pulls in extra dependencies
 //!   (such as `boa`, `tokio` and `serde_json`).
+//! - `abi-decoder`: Enables the [`AbiDecoder`](tracing::AbiDecoder), which decodes call traces and
+//!   logs with JSON ABIs. This pulls in `alloy-dyn-abi` and `alloy-json-abi`.
 
 #![doc = include_str!("../README.md")]
 #![doc(
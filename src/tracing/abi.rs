@@ -0,0 +1,209 @@
+//! Decoding of calls, return data, errors and logs with JSON ABIs.
+
+use super::{
+    types::{CallLog, CallTraceNode, DecodedCallData},
+    CallTraceArena, RevertReason,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use alloy_dyn_abi::{DynSolValue, ErrorExt, EventExt, FunctionExt, JsonAbiExt};
+use alloy_json_abi::{Event, JsonAbi};
+use alloy_primitives::{hex, map::HashMap, Address};
+
+/// Decodes calls, return data, custom errors and logs with the JSON ABIs of known contracts.
+///
+/// This fills the [decoded](crate::tracing::types::CallTrace::decoded) fields of call traces and
+/// the [decoded](CallLog::decoded) fields of logs, which are rendered by the
+/// [TraceWriter](crate::tracing::TraceWriter).
+///
+/// Calls are decoded with the ABI of the contract whose code is executed, so delegate calls are
+/// decoded with the ABI of the implementation. Custom errors and events that are not part of that
+/// ABI are looked up in the ABIs of all known contracts, as errors bubble up from sub-calls and
+/// events may be declared in libraries.
+///
+/// Fields that are already decoded are left untouched, so multiple decoders can be applied to the
+/// same arena.
+#[derive(Clone, Debug, Default)]
+pub struct AbiDecoder {
+    abis: HashMap<Address, JsonAbi>,
+    labels: HashMap<Address, String>,
+}
+
+impl AbiDecoder {
+    /// Creates a new decoder without any contracts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the ABI of the contract deployed at the given address.
+    pub fn with_abi(mut self, address: Address, abi: JsonAbi) -> Self {
+        self.insert_abi(address, abi);
+        self
+    }
+
+    /// Adds the ABI of the contract deployed at the given address.
+    pub fn insert_abi(&mut self, address: Address, abi: JsonAbi) {
+        self.abis.insert(address, abi);
+    }
+
+    /// Adds a label for the given address, which is displayed instead of the address.
+    pub fn with_label(mut self, address: Address, label: impl Into<String>) -> Self {
+        self.insert_label(address, label);
+        self
+    }
+
+    /// Adds a label for the given address, which is displayed instead of the address.
+    pub fn insert_label(&mut self, address: Address, label: impl Into<String>) {
+        self.labels.insert(address, label.into());
+    }
+
+    /// Decodes all nodes of the arena.
+    pub fn decode_arena(&self, arena: &mut CallTraceArena) {
+        for node in arena.nodes_mut() {
+            self.decode_node(node);
+        }
+    }
+
+    /// Decodes the call, return data and logs of the given node.
+    pub fn decode_node(&self, node: &mut CallTraceNode) {
+        let trace = &mut node.trace;
+        if trace.decoded.label.is_none() {
+            trace.decoded.label = self.labels.get(&trace.address).cloned();
+        }
+
+        let abi = self.abis.get(&trace.address);
+        if !trace.kind.is_any_create() {
+            if let Some(function) = abi.and_then(|abi| {
+                let selector = trace.data.get(..4)?;
+                abi.functions().find(|function| function.selector() == selector)
+            }) {
+                if trace.decoded.call_data.is_none() {
+                    trace.decoded.call_data =
+                        function.abi_decode_input(&trace.data[4..]).ok().map(|args| {
+                            DecodedCallData {
+                                signature: function.signature(),
+                                args: args.iter().map(fmt_value).collect(),
+                            }
+                        });
+                }
+                if trace.decoded.return_data.is_none() && trace.success {
+                    trace.decoded.return_data = function
+                        .abi_decode_output(&trace.output)
+                        .ok()
+                        .filter(|values| !values.is_empty())
+                        .map(|values| fmt_values(&values));
+                }
+            }
+        }
+        if trace.decoded.return_data.is_none() && !trace.success {
+            trace.decoded.return_data = self.decode_error(abi, &trace.output);
+        }
+
+        for log in &mut node.logs {
+            if log.decoded.name.is_none() {
+                self.decode_log(abi, log);
+            }
+        }
+    }
+
+    /// Decodes a custom error, falls back to [RevertReason::decode].
+    fn decode_error(&self, abi: Option<&JsonAbi>, output: &[u8]) -> Option<String> {
+        let selector = output.get(..4)?;
+        let mut errors = abi
+            .into_iter()
+            .chain(self.abis.values())
+            .flat_map(|abi| abi.errors())
+            .filter(|error| error.selector() == selector);
+        let custom = errors.find_map(|error| {
+            let decoded = error.decode_error(output).ok()?;
+            Some(format!("{}({})", error.name, fmt_values(&decoded.body)))
+        });
+        custom.or_else(|| RevertReason::decode(output).map(|reason| reason.to_string()))
+    }
+
+    /// Decodes the name and parameters of a log.
+    fn decode_log(&self, abi: Option<&JsonAbi>, log: &mut CallLog) {
+        let Some(topic) = log.raw_log.topics().first() else { return };
+        let events = abi
+            .into_iter()
+            .chain(self.abis.values())
+            .flat_map(|abi| abi.events())
+            .filter(|event| !event.anonymous && event.selector() == *topic);
+        for event in events {
+            if let Some(params) = decode_event(event, log) {
+                log.decoded.name = Some(event.name.clone());
+                log.decoded.params = Some(params);
+                return;
+            }
+        }
+    }
+}
+
+/// Decodes the parameters of an event, returns `None` if the log does not match the event.
+fn decode_event(event: &Event, log: &CallLog) -> Option<Vec<(String, String)>> {
+    let decoded =
+        event.decode_log_parts(log.raw_log.topics().iter().copied(), &log.raw_log.data).ok()?;
+    let (mut indexed, mut body) = (decoded.indexed.iter(), decoded.body.iter());
+    event
+        .inputs
+        .iter()
+        .map(|input| {
+            let value = if input.indexed { indexed.next() } else { body.next() }?;
+            Some((input.name.clone(), fmt_value(value)))
+        })
+        .collect()
+}
+
+fn fmt_values(values: &[DynSolValue]) -> String {
+    values.iter().map(fmt_value).collect::<Vec<_>>().join(", ")
+}
+
+/// Formats a decoded value in Solidity syntax.
+fn fmt_value(value: &DynSolValue) -> String {
+    match value {
+        DynSolValue::Bool(value) => value.to_string(),
+        DynSolValue::Int(value, _) => value.to_string(),
+        DynSolValue::Uint(value, _) => value.to_string(),
+        DynSolValue::FixedBytes(word, size) => hex::encode_prefixed(&word[..*size]),
+        DynSolValue::Address(address) => address.to_checksum(None),
+        DynSolValue::Function(function) => function.to_string(),
+        DynSolValue::Bytes(bytes) => hex::encode_prefixed(bytes),
+        DynSolValue::String(value) => format!("{value:?}"),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) => {
+            format!("[{}]", fmt_values(values))
+        }
+        DynSolValue::Tuple(values) => format!("({})", fmt_values(values)),
+        #[allow(unreachable_patterns)]
+        value => format!("{value:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloy_primitives::{B256, I256, U256};
+
+    #[test]
+    fn fmt_values() {
+        let values = [
+            DynSolValue::Bool(true),
+            DynSolValue::Int(I256::MINUS_ONE, 8),
+            DynSolValue::Uint(U256::from(42), 256),
+            DynSolValue::FixedBytes(B256::right_padding_from(&[1]), 2),
+            DynSolValue::Address(Address::with_last_byte(0xaa)),
+            DynSolValue::Bytes(vec![0xab, 0xcd]),
+            DynSolValue::String("a \"quoted\" string".to_string()),
+            DynSolValue::Array(vec![DynSolValue::Uint(U256::from(1), 8)]),
+            DynSolValue::Tuple(vec![DynSolValue::Bool(false), DynSolValue::String("".into())]),
+        ];
+        assert_eq!(
+            super::fmt_values(&values),
+            "true, -1, 42, 0x0100, 0x00000000000000000000000000000000000000AA, 0xabcd, \
+             \"a \\\"quoted\\\" string\", [1], (false, \"\")"
+        );
+    }
+}
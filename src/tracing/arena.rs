@@ -114,3 +114,59 @@ impl PushTraceKind {
         matches!(self, Self::PushAndAttachToParent)
     }
 }
+
+#[cfg(test)]
+impl CallTraceArena {
+    /// Builds an arena from the depths of its calls in execution order, for tests.
+    ///
+    /// The address of every call is its index, and every call has a `CALL` step before each of
+    /// its sub-calls and a log at the end.
+    pub(crate) fn from_depths(depths: impl IntoIterator<Item = usize>) -> Self {
+        use super::types::{CallLog, CallTraceStep};
+        use alloy_primitives::{Log, LogData};
+
+        let mut arena = Self::default();
+        for (idx, depth) in depths.into_iter().enumerate() {
+            let trace = CallTrace {
+                depth,
+                address: Address::with_last_byte(idx as u8),
+                ..Default::default()
+            };
+            arena.push_trace(0, PushTraceKind::PushAndAttachToParent, trace);
+        }
+        for node in arena.nodes_mut() {
+            let mut ordering = Vec::new();
+            for call in 0..node.children.len() {
+                ordering.push(TraceMemberOrder::Step(call));
+                node.trace.steps.push(CallTraceStep {
+                    depth: node.trace.depth as u64,
+                    pc: call,
+                    op: revm::bytecode::OpCode::CALL,
+                    contract: node.trace.address,
+                    stack: None,
+                    push_stack: None,
+                    memory: None,
+                    memory_size: 0,
+                    returndata: Default::default(),
+                    gas_remaining: 0,
+                    gas_refund_counter: 0,
+                    gas_used: 0,
+                    gas_cost: 0,
+                    storage_change: None,
+                    memory_change: None,
+                    status: None,
+                    immediate_bytes: None,
+                    decoded: None,
+                });
+                ordering.push(TraceMemberOrder::Call(call));
+            }
+            ordering.push(TraceMemberOrder::Log(0));
+            node.logs.push(
+                CallLog::from(Log { address: node.trace.address, data: LogData::empty() })
+                    .with_position(node.children.len() as u64),
+            );
+            node.ordering = ordering;
+        }
+        arena
+    }
+}
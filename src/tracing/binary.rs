@@ -0,0 +1,972 @@
+//! Compact binary encoding of a [CallTraceArena].
+//!
+//! The encoding is versioned and laid out as follows, all integers are LEB128 varints unless noted
+//! otherwise:
+//!
+//! ```text
+//! magic (4 bytes) | version (u8) | node count | node offsets (u64 LE each) | nodes
+//! ```
+//!
+//! Every node is encoded independently, so a single node can be decoded via the node offset table
+//! without decoding the rest of the arena, see [CallTraceArenaReader]. Within a node, each step is
+//! encoded relative to the previous step: stacks only store the items that are not shared with the
+//! previous stack, memory snapshots only store the words that changed, and unchanged return data
+//! and contract addresses are not repeated.
+
+use super::{
+    types::{
+        CallKind, CallLog, CallStateChange, CallStateChangeKind, CallTrace, CallTraceNode,
+        CallTraceStep, DecodedCallData, DecodedCallLog, DecodedCallTrace, DecodedInternalCall,
+        DecodedTraceStep, MemoryChange, RecordedMemory, StorageChange, StorageChangeReason,
+        TraceMemberOrder,
+    },
+    CallTraceArena,
+};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
+use alloy_primitives::{Address, Bytes, LogData, B256, U256};
+use revm::{bytecode::OpCode, interpreter::InstructionResult};
+
+/// The magic bytes every encoded arena starts with.
+const MAGIC: [u8; 4] = *b"RITA";
+
+/// The current version of the encoding.
+pub const BINARY_FORMAT_VERSION: u8 = 1;
+
+/// The size of the header before the node offset table: magic and version.
+const HEADER_LEN: usize = MAGIC.len() + 1;
+
+/// The granularity in which memory snapshots are diffed.
+const MEMORY_WORD: usize = 32;
+
+/// The maximum length of a decoded memory snapshot, 64 MiB.
+///
+/// Expanding memory to this size costs more than 8 billion gas, so only corrupt input exceeds it.
+const MAX_MEMORY_LEN: usize = 1 << 26;
+
+/// Errors that can occur when decoding an encoded [CallTraceArena].
+#[derive(Debug, thiserror::Error)]
+pub enum BinaryTraceError {
+    /// The input does not start with the expected magic bytes.
+    #[error("invalid magic bytes")]
+    InvalidMagic,
+    /// The input was encoded with an unsupported version of the format.
+    #[error("unsupported binary trace format version {0}")]
+    UnsupportedVersion(u8),
+    /// The input ended unexpectedly.
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    /// The input contains an invalid value.
+    #[error("invalid {0}")]
+    InvalidValue(&'static str),
+    /// The requested node does not exist.
+    #[error("node index {0} out of bounds")]
+    NodeOutOfBounds(usize),
+}
+
+impl CallTraceArena {
+    /// Encodes the arena into the compact binary format, see [CallTraceArenaReader].
+    pub fn encode_binary(&self) -> Vec<u8> {
+        let mut nodes = Encoder::default();
+        let mut offsets = Vec::with_capacity(self.arena.len());
+        for node in &self.arena {
+            offsets.push(nodes.buf.len() as u64);
+            nodes.node(node);
+        }
+
+        let mut out = Encoder::default();
+        out.buf.extend_from_slice(&MAGIC);
+        out.u8(BINARY_FORMAT_VERSION);
+        out.usize(offsets.len());
+        for offset in offsets {
+            out.buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        out.buf.extend_from_slice(&nodes.buf);
+        out.buf
+    }
+
+    /// Decodes an arena that was encoded with [Self::encode_binary].
+    pub fn decode_binary(data: &[u8]) -> Result<Self, BinaryTraceError> {
+        CallTraceArenaReader::new(data)?.decode_arena()
+    }
+}
+
+/// Lazily decodes the nodes of an arena that was encoded with [CallTraceArena::encode_binary].
+///
+/// Creating the reader only validates the header, nodes are decoded on demand.
+#[derive(Clone, Copy, Debug)]
+pub struct CallTraceArenaReader<'a> {
+    /// The node offset table.
+    offsets: &'a [u8],
+    /// The encoded nodes.
+    nodes: &'a [u8],
+}
+
+impl<'a> CallTraceArenaReader<'a> {
+    /// Creates a new reader for the given encoded arena.
+    pub fn new(data: &'a [u8]) -> Result<Self, BinaryTraceError> {
+        if data.len() < HEADER_LEN || data[..MAGIC.len()] != MAGIC {
+            return Err(BinaryTraceError::InvalidMagic);
+        }
+        let version = data[MAGIC.len()];
+        if version != BINARY_FORMAT_VERSION {
+            return Err(BinaryTraceError::UnsupportedVersion(version));
+        }
+
+        let mut decoder = Decoder { data: &data[HEADER_LEN..] };
+        let len = decoder.usize()?;
+        let offsets_len = len.checked_mul(8).ok_or(BinaryTraceError::InvalidValue("node count"))?;
+        let offsets = decoder.take(offsets_len)?;
+        Ok(Self { offsets, nodes: decoder.data })
+    }
+
+    /// Returns the number of nodes in the arena.
+    pub const fn len(&self) -> usize {
+        self.offsets.len() / 8
+    }
+
+    /// Returns `true` if the arena has no nodes.
+    pub const fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Decodes the node with the given index.
+    pub fn node(&self, idx: usize) -> Result<CallTraceNode, BinaryTraceError> {
+        if idx >= self.len() {
+            return Err(BinaryTraceError::NodeOutOfBounds(idx));
+        }
+        let start = self.offset(idx)?;
+        let end = if idx + 1 < self.len() { self.offset(idx + 1)? } else { self.nodes.len() };
+        let data =
+            self.nodes.get(start..end).ok_or(BinaryTraceError::InvalidValue("node offset"))?;
+
+        let mut decoder = Decoder { data };
+        let node = decoder.node()?;
+        if !decoder.data.is_empty() {
+            return Err(BinaryTraceError::InvalidValue("node length"));
+        }
+        Ok(node)
+    }
+
+    /// Returns an iterator that decodes all nodes in order.
+    pub fn nodes(&self) -> impl Iterator<Item = Result<CallTraceNode, BinaryTraceError>> + '_ {
+        (0..self.len()).map(|idx| self.node(idx))
+    }
+
+    /// Decodes all nodes into a [CallTraceArena].
+    pub fn decode_arena(&self) -> Result<CallTraceArena, BinaryTraceError> {
+        let arena = self.nodes().collect::<Result<Vec<_>, _>>()?;
+        if arena.is_empty() {
+            return Err(BinaryTraceError::InvalidValue("node count"));
+        }
+        Ok(CallTraceArena { arena })
+    }
+
+    fn offset(&self, idx: usize) -> Result<usize, BinaryTraceError> {
+        let bytes = self.offsets[idx * 8..idx * 8 + 8].try_into().expect("8 bytes");
+        usize::try_from(u64::from_le_bytes(bytes))
+            .map_err(|_| BinaryTraceError::InvalidValue("node offset"))
+    }
+}
+
+/// Writes the binary encoding.
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn u64(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.usize(value.len());
+        self.buf.extend_from_slice(value);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    fn address(&mut self, value: &Address) {
+        self.buf.extend_from_slice(value.as_slice());
+    }
+
+    fn b256(&mut self, value: &B256) {
+        self.buf.extend_from_slice(value.as_slice());
+    }
+
+    /// Encodes the value as its big-endian bytes without leading zeros.
+    fn u256(&mut self, value: &U256) {
+        let bytes = value.to_be_bytes::<32>();
+        let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(32);
+        self.bytes(&bytes[start..]);
+    }
+
+    fn option<T>(&mut self, value: Option<T>, f: impl FnOnce(&mut Self, T)) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                f(self, value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn seq<T>(&mut self, values: &[T], mut f: impl FnMut(&mut Self, &T)) {
+        self.usize(values.len());
+        for value in values {
+            f(self, value);
+        }
+    }
+
+    fn strings(&mut self, values: &[String]) {
+        self.seq(values, |e, value| e.str(value));
+    }
+
+    fn status(&mut self, value: Option<InstructionResult>) {
+        self.option(value, |e, status| e.u8(instruction_result_tag(status)));
+    }
+
+    fn node(&mut self, node: &CallTraceNode) {
+        self.option(node.parent, Self::usize);
+        self.seq(&node.children, |e, child| e.usize(*child));
+        self.usize(node.idx);
+        self.trace(&node.trace);
+        self.seq(&node.logs, Self::log);
+        self.seq(&node.ordering, |e, order| {
+            let (tag, idx) = match *order {
+                TraceMemberOrder::Log(idx) => (0, idx),
+                TraceMemberOrder::Call(idx) => (1, idx),
+                TraceMemberOrder::Step(idx) => (2, idx),
+            };
+            e.u8(tag);
+            e.usize(idx);
+        });
+        self.seq(&node.state_changes, Self::state_change);
+        self.usize(node.accessed_state.len());
+        for (address, slots) in &node.accessed_state {
+            self.address(address);
+            self.usize(slots.len());
+            for slot in slots {
+                self.b256(slot);
+            }
+        }
+        self.option(node.original_position, Self::usize);
+    }
+
+    fn trace(&mut self, trace: &CallTrace) {
+        self.usize(trace.depth);
+        self.bool(trace.success);
+        self.address(&trace.caller);
+        self.address(&trace.address);
+        self.option(trace.maybe_precompile, Self::bool);
+        self.option(trace.selfdestruct_address.as_ref(), Self::address);
+        self.option(trace.selfdestruct_refund_target.as_ref(), Self::address);
+        self.option(trace.selfdestruct_transferred_value.as_ref(), Self::u256);
+        self.u8(call_kind_tag(trace.kind));
+        self.u256(&trace.value);
+        self.bytes(&trace.data);
+        self.bytes(&trace.output);
+        self.option(trace.code.as_deref(), |e, code| e.bytes(code));
+        self.u64(trace.gas_used);
+        self.u64(trace.gas_limit);
+        self.status(trace.status);
+
+        let DecodedCallTrace { label, return_data, call_data } = &trace.decoded;
+        self.option(label.as_deref(), Self::str);
+        self.option(return_data.as_deref(), Self::str);
+        self.option(call_data.as_ref(), |e, DecodedCallData { signature, args }| {
+            e.str(signature);
+            e.strings(args);
+        });
+
+        self.usize(trace.steps.len());
+        let mut prev = None;
+        for step in &trace.steps {
+            self.step(step, prev);
+            prev = Some(step);
+        }
+    }
+
+    fn log(&mut self, log: &CallLog) {
+        self.seq(log.raw_log.topics(), |e, topic| e.b256(topic));
+        self.bytes(&log.raw_log.data);
+        let DecodedCallLog { name, params } = &log.decoded;
+        self.option(name.as_deref(), Self::str);
+        self.option(params.as_deref(), |e, params| {
+            e.seq(params, |e, (name, value)| {
+                e.str(name);
+                e.str(value);
+            })
+        });
+        self.u64(log.position);
+    }
+
+    fn state_change(&mut self, change: &CallStateChange) {
+        self.address(&change.address);
+        match &change.kind {
+            CallStateChangeKind::Transfer { to, value } => {
+                self.u8(0);
+                self.address(to);
+                self.u256(value);
+            }
+            CallStateChangeKind::BalanceChange { had_balance, balance } => {
+                self.u8(1);
+                self.u256(had_balance);
+                self.u256(balance);
+            }
+            CallStateChangeKind::NonceChange { nonce } => {
+                self.u8(2);
+                self.u64(*nonce);
+            }
+            CallStateChangeKind::CodeChange { code_hash } => {
+                self.u8(3);
+                self.b256(code_hash);
+            }
+            CallStateChangeKind::Created => self.u8(4),
+            CallStateChangeKind::Storage { key, had_value, value } => {
+                self.u8(5);
+                self.u256(key);
+                self.u256(had_value);
+                self.u256(value);
+            }
+            CallStateChangeKind::SelfDestruct { target, balance } => {
+                self.u8(6);
+                self.address(target);
+                self.u256(balance);
+            }
+        }
+        self.bool(change.reverted);
+    }
+
+    /// Encodes the step relative to the previous step of the same node.
+    fn step(&mut self, step: &CallTraceStep, prev: Option<&CallTraceStep>) {
+        self.u64(step.depth);
+        self.usize(step.pc);
+        self.u8(step.op.get());
+
+        // the contract only changes between nodes
+        if prev.is_some_and(|prev| prev.contract == step.contract) {
+            self.u8(0);
+        } else {
+            self.u8(1);
+            self.address(&step.contract);
+        }
+
+        // only the items that are not shared with the previous stack are stored
+        let prev_stack = prev.and_then(|prev| prev.stack.as_deref()).unwrap_or_default();
+        self.option(step.stack.as_deref(), |e, stack| {
+            let shared =
+                stack.iter().zip(prev_stack).take_while(|(item, prev)| item == prev).count();
+            e.usize(shared);
+            e.seq(&stack[shared..], |e, item| e.u256(item));
+        });
+        self.option(step.push_stack.as_deref(), |e, stack| e.seq(stack, |e, item| e.u256(item)));
+
+        let prev_memory = prev.and_then(|prev| prev.memory.as_ref());
+        match (&step.memory, prev_memory) {
+            (None, _) => self.u8(0),
+            (Some(memory), Some(prev)) if memory == prev => self.u8(1),
+            (Some(memory), Some(prev)) => {
+                self.u8(2);
+                self.memory_diff(prev.as_bytes(), memory.as_bytes());
+            }
+            (Some(memory), None) => {
+                self.u8(3);
+                self.bytes(memory.as_bytes());
+            }
+        }
+        self.usize(step.memory_size);
+
+        if prev.is_some_and(|prev| prev.returndata == step.returndata) {
+            self.u8(0);
+        } else {
+            self.u8(1);
+            self.bytes(&step.returndata);
+        }
+
+        self.u64(step.gas_remaining);
+        self.u64(step.gas_refund_counter);
+        self.u64(step.gas_used);
+        self.u64(step.gas_cost);
+        self.option(step.storage_change.as_ref(), |e, change| {
+            e.u256(&change.key);
+            e.u256(&change.value);
+            e.option(change.had_value.as_ref(), Self::u256);
+            e.u8(match change.reason {
+                StorageChangeReason::SLOAD => 0,
+                StorageChangeReason::SSTORE => 1,
+                StorageChangeReason::TSTORE => 2,
+            });
+        });
+        self.option(step.memory_change.as_ref(), |e, change| {
+            e.usize(change.offset);
+            e.bytes(&change.data);
+        });
+        self.status(step.status);
+        self.option(step.immediate_bytes.as_deref(), |e, bytes| e.bytes(bytes));
+        self.option(step.decoded.as_ref(), |e, decoded| match decoded {
+            DecodedTraceStep::InternalCall(call, end_step) => {
+                e.u8(0);
+                e.str(&call.func_name);
+                e.option(call.args.as_deref(), Self::strings);
+                e.option(call.return_data.as_deref(), Self::strings);
+                e.usize(*end_step);
+            }
+            DecodedTraceStep::Line(line) => {
+                e.u8(1);
+                e.str(line);
+            }
+        });
+    }
+
+    /// Encodes `memory` as the new length and the runs of words that differ from `prev`.
+    fn memory_diff(&mut self, prev: &[u8], memory: &[u8]) {
+        self.usize(memory.len());
+        // the word at the given range, zero padded if `data` is shorter
+        let word = |data: &[u8], start: usize, end: usize| {
+            let mut word = [0u8; MEMORY_WORD];
+            if let Some(bytes) = data.get(start..end.min(data.len())) {
+                word[..bytes.len()].copy_from_slice(bytes);
+            }
+            word
+        };
+
+        let mut runs = Vec::new();
+        let mut run_start = None;
+        let mut start = 0;
+        while start < memory.len() {
+            let end = (start + MEMORY_WORD).min(memory.len());
+            match (word(prev, start, end) == word(memory, start, end), run_start) {
+                (false, None) => run_start = Some(start),
+                (true, Some(run)) => {
+                    runs.push(run..start);
+                    run_start = None;
+                }
+                _ => {}
+            }
+            start = end;
+        }
+        if let Some(run) = run_start {
+            runs.push(run..memory.len());
+        }
+
+        self.usize(runs.len());
+        for run in runs {
+            self.usize(run.start);
+            self.bytes(&memory[run]);
+        }
+    }
+}
+
+/// Reads the binary encoding.
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BinaryTraceError> {
+        if self.data.len() < len {
+            return Err(BinaryTraceError::UnexpectedEof);
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, BinaryTraceError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, BinaryTraceError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(BinaryTraceError::InvalidValue("bool")),
+        }
+    }
+
+    fn u64(&mut self) -> Result<u64, BinaryTraceError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f)
+                .checked_shl(shift)
+                .filter(|shifted| shifted >> shift == u64::from(byte & 0x7f))
+                .ok_or(BinaryTraceError::InvalidValue("varint"))?;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BinaryTraceError::InvalidValue("varint"))
+    }
+
+    fn usize(&mut self) -> Result<usize, BinaryTraceError> {
+        usize::try_from(self.u64()?).map_err(|_| BinaryTraceError::InvalidValue("usize"))
+    }
+
+    fn raw_bytes(&mut self) -> Result<&'a [u8], BinaryTraceError> {
+        let len = self.usize()?;
+        self.take(len)
+    }
+
+    fn bytes(&mut self) -> Result<Bytes, BinaryTraceError> {
+        self.raw_bytes().map(Bytes::copy_from_slice)
+    }
+
+    fn string(&mut self) -> Result<String, BinaryTraceError> {
+        let bytes = self.raw_bytes()?;
+        core::str::from_utf8(bytes)
+            .map(String::from)
+            .map_err(|_| BinaryTraceError::InvalidValue("string"))
+    }
+
+    fn address(&mut self) -> Result<Address, BinaryTraceError> {
+        self.take(20).map(Address::from_slice)
+    }
+
+    fn b256(&mut self) -> Result<B256, BinaryTraceError> {
+        self.take(32).map(B256::from_slice)
+    }
+
+    fn u256(&mut self) -> Result<U256, BinaryTraceError> {
+        let bytes = self.raw_bytes()?;
+        U256::try_from_be_slice(bytes).ok_or(BinaryTraceError::InvalidValue("U256"))
+    }
+
+    fn option<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, BinaryTraceError>,
+    ) -> Result<Option<T>, BinaryTraceError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => f(self).map(Some),
+            _ => Err(BinaryTraceError::InvalidValue("option")),
+        }
+    }
+
+    fn seq<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<T, BinaryTraceError>,
+    ) -> Result<Vec<T>, BinaryTraceError> {
+        let len = self.usize()?;
+        // every item is at least one byte, this prevents huge allocations for corrupt lengths
+        let mut values = Vec::with_capacity(len.min(self.data.len()));
+        for _ in 0..len {
+            values.push(f(self)?);
+        }
+        Ok(values)
+    }
+
+    fn strings(&mut self) -> Result<Vec<String>, BinaryTraceError> {
+        self.seq(Self::string)
+    }
+
+    fn status(&mut self) -> Result<Option<InstructionResult>, BinaryTraceError> {
+        self.option(|d| instruction_result_from_tag(d.u8()?))
+    }
+
+    fn node(&mut self) -> Result<CallTraceNode, BinaryTraceError> {
+        Ok(CallTraceNode {
+            parent: self.option(Self::usize)?,
+            children: self.seq(Self::usize)?,
+            idx: self.usize()?,
+            trace: self.trace()?,
+            logs: self.seq(Self::log)?,
+            ordering: self.seq(|d| {
+                let tag = d.u8()?;
+                let idx = d.usize()?;
+                match tag {
+                    0 => Ok(TraceMemberOrder::Log(idx)),
+                    1 => Ok(TraceMemberOrder::Call(idx)),
+                    2 => Ok(TraceMemberOrder::Step(idx)),
+                    _ => Err(BinaryTraceError::InvalidValue("trace member order")),
+                }
+            })?,
+            state_changes: self.seq(Self::state_change)?,
+            accessed_state: {
+                let len = self.usize()?;
+                let mut accessed_state = BTreeMap::new();
+                for _ in 0..len {
+                    let address = self.address()?;
+                    let slots = self.seq(Self::b256)?.into_iter().collect::<BTreeSet<_>>();
+                    accessed_state.insert(address, slots);
+                }
+                accessed_state
+            },
+            original_position: self.option(Self::usize)?,
+        })
+    }
+
+    fn trace(&mut self) -> Result<CallTrace, BinaryTraceError> {
+        let mut trace = CallTrace {
+            depth: self.usize()?,
+            success: self.bool()?,
+            caller: self.address()?,
+            address: self.address()?,
+            maybe_precompile: self.option(Self::bool)?,
+            selfdestruct_address: self.option(Self::address)?,
+            selfdestruct_refund_target: self.option(Self::address)?,
+            selfdestruct_transferred_value: self.option(Self::u256)?,
+            kind: call_kind_from_tag(self.u8()?)?,
+            value: self.u256()?,
+            data: self.bytes()?,
+            output: self.bytes()?,
+            code: self.option(Self::bytes)?,
+            gas_used: self.u64()?,
+            gas_limit: self.u64()?,
+            status: self.status()?,
+            steps: Vec::new(),
+            decoded: DecodedCallTrace {
+                label: self.option(Self::string)?,
+                return_data: self.option(Self::string)?,
+                call_data: self.option(|d| {
+                    Ok(DecodedCallData { signature: d.string()?, args: d.strings()? })
+                })?,
+            },
+        };
+
+        let len = self.usize()?;
+        let mut steps: Vec<CallTraceStep> = Vec::with_capacity(len.min(self.data.len()));
+        for _ in 0..len {
+            let step = self.step(steps.last())?;
+            steps.push(step);
+        }
+        trace.steps = steps;
+        Ok(trace)
+    }
+
+    fn log(&mut self) -> Result<CallLog, BinaryTraceError> {
+        let topics = self.seq(Self::b256)?;
+        let data = self.bytes()?;
+        Ok(CallLog {
+            raw_log: LogData::new(topics, data).ok_or(BinaryTraceError::InvalidValue("log"))?,
+            decoded: DecodedCallLog {
+                name: self.option(Self::string)?,
+                params: self.option(|d| d.seq(|d| Ok((d.string()?, d.string()?))))?,
+            },
+            position: self.u64()?,
+        })
+    }
+
+    fn state_change(&mut self) -> Result<CallStateChange, BinaryTraceError> {
+        let address = self.address()?;
+        let kind = match self.u8()? {
+            0 => CallStateChangeKind::Transfer { to: self.address()?, value: self.u256()? },
+            1 => CallStateChangeKind::BalanceChange {
+                had_balance: self.u256()?,
+                balance: self.u256()?,
+            },
+            2 => CallStateChangeKind::NonceChange { nonce: self.u64()? },
+            3 => CallStateChangeKind::CodeChange { code_hash: self.b256()? },
+            4 => CallStateChangeKind::Created,
+            5 => CallStateChangeKind::Storage {
+                key: self.u256()?,
+                had_value: self.u256()?,
+                value: self.u256()?,
+            },
+            6 => {
+                CallStateChangeKind::SelfDestruct { target: self.address()?, balance: self.u256()? }
+            }
+            _ => return Err(BinaryTraceError::InvalidValue("state change kind")),
+        };
+        Ok(CallStateChange { address, kind, reverted: self.bool()? })
+    }
+
+    /// Decodes a step that was encoded relative to the previous step of the same node.
+    fn step(&mut self, prev: Option<&CallTraceStep>) -> Result<CallTraceStep, BinaryTraceError> {
+        let depth = self.u64()?;
+        let pc = self.usize()?;
+        // SAFETY: unknown opcodes are recorded as well
+        let op = unsafe { OpCode::new_unchecked(self.u8()?) };
+
+        let contract = match (self.u8()?, prev) {
+            (0, Some(prev)) => prev.contract,
+            (1, _) => self.address()?,
+            _ => return Err(BinaryTraceError::InvalidValue("step contract")),
+        };
+
+        let prev_stack = prev.and_then(|prev| prev.stack.as_deref()).unwrap_or_default();
+        let stack = self.option(|d| {
+            let shared = d.usize()?;
+            let shared = prev_stack
+                .get(..shared)
+                .ok_or(BinaryTraceError::InvalidValue("shared stack length"))?;
+            let mut stack = shared.to_vec();
+            stack.extend(d.seq(Self::u256)?);
+            Ok(stack)
+        })?;
+        let push_stack = self.option(|d| d.seq(Self::u256))?;
+
+        let prev_memory = prev.and_then(|prev| prev.memory.as_ref());
+        let memory = match (self.u8()?, prev_memory) {
+            (0, _) => None,
+            (1, Some(prev)) => Some(prev.clone()),
+            (2, Some(prev)) => Some(RecordedMemory(self.memory_diff(prev.as_bytes())?)),
+            (3, _) => Some(RecordedMemory(self.bytes()?)),
+            _ => return Err(BinaryTraceError::InvalidValue("step memory")),
+        };
+        let memory_size = self.usize()?;
+
+        let returndata = match (self.u8()?, prev) {
+            (0, Some(prev)) => prev.returndata.clone(),
+            (1, _) => self.bytes()?,
+            _ => return Err(BinaryTraceError::InvalidValue("step return data")),
+        };
+
+        Ok(CallTraceStep {
+            depth,
+            pc,
+            op,
+            contract,
+            stack,
+            push_stack,
+            memory,
+            memory_size,
+            returndata,
+            gas_remaining: self.u64()?,
+            gas_refund_counter: self.u64()?,
+            gas_used: self.u64()?,
+            gas_cost: self.u64()?,
+            storage_change: self.option(|d| {
+                Ok(StorageChange {
+                    key: d.u256()?,
+                    value: d.u256()?,
+                    had_value: d.option(Self::u256)?,
+                    reason: match d.u8()? {
+                        0 => StorageChangeReason::SLOAD,
+                        1 => StorageChangeReason::SSTORE,
+                        2 => StorageChangeReason::TSTORE,
+                        _ => return Err(BinaryTraceError::InvalidValue("storage change reason")),
+                    },
+                })
+            })?,
+            memory_change: self
+                .option(|d| Ok(MemoryChange { offset: d.usize()?, data: d.bytes()? }))?,
+            status: self.status()?,
+            immediate_bytes: self.option(Self::bytes)?,
+            decoded: self.option(|d| match d.u8()? {
+                0 => Ok(DecodedTraceStep::InternalCall(
+                    DecodedInternalCall {
+                        func_name: d.string()?,
+                        args: d.option(Self::strings)?,
+                        return_data: d.option(Self::strings)?,
+                    },
+                    d.usize()?,
+                )),
+                1 => Ok(DecodedTraceStep::Line(d.string()?)),
+                _ => Err(BinaryTraceError::InvalidValue("decoded step")),
+            })?,
+        })
+    }
+
+    /// Decodes a memory snapshot that was encoded with [Encoder::memory_diff].
+    fn memory_diff(&mut self, prev: &[u8]) -> Result<Bytes, BinaryTraceError> {
+        let len = self.usize()?;
+        if len > MAX_MEMORY_LEN {
+            return Err(BinaryTraceError::InvalidValue("memory length"));
+        }
+        let mut memory = prev.to_vec();
+        memory.resize(len, 0);
+        for _ in 0..self.usize()? {
+            let offset = self.usize()?;
+            let data = self.raw_bytes()?;
+            memory
+                .get_mut(offset..)
+                .and_then(|memory| memory.get_mut(..data.len()))
+                .ok_or(BinaryTraceError::InvalidValue("memory diff"))?
+                .copy_from_slice(data);
+        }
+        Ok(memory.into())
+    }
+}
+
+const fn call_kind_tag(kind: CallKind) -> u8 {
+    match kind {
+        CallKind::Call => 0,
+        CallKind::StaticCall => 1,
+        CallKind::CallCode => 2,
+        CallKind::DelegateCall => 3,
+        CallKind::AuthCall => 4,
+        CallKind::Create => 5,
+        CallKind::Create2 => 6,
+    }
+}
+
+const fn call_kind_from_tag(tag: u8) -> Result<CallKind, BinaryTraceError> {
+    Ok(match tag {
+        0 => CallKind::Call,
+        1 => CallKind::StaticCall,
+        2 => CallKind::CallCode,
+        3 => CallKind::DelegateCall,
+        4 => CallKind::AuthCall,
+        5 => CallKind::Create,
+        6 => CallKind::Create2,
+        _ => return Err(BinaryTraceError::InvalidValue("call kind")),
+    })
+}
+
+/// The tag of an [InstructionResult], independent of its discriminant in revm.
+const fn instruction_result_tag(result: InstructionResult) -> u8 {
+    match result {
+        InstructionResult::Stop => 0,
+        InstructionResult::Return => 1,
+        InstructionResult::SelfDestruct => 2,
+        InstructionResult::Revert => 3,
+        InstructionResult::CallTooDeep => 4,
+        InstructionResult::OutOfFunds => 5,
+        InstructionResult::CreateInitCodeStartingEF00 => 6,
+        InstructionResult::InvalidEOFInitCode => 7,
+        InstructionResult::InvalidExtDelegateCallTarget => 8,
+        InstructionResult::OutOfGas => 9,
+        InstructionResult::MemoryOOG => 10,
+        InstructionResult::MemoryLimitOOG => 11,
+        InstructionResult::PrecompileOOG => 12,
+        InstructionResult::InvalidOperandOOG => 13,
+        InstructionResult::ReentrancySentryOOG => 14,
+        InstructionResult::OpcodeNotFound => 15,
+        InstructionResult::CallNotAllowedInsideStatic => 16,
+        InstructionResult::StateChangeDuringStaticCall => 17,
+        InstructionResult::InvalidFEOpcode => 18,
+        InstructionResult::InvalidJump => 19,
+        InstructionResult::NotActivated => 20,
+        InstructionResult::StackUnderflow => 21,
+        InstructionResult::StackOverflow => 22,
+        InstructionResult::OutOfOffset => 23,
+        InstructionResult::CreateCollision => 24,
+        InstructionResult::OverflowPayment => 25,
+        InstructionResult::PrecompileError => 26,
+        InstructionResult::NonceOverflow => 27,
+        InstructionResult::CreateContractSizeLimit => 28,
+        InstructionResult::CreateContractStartingWithEF => 29,
+        InstructionResult::CreateInitCodeSizeLimit => 30,
+        InstructionResult::FatalExternalError => 31,
+    }
+}
+
+const fn instruction_result_from_tag(tag: u8) -> Result<InstructionResult, BinaryTraceError> {
+    Ok(match tag {
+        0 => InstructionResult::Stop,
+        1 => InstructionResult::Return,
+        2 => InstructionResult::SelfDestruct,
+        3 => InstructionResult::Revert,
+        4 => InstructionResult::CallTooDeep,
+        5 => InstructionResult::OutOfFunds,
+        6 => InstructionResult::CreateInitCodeStartingEF00,
+        7 => InstructionResult::InvalidEOFInitCode,
+        8 => InstructionResult::InvalidExtDelegateCallTarget,
+        9 => InstructionResult::OutOfGas,
+        10 => InstructionResult::MemoryOOG,
+        11 => InstructionResult::MemoryLimitOOG,
+        12 => InstructionResult::PrecompileOOG,
+        13 => InstructionResult::InvalidOperandOOG,
+        14 => InstructionResult::ReentrancySentryOOG,
+        15 => InstructionResult::OpcodeNotFound,
+        16 => InstructionResult::CallNotAllowedInsideStatic,
+        17 => InstructionResult::StateChangeDuringStaticCall,
+        18 => InstructionResult::InvalidFEOpcode,
+        19 => InstructionResult::InvalidJump,
+        20 => InstructionResult::NotActivated,
+        21 => InstructionResult::StackUnderflow,
+        22 => InstructionResult::StackOverflow,
+        23 => InstructionResult::OutOfOffset,
+        24 => InstructionResult::CreateCollision,
+        25 => InstructionResult::OverflowPayment,
+        26 => InstructionResult::PrecompileError,
+        27 => InstructionResult::NonceOverflow,
+        28 => InstructionResult::CreateContractSizeLimit,
+        29 => InstructionResult::CreateContractStartingWithEF,
+        30 => InstructionResult::CreateInitCodeSizeLimit,
+        31 => InstructionResult::FatalExternalError,
+        _ => return Err(BinaryTraceError::InvalidValue("instruction result")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_roundtrip() {
+        for value in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u32::MAX as u64, u64::MAX] {
+            let mut encoder = Encoder::default();
+            encoder.u64(value);
+            let mut decoder = Decoder { data: &encoder.buf };
+            assert_eq!(decoder.u64().unwrap(), value);
+            assert!(decoder.data.is_empty());
+        }
+    }
+
+    #[test]
+    fn memory_diff_roundtrip() {
+        let prev = [1u8; 96];
+        let mut memory = [0u8; 160];
+        memory[..96].copy_from_slice(&prev);
+        memory[40] = 2;
+        memory[150] = 3;
+
+        let mut encoder = Encoder::default();
+        encoder.memory_diff(&prev, &memory);
+        // the length, the number of runs and two runs of one word each at offsets 32 and 128
+        assert_eq!(encoder.buf.len(), 2 + 1 + (1 + 1 + MEMORY_WORD) + (2 + 1 + MEMORY_WORD));
+        let mut decoder = Decoder { data: &encoder.buf };
+        assert_eq!(decoder.memory_diff(&prev).unwrap(), memory[..]);
+
+        // corrupt lengths are rejected instead of being allocated
+        let mut encoder = Encoder::default();
+        encoder.usize(usize::MAX);
+        encoder.usize(0);
+        let mut decoder = Decoder { data: &encoder.buf };
+        assert!(matches!(
+            decoder.memory_diff(&prev),
+            Err(BinaryTraceError::InvalidValue("memory length"))
+        ));
+    }
+
+    #[test]
+    fn instruction_results_roundtrip() {
+        let statuses = (0..=u8::MAX).filter_map(|tag| instruction_result_from_tag(tag).ok());
+        assert_eq!(statuses.clone().count(), 32);
+        for status in statuses {
+            let mut encoder = Encoder::default();
+            encoder.status(Some(status));
+            let mut decoder = Decoder { data: &encoder.buf };
+            assert_eq!(decoder.status().unwrap(), Some(status));
+        }
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert!(matches!(
+            CallTraceArena::decode_binary(b"nope"),
+            Err(BinaryTraceError::InvalidMagic)
+        ));
+        let mut data = CallTraceArena::default().encode_binary();
+        data[MAGIC.len()] = BINARY_FORMAT_VERSION + 1;
+        assert!(matches!(
+            CallTraceArena::decode_binary(&data),
+            Err(BinaryTraceError::UnsupportedVersion(_))
+        ));
+    }
+}
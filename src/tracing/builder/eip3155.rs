@@ -0,0 +1,197 @@
+//! EIP-3155 style step traces.
+//!
+//! See <https://eips.ethereum.org/EIPS/eip-3155>
+
+use crate::tracing::{
+    config::TraceStyle,
+    types::{CallTraceNode, CallTraceStep, TraceMemberOrder},
+    utils, CallTraceArena, StepSink,
+};
+use alloc::{format, string::String};
+use alloy_primitives::{hex, Bytes, B256};
+use revm::context_interface::result::ExecutionResult;
+use serde_json::{json, Map, Value};
+use std::io::{self, Write};
+
+/// Writes recorded steps as [EIP-3155](https://eips.ethereum.org/EIPS/eip-3155) JSON lines.
+///
+/// This is the output format of `evm t8n --trace` and the `--json` tracer of other clients: one
+/// JSON object per executed opcode, followed by a summary line, see [Self::write_summary].
+///
+/// Steps can be written from a recorded [CallTraceArena] via [Self::write_arena], or streamed
+/// straight from the inspector by installing the writer as [StepSink] via
+/// [TracingInspector::with_step_sink](crate::tracing::TracingInspector::with_step_sink). In the
+/// latter case, errors of the underlying writer are returned by [Self::into_inner].
+///
+/// The `stack`, `memory` and `returnData` fields are only populated if the corresponding snapshots
+/// were recorded, see [TracingInspectorConfig](crate::tracing::TracingInspectorConfig).
+#[derive(Debug)]
+pub struct Eip3155Writer<W> {
+    writer: W,
+    write_stack: bool,
+    write_memory: bool,
+    write_return_data: bool,
+    /// The first error that occurred while streaming steps.
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Eip3155Writer<W> {
+    /// Create a new `Eip3155Writer` that writes to the given writer.
+    ///
+    /// By default, only the stack is written.
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            write_stack: true,
+            write_memory: false,
+            write_return_data: false,
+            error: None,
+        }
+    }
+
+    /// Write the stack of each step. Default: true.
+    pub const fn write_stack(mut self, yes: bool) -> Self {
+        self.write_stack = yes;
+        self
+    }
+
+    /// Write the memory of each step. Default: false.
+    pub const fn write_memory(mut self, yes: bool) -> Self {
+        self.write_memory = yes;
+        self
+    }
+
+    /// Write the return data buffer of each step. Default: false.
+    pub const fn write_return_data(mut self, yes: bool) -> Self {
+        self.write_return_data = yes;
+        self
+    }
+
+    /// Returns a reference to the inner writer.
+    pub const fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Consumes the writer and returns the inner writer.
+    ///
+    /// Returns the first error that occurred while streaming steps, if any.
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.writer),
+        }
+    }
+
+    /// Writes all recorded steps of the arena in execution order.
+    pub fn write_arena(&mut self, arena: &CallTraceArena) -> io::Result<()> {
+        self.write_node(arena.nodes(), 0)
+    }
+
+    /// Writes the steps of the node and its children in execution order.
+    fn write_node(&mut self, nodes: &[CallTraceNode], idx: usize) -> io::Result<()> {
+        let node = &nodes[idx];
+        for order in &node.ordering {
+            match *order {
+                TraceMemberOrder::Step(step_idx) => self.write_step(&node.trace.steps[step_idx])?,
+                TraceMemberOrder::Call(child_idx) => {
+                    self.write_node(nodes, node.children[child_idx])?
+                }
+                TraceMemberOrder::Log(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a single step as JSON line.
+    pub fn write_step(&mut self, step: &CallTraceStep) -> io::Result<()> {
+        let mut line = Map::new();
+        line.insert("pc".into(), step.pc.into());
+        line.insert("op".into(), step.op.get().into());
+        line.insert("gas".into(), format!("{:#x}", step.gas_remaining).into());
+        line.insert("gasCost".into(), format!("{:#x}", step.gas_cost).into());
+        if self.write_memory {
+            if let Some(memory) = &step.memory {
+                line.insert("memory".into(), hex::encode_prefixed(memory.as_bytes()).into());
+            }
+        }
+        line.insert("memSize".into(), step.memory_size.into());
+        let stack = if self.write_stack { step.stack.as_deref() } else { None };
+        line.insert(
+            "stack".into(),
+            stack.unwrap_or_default().iter().map(|item| format!("{item:#x}")).collect(),
+        );
+        if self.write_return_data {
+            line.insert("returnData".into(), step.returndata.to_string().into());
+        }
+        line.insert("depth".into(), step.depth.into());
+        line.insert("refund".into(), step.gas_refund_counter.into());
+        line.insert("opName".into(), step.op.as_str().into());
+        if let Some(err) =
+            step.status.and_then(|status| utils::fmt_error_msg(status, TraceStyle::Geth))
+        {
+            line.insert("error".into(), err.into());
+        }
+        self.write_line(&Value::Object(line))
+    }
+
+    /// Writes the summary line that concludes the trace of a transaction.
+    pub fn write_summary(&mut self, summary: &Eip3155Summary) -> io::Result<()> {
+        let mut line = json!({
+            "stateRoot": summary.state_root,
+            "output": summary.output,
+            "gasUsed": format!("{:#x}", summary.gas_used),
+            "pass": summary.pass,
+        });
+        if let Some(time) = summary.time {
+            line["time"] = time.into();
+        }
+        if let Some(fork) = &summary.fork {
+            line["fork"] = fork.as_str().into();
+        }
+        self.write_line(&line)
+    }
+
+    fn write_line(&mut self, line: &Value) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, line)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+impl<W: Write> StepSink for Eip3155Writer<W> {
+    fn record_step(&mut self, _node: &CallTraceNode, _step_idx: usize, step: CallTraceStep) {
+        if self.error.is_none() {
+            self.error = self.write_step(&step).err();
+        }
+    }
+}
+
+/// The summary line of an [EIP-3155](https://eips.ethereum.org/EIPS/eip-3155) trace, see
+/// [Eip3155Writer::write_summary].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Eip3155Summary {
+    /// The state root after the transaction.
+    ///
+    /// This is zero if the state root is not computed.
+    pub state_root: B256,
+    /// The output of the transaction.
+    pub output: Bytes,
+    /// The gas used by the transaction.
+    pub gas_used: u64,
+    /// Whether the transaction was successful.
+    pub pass: bool,
+    /// The execution time in nanoseconds, if measured.
+    pub time: Option<u64>,
+    /// The name of the fork rules used for execution, if known.
+    pub fork: Option<String>,
+}
+
+impl<H> From<&ExecutionResult<H>> for Eip3155Summary {
+    fn from(result: &ExecutionResult<H>) -> Self {
+        Self {
+            output: result.output().cloned().unwrap_or_default(),
+            gas_used: result.gas_used(),
+            pass: result.is_success(),
+            ..Default::default()
+        }
+    }
+}
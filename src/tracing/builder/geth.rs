@@ -1,20 +1,28 @@
 //! Geth trace builder
 use crate::tracing::{
-    types::{CallTraceNode, CallTraceStepStackItem},
+    types::{CallKind, CallTraceNode, CallTraceStep, CallTraceStepStackItem},
     utils::load_account_code,
 };
 use alloc::{
     borrow::Cow,
-    collections::{BTreeMap, VecDeque},
+    collections::{btree_map, BTreeMap, BTreeSet, VecDeque},
+    string::ToString,
+    vec,
     vec::Vec,
 };
-use alloy_primitives::{map::HashMap, Address, Bytes, B256, U256};
+use alloy_primitives::{
+    map::{HashMap, HashSet},
+    Address, Bytes, B256, U256,
+};
 use alloy_rpc_types_trace::geth::{
+    erc7562::{CallFrameType, ContractSize, Erc7562Config, Erc7562Frame},
     AccountChangeKind, AccountState, CallConfig, CallFrame, DefaultFrame, DiffMode,
     GethDefaultTracingOptions, PreStateConfig, PreStateFrame, PreStateMode, StructLog,
 };
 use revm::{
+    bytecode::opcode::{self, OpCode},
     context_interface::result::{HaltReasonTr, ResultAndState},
+    interpreter::InstructionResult,
     state::EvmState,
     DatabaseRef,
 };
@@ -63,16 +71,34 @@ impl<'a> GethTraceBuilder<'a> {
 
         main_trace_node.push_steps_on_stack(&mut step_stack);
 
+        // The transient storage of each contract, TSTORE steps report this instead of the
+        // persistent storage.
+        let mut transient_storage: HashMap<Address, BTreeMap<B256, B256>> = HashMap::default();
+
+        // a limit of 0 means no limit
+        let limit = opts.limit.filter(|limit| *limit > 0).map(|limit| limit as usize);
+
         // Iterate over the steps inside the given trace
         while let Some(CallTraceStepStackItem { trace_node, step, call_child_id }) =
             step_stack.pop_back()
         {
+            if limit.is_some_and(|limit| struct_logs.len() >= limit) {
+                // like geth, stop recording struct logs once the limit is reached
+                break;
+            }
+
             let mut log = step.convert_to_geth_struct_log(opts);
 
             // Fill in memory and storage depending on the options
             if opts.is_storage_enabled() {
-                let contract_storage = storage.entry(step.contract).or_default();
                 if let Some(change) = step.storage_change {
+                    // transient storage writes are tracked separately so they don't leak into the
+                    // persistent storage of the contract
+                    let contract_storage = if change.reason.is_transient() {
+                        transient_storage.entry(step.contract).or_default()
+                    } else {
+                        storage.entry(step.contract).or_default()
+                    };
                     contract_storage.insert(change.key.into(), change.value.into());
                     log.storage = Some(contract_storage.clone());
                 }
@@ -99,6 +125,9 @@ impl<'a> GethTraceBuilder<'a> {
     /// This expects the gas used and return value for the
     /// [[revm::context::result::ExecutionResult]] of the executed
     /// transaction.
+    ///
+    /// Like geth, this returns at most [GethDefaultTracingOptions::limit] struct logs, if set, and
+    /// the return value is only included if the transaction succeeded or reverted.
     pub fn geth_traces(
         &self,
         receipt_gas_used: u64,
@@ -116,9 +145,15 @@ impl<'a> GethTraceBuilder<'a> {
         let mut storage = HashMap::default();
         self.fill_geth_trace(main_trace_node, &opts, &mut storage, &mut struct_logs);
 
+        // geth returns the output if the transaction succeeded and the revert reason if it
+        // reverted, otherwise the return value is empty
+        let failed = !main_trace.success;
+        let return_value =
+            if failed && !main_trace.is_revert() { Bytes::new() } else { return_value };
+
         DefaultFrame {
             // If the top-level trace succeeded, then it was a success
-            failed: !main_trace.success,
+            failed,
             gas: receipt_gas_used,
             return_value,
             struct_logs,
@@ -191,6 +226,64 @@ impl<'a> GethTraceBuilder<'a> {
         }
     }
 
+    /// Generate a geth-style trace for the `erc7562Tracer`, used to validate ERC-4337
+    /// UserOperations.
+    ///
+    /// This requires the traces to be recorded with
+    /// [TracingInspectorConfig::from_geth_erc7562_config](crate::tracing::TracingInspectorConfig::from_geth_erc7562_config).
+    ///
+    /// Contract sizes are resolved from the post-transaction state, falling back to the database
+    /// for accounts that were not touched by the transaction.
+    ///
+    /// * `gas_used` - The gas used by the transaction.
+    /// * `state` - The state post-transaction execution.
+    /// * `db` - The database to fetch state pre-transaction execution.
+    pub fn geth_erc7562_traces<DB: DatabaseRef>(
+        &self,
+        config: &Erc7562Config,
+        gas_used: u64,
+        ResultAndState { state, .. }: &ResultAndState<impl HaltReasonTr>,
+        db: DB,
+    ) -> Result<Erc7562Frame, DB::Error> {
+        if self.nodes.is_empty() {
+            return Ok(Default::default());
+        }
+
+        let include_logs = config.with_log.unwrap_or_default();
+        let ignored_opcodes = if config.ignored_opcodes.is_empty() {
+            default_erc7562_ignored_opcodes()
+        } else {
+            config.ignored_opcodes.iter().copied().collect()
+        };
+
+        let mut keccak = Vec::new();
+        let mut frames = Vec::with_capacity(self.nodes.len());
+        for node in self.nodes.iter() {
+            // include logs only if call and all its parents were successful
+            let include_logs = include_logs && !self.call_or_parent_failed(node);
+            let frame =
+                erc7562_frame(node, include_logs, &ignored_opcodes, state, &db, &mut keccak)?;
+            frames.push(frame);
+        }
+
+        frames[0].gas_used = gas_used;
+
+        // roll up the child frames to their parent, this works because `child idx > parent idx`
+        loop {
+            let call = frames.pop().expect("call frames not empty");
+            let node = &self.nodes[frames.len()];
+            if let Some(parent) = node.parent {
+                frames[parent].calls.insert(0, call);
+            } else {
+                debug_assert!(frames.is_empty(), "only one root node has no parent");
+                let mut root = call;
+                // keccak preimages are collected for the entire transaction
+                root.keccak = keccak;
+                return Ok(root);
+            }
+        }
+    }
+
     /// Returns true if the given trace or any of its parents failed.
     fn call_or_parent_failed(&self, node: &CallTraceNode) -> bool {
         if node.trace.is_error() {
@@ -214,6 +307,10 @@ impl<'a> GethTraceBuilder<'a> {
     /// The prestate mode returns the accounts necessary to execute a given transaction.
     /// diff_mode returns the differences between the transaction's pre and post-state.
     ///
+    /// In prestate mode, accounts and storage slots that were only read are included if the
+    /// traces were recorded with
+    /// [TracingInspectorConfig::record_accessed_state](crate::tracing::TracingInspectorConfig::record_accessed_state).
+    ///
     /// * `state` - The state post-transaction execution.
     /// * `diff_mode` - if prestate is in diff or prestate mode.
     /// * `db` - The database to fetch state pre-transaction execution.
@@ -239,12 +336,11 @@ impl<'a> GethTraceBuilder<'a> {
         code_enabled: bool,
         storage_enabled: bool,
     ) -> Result<PreStateFrame, DB::Error> {
-        let account_diffs = state.iter().map(|(addr, acc)| (*addr, acc));
         let mut prestate = PreStateMode::default();
 
         // we only want changed accounts for things like balance changes etc
-        for (addr, changed_acc) in account_diffs {
-            let db_acc = db.basic_ref(addr)?.unwrap_or_default();
+        for (addr, changed_acc) in state.iter() {
+            let db_acc = db.basic_ref(*addr)?.unwrap_or_default();
             let code = code_enabled.then(|| load_account_code(&db, &db_acc)).flatten();
             let mut acc_state = AccountState::from_account_info(db_acc.nonce, db_acc.balance, code);
 
@@ -255,12 +351,48 @@ impl<'a> GethTraceBuilder<'a> {
                 }
             }
 
-            prestate.0.insert(addr, acc_state);
+            prestate.0.insert(*addr, acc_state);
+        }
+
+        // include all accounts and storage slots that were only read
+        for (addr, slots) in self.accessed_state() {
+            let acc_state = match prestate.0.entry(addr) {
+                btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                btree_map::Entry::Vacant(entry) => {
+                    let db_acc = db.basic_ref(addr)?.unwrap_or_default();
+                    let code = code_enabled.then(|| load_account_code(&db, &db_acc)).flatten();
+                    entry.insert(AccountState::from_account_info(
+                        db_acc.nonce,
+                        db_acc.balance,
+                        code,
+                    ))
+                }
+            };
+
+            if storage_enabled {
+                for slot in slots {
+                    if let btree_map::Entry::Vacant(entry) = acc_state.storage.entry(slot) {
+                        entry.insert(db.storage_ref(addr, slot.into())?.into());
+                    }
+                }
+            }
         }
 
         Ok(PreStateFrame::Default(prestate))
     }
 
+    /// Returns all accounts and storage slots accessed during execution, as recorded in
+    /// [CallTraceNode::accessed_state].
+    fn accessed_state(&self) -> BTreeMap<Address, BTreeSet<B256>> {
+        let mut accessed = BTreeMap::<Address, BTreeSet<B256>>::new();
+        for node in self.nodes.iter() {
+            for (addr, slots) in &node.accessed_state {
+                accessed.entry(*addr).or_default().extend(slots);
+            }
+        }
+        accessed
+    }
+
     fn geth_prestate_diff_traces<DB: DatabaseRef>(
         &self,
         state: &EvmState,
@@ -348,3 +480,197 @@ impl<'a> GethTraceBuilder<'a> {
         });
     }
 }
+
+/// Converts the given node into an [Erc7562Frame], without its child calls.
+///
+/// See also <https://github.com/ethereum/go-ethereum/blob/master/eth/tracers/native/erc7562.go>
+fn erc7562_frame<DB: DatabaseRef>(
+    node: &CallTraceNode,
+    include_logs: bool,
+    ignored_opcodes: &HashSet<u8>,
+    state: &EvmState,
+    db: &DB,
+    keccak: &mut Vec<Bytes>,
+) -> Result<Erc7562Frame, DB::Error> {
+    let call_frame = node.geth_empty_call_frame(include_logs);
+    let mut frame = Erc7562Frame {
+        call_frame_type: match node.kind() {
+            CallKind::Call | CallKind::AuthCall => CallFrameType::Call,
+            CallKind::StaticCall => CallFrameType::StaticCall,
+            CallKind::CallCode => CallFrameType::CallCode,
+            CallKind::DelegateCall => CallFrameType::DelegateCall,
+            CallKind::Create => CallFrameType::Create,
+            CallKind::Create2 => CallFrameType::Create2,
+        },
+        from: call_frame.from,
+        gas: call_frame.gas.saturating_to(),
+        gas_used: call_frame.gas_used.saturating_to(),
+        to: call_frame.to,
+        input: call_frame.input,
+        output: call_frame.output,
+        error: call_frame.error,
+        revert_reason: call_frame.revert_reason,
+        logs: call_frame.logs,
+        value: call_frame.value,
+        out_of_gas: node.status().is_some_and(|status| {
+            matches!(
+                status,
+                InstructionResult::OutOfGas
+                    | InstructionResult::MemoryOOG
+                    | InstructionResult::MemoryLimitOOG
+                    | InstructionResult::PrecompileOOG
+                    | InstructionResult::InvalidOperandOOG
+                    | InstructionResult::ReentrancySentryOOG
+            )
+        }),
+        ..Default::default()
+    };
+
+    let steps = &node.trace.steps;
+    for (idx, step) in steps.iter().enumerate() {
+        let op = step.op;
+        let prev = idx.checked_sub(1).map(|prev| &steps[prev]);
+
+        if let Some(prev) = prev {
+            // [OP-051] EXTCODESIZE followed by ISZERO is allowed
+            if is_ext_code_op(prev.op)
+                && !(prev.op.get() == opcode::EXTCODESIZE && op.get() == opcode::ISZERO)
+            {
+                if let Some(address) = stack_address(prev, 0) {
+                    frame.ext_code_access_info.push(address.to_string());
+                }
+            }
+        }
+
+        // [OP-041] record the code size of accessed contracts
+        if is_ext_code_op(op) || is_call_op(op) {
+            let n = if is_ext_code_op(op) { 0 } else { 1 };
+            if let Some(address) = stack_address(step, n) {
+                if !frame.contract_size.contains_key(&address) && !is_allowed_precompile(address) {
+                    let contract_size = code_size(address, state, db)?;
+                    frame
+                        .contract_size
+                        .insert(address, ContractSize { contract_size, opcode: op.get() });
+                }
+            }
+        }
+
+        // [OP-012] GAS is only allowed if followed by a call
+        if prev.is_some_and(|prev| prev.op.get() == opcode::GAS) && !is_call_op(op) {
+            *frame.used_opcodes.entry(opcode::GAS).or_default() += 1;
+        }
+        if op.get() != opcode::GAS && !ignored_opcodes.contains(&op.get()) {
+            *frame.used_opcodes.entry(op.get()).or_default() += 1;
+        }
+
+        match op.get() {
+            opcode::SLOAD | opcode::SSTORE | opcode::TLOAD | opcode::TSTORE => {
+                let Some(slot) = stack_item(step, 0).map(B256::from) else { continue };
+                let slots = &mut frame.accessed_slots;
+                match op.get() {
+                    opcode::SLOAD => {
+                        // only record the value before the slot was written
+                        if !slots.reads.contains_key(&slot) && !slots.writes.contains_key(&slot) {
+                            let value = steps
+                                .get(idx + 1)
+                                .and_then(|next| next.stack.as_ref()?.last().copied())
+                                .or_else(|| step.push_stack.as_ref()?.last().copied());
+                            if let Some(value) = value {
+                                slots.reads.insert(slot, vec![value.into()]);
+                            }
+                        }
+                    }
+                    opcode::SSTORE => *slots.writes.entry(slot).or_default() += 1,
+                    opcode::TLOAD => *slots.transient_reads.entry(slot).or_default() += 1,
+                    _ => *slots.transient_writes.entry(slot).or_default() += 1,
+                }
+            }
+            opcode::KECCAK256 => {
+                let (Some(offset), Some(len)) = (stack_item(step, 0), stack_item(step, 1)) else {
+                    continue;
+                };
+                let Some(memory) = &step.memory else { continue };
+                let (offset, len) = (offset.saturating_to::<usize>(), len.saturating_to::<usize>());
+                let memory = memory.as_bytes();
+                // memory is padded with zeros
+                let mut preimage = vec![0u8; len];
+                if offset < memory.len() {
+                    let available = (memory.len() - offset).min(len);
+                    preimage[..available].copy_from_slice(&memory[offset..offset + available]);
+                }
+                let preimage = Bytes::from(preimage);
+                if !keccak.contains(&preimage) {
+                    keccak.push(preimage);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(frame)
+}
+
+/// Returns the `n`th item from the top of the stack recorded before the step was executed.
+fn stack_item(step: &CallTraceStep, n: usize) -> Option<U256> {
+    let stack = step.stack.as_ref()?;
+    stack.len().checked_sub(n + 1).map(|idx| stack[idx])
+}
+
+/// Returns the `n`th item from the top of the stack as address.
+fn stack_address(step: &CallTraceStep, n: usize) -> Option<Address> {
+    stack_item(step, n).map(|item| Address::from_word(item.into()))
+}
+
+/// Returns the size of the code of the given account.
+fn code_size<DB: DatabaseRef>(
+    address: Address,
+    state: &EvmState,
+    db: &DB,
+) -> Result<u64, DB::Error> {
+    let info = match state.get(&address) {
+        Some(account) => account.info.clone(),
+        None => db.basic_ref(address)?.unwrap_or_default(),
+    };
+    Ok(load_account_code(db, &info).map(|code| code.len() as u64).unwrap_or_default())
+}
+
+/// Returns true if the opcode is EXTCODESIZE, EXTCODEHASH or EXTCODECOPY.
+const fn is_ext_code_op(op: OpCode) -> bool {
+    matches!(op.get(), opcode::EXTCODESIZE | opcode::EXTCODEHASH | opcode::EXTCODECOPY)
+}
+
+/// Returns true if the opcode is CALL, CALLCODE, DELEGATECALL or STATICCALL.
+const fn is_call_op(op: OpCode) -> bool {
+    matches!(op.get(), opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL)
+}
+
+/// Returns true if the address is one of the precompiles that are allowed to be accessed.
+fn is_allowed_precompile(address: Address) -> bool {
+    let address = U256::from_be_bytes(address.into_word().0);
+    address > U256::ZERO && address <= U256::from(10)
+}
+
+/// Returns the opcodes that are not tracked in [Erc7562Frame::used_opcodes] by default.
+fn default_erc7562_ignored_opcodes() -> HashSet<u8> {
+    // all PUSHx, DUPx and SWAPx opcodes have sequential codes
+    (opcode::PUSH0..opcode::SWAP16)
+        .chain([
+            opcode::POP,
+            opcode::ADD,
+            opcode::SUB,
+            opcode::MUL,
+            opcode::DIV,
+            opcode::EQ,
+            opcode::LT,
+            opcode::GT,
+            opcode::SLT,
+            opcode::SGT,
+            opcode::SHL,
+            opcode::SHR,
+            opcode::AND,
+            opcode::OR,
+            opcode::NOT,
+            opcode::ISZERO,
+        ])
+        .collect()
+}
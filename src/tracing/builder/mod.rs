@@ -6,5 +6,6 @@ pub mod geth;
 /// Parity style trace builders for `trace_` namespace
 pub mod parity;
 
-/// Walker types used for traversing various callgraphs
-mod walker;
+/// EIP-3155 style step traces
+#[cfg(feature = "std")]
+pub mod eip3155;
@@ -1,17 +1,26 @@
-use super::walker::CallTraceNodeWalkerBF;
 use crate::tracing::{
+    config::TraceStyle,
     types::{CallTraceNode, CallTraceStep},
-    utils::load_account_code,
+    utils::{self, load_account_code},
     TracingInspectorConfig,
 };
-use alloc::{collections::VecDeque, string::ToString, vec, vec::Vec};
-use alloy_primitives::{map::HashSet, Address, U256, U64};
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use alloy_primitives::{map::HashSet, Address, Bytes, U256, U64};
 use alloy_rpc_types_eth::TransactionInfo;
-use alloy_rpc_types_trace::parity::*;
-use core::iter::Peekable;
+use alloy_rpc_types_trace::{
+    geth::{call::FlatCallFrame, FlatCallConfig},
+    parity::*,
+};
+use core::{convert::Infallible, iter::Peekable};
 use revm::{
     context_interface::result::{ExecutionResult, HaltReasonTr, ResultAndState},
-    primitives::{hardfork::SpecId, KECCAK_EMPTY},
+    primitives::hardfork::SpecId,
     state::Account,
     DatabaseRef,
 };
@@ -23,6 +32,8 @@ use revm::{
 pub struct ParityTraceBuilder {
     /// Recorded trace nodes
     nodes: Vec<CallTraceNode>,
+    /// Whether to append the decoded revert reason to the error of reverted traces
+    revert_reasons: bool,
 }
 
 impl ParityTraceBuilder {
@@ -32,7 +43,16 @@ impl ParityTraceBuilder {
         _spec_id: Option<SpecId>,
         _config: TracingInspectorConfig,
     ) -> Self {
-        Self { nodes }
+        Self { nodes, revert_reasons: false }
+    }
+
+    /// Appends the decoded revert reason to the error of reverted traces, e.g.
+    /// `Reverted: panic: division or modulo by zero (0x12)`. Default: false.
+    ///
+    /// See also [RevertReason](crate::tracing::RevertReason).
+    pub const fn with_revert_reasons(mut self, yes: bool) -> Self {
+        self.revert_reasons = yes;
+        self
     }
 
     /// Returns a list of all addresses that appeared as callers.
@@ -95,28 +115,17 @@ impl ParityTraceBuilder {
             return graph;
         }
         while let Some(parent) = node.parent {
-            // the index of the child call in the arena
-            let child_idx = node.idx;
+            let child = node;
             node = &self.nodes[parent];
             // find the index of the child call in the parent node
-            let call_idx = node
-                .children
-                .iter()
-                .position(|child| *child == child_idx)
-                .expect("non precompile child call exists in parent");
+            let call_idx =
+                child.position_in_parent(node).expect("non precompile child call exists in parent");
             graph.push(call_idx);
         }
         graph.reverse();
         graph
     }
 
-    /// Returns an iterator over all nodes to trace
-    ///
-    /// This excludes nodes that represent calls to precompiles.
-    fn iter_traceable_nodes(&self) -> impl Iterator<Item = &CallTraceNode> {
-        self.nodes.iter().filter(|node| !node.is_precompile())
-    }
-
     /// Returns an iterator over all recorded traces  for `trace_transaction`
     pub fn into_localized_transaction_traces_iter(
         self,
@@ -142,6 +151,39 @@ impl ParityTraceBuilder {
         self.into_localized_transaction_traces_iter(info).collect()
     }
 
+    /// Returns the traces of geth's `flatCallTracer`.
+    ///
+    /// Unlike [Self::into_localized_transaction_traces], errors are reported with geth's error
+    /// messages unless [FlatCallConfig::convert_parity_errors] is set, and calls to precompiles
+    /// are included if [FlatCallConfig::include_precompiles] is set.
+    ///
+    /// Note: calls to precompiles can only be told apart if they were recorded with
+    /// [TracingInspectorConfig::exclude_precompile_calls] enabled, see also
+    /// [TracingInspectorConfig::from_flat_call_config].
+    ///
+    /// See also <https://github.com/ethereum/go-ethereum/blob/master/eth/tracers/native/call_flat.go>
+    pub fn into_flat_call_traces(
+        self,
+        config: &FlatCallConfig,
+        info: TransactionInfo,
+    ) -> FlatCallFrame {
+        let convert_parity_errors = config.convert_parity_errors.unwrap_or_default();
+        let TransactionInfo { hash, index, block_hash, block_number, .. } = info;
+        self.transaction_traces_with(config.include_precompiles.unwrap_or_default(), |node| {
+            let error = node.trace.as_error_msg(TraceStyle::Geth)?;
+            Some(if convert_parity_errors { utils::convert_error_to_parity(&error) } else { error })
+        })
+        .into_iter()
+        .map(|trace| LocalizedTransactionTrace {
+            trace,
+            transaction_position: index,
+            transaction_hash: hash,
+            block_number,
+            block_hash,
+        })
+        .collect()
+    }
+
     /// Consumes the inspector and returns the trace results according to the configured trace
     /// types.
     ///
@@ -178,26 +220,22 @@ impl ParityTraceBuilder {
     ) -> Result<TraceResults, DB::Error> {
         let ResultAndState { ref result, ref state } = res;
 
-        let breadth_first_addresses = if trace_types.contains(&TraceType::VmTrace) {
-            CallTraceNodeWalkerBF::new(&self.nodes)
-                .map(|node| node.trace.address)
-                .collect::<Vec<_>>()
+        // the vm trace is built with the code of all frames loaded from the database
+        let mut trace_types = trace_types.clone();
+        let vm_trace = if trace_types.remove(&TraceType::VmTrace) {
+            Some(self.vm_trace_with_db(&db)?)
         } else {
-            vec![]
+            None
         };
 
-        let mut trace_res = self.into_trace_results(result, trace_types);
+        let mut trace_res = self.into_trace_results(result, &trace_types);
+        trace_res.vm_trace = vm_trace;
 
         // check the state diff case
         if let Some(ref mut state_diff) = trace_res.state_diff {
             populate_state_diff(state_diff, &db, state.iter())?;
         }
 
-        // check the vm trace case
-        if let Some(ref mut vm_trace) = trace_res.vm_trace {
-            populate_vm_trace_bytecodes(&db, vm_trace, breadth_first_addresses)?;
-        }
-
         Ok(trace_res)
     }
 
@@ -236,13 +274,31 @@ impl ParityTraceBuilder {
     /// Selfdestructs appear as individual [`TransactionTrace`] instance but selfdestructs are
     /// tracked as metadata of the recorded nodes.
     fn transaction_traces(&self) -> Vec<TransactionTrace> {
+        self.transaction_traces_with(false, |node| parity_error_msg(node, self.revert_reasons))
+    }
+
+    /// Returns all the ordered [`TransactionTrace`], including selfdestructs.
+    ///
+    /// If `include_precompiles` is set, calls to precompiles are treated like any other call,
+    /// otherwise they're skipped. The error message of each trace is produced by `error_msg`.
+    fn transaction_traces_with(
+        &self,
+        include_precompiles: bool,
+        error_msg: impl Fn(&CallTraceNode) -> Option<String>,
+    ) -> Vec<TransactionTrace> {
         let mut traces = Vec::with_capacity(self.nodes.len());
         // Boolean marker to track if sorting for selfdestruct is needed
         let mut sorting_selfdestruct = false;
 
-        for node in self.iter_traceable_nodes() {
-            let trace_address = self.trace_address(node.idx);
-            let trace = node.parity_transaction_trace(trace_address);
+        for (node, (trace_address, subtraces)) in
+            self.nodes.iter().zip(self.flat_trace_addresses(include_precompiles))
+        {
+            if node.is_precompile() && !include_precompiles {
+                continue;
+            }
+            let mut trace = node.parity_transaction_trace(trace_address);
+            trace.subtraces = subtraces;
+            trace.error = error_msg(node);
             traces.push(trace);
 
             if node.is_selfdestruct() {
@@ -272,9 +328,33 @@ impl ParityTraceBuilder {
         traces
     }
 
+    /// Returns the trace address and the number of subtraces of every node in the set.
+    ///
+    /// Unlike [Self::trace_address], this also assigns trace addresses to calls to precompiles if
+    /// `include_precompiles` is set. Otherwise calls to precompiles are not counted as subtraces
+    /// and have an empty trace address.
+    fn flat_trace_addresses(&self, include_precompiles: bool) -> Vec<(Vec<usize>, usize)> {
+        let mut addresses: Vec<(Vec<usize>, usize)> = vec![Default::default(); self.nodes.len()];
+        // child nodes are always recorded after their parent, in execution order
+        for node in self.nodes.iter().skip(1) {
+            if node.is_precompile() && !include_precompiles {
+                continue;
+            }
+            let Some(parent) = node.parent else { continue };
+            let (parent_address, subtraces) = &mut addresses[parent];
+            let mut address = Vec::with_capacity(parent_address.len() + 1);
+            address.extend_from_slice(parent_address);
+            address.push(node.original_position.unwrap_or(*subtraces));
+            *subtraces += 1;
+            addresses[node.idx].0 = address;
+        }
+        addresses
+    }
+
     /// Returns an iterator over all recorded traces  for `trace_transaction`
     pub fn into_transaction_traces_iter(self) -> impl Iterator<Item = TransactionTrace> {
         let trace_addresses = self.trace_addresses();
+        let revert_reasons = self.revert_reasons;
         TransactionTraceIter {
             next_selfdestructs: Default::default(),
             iter: self
@@ -282,7 +362,11 @@ impl ParityTraceBuilder {
                 .into_iter()
                 .zip(trace_addresses)
                 .filter(|(node, _)| !node.is_precompile())
-                .map(|(node, trace_address)| (node.parity_transaction_trace(trace_address), node))
+                .map(move |(node, trace_address)| {
+                    let mut trace = node.parity_transaction_trace(trace_address);
+                    trace.error = parity_error_msg(&node, revert_reasons);
+                    (trace, node)
+                })
                 .peekable(),
         }
     }
@@ -306,15 +390,46 @@ impl ParityTraceBuilder {
 
     /// Creates a VM trace by walking over `CallTraceNode`s
     ///
-    /// does not have the code fields filled in
+    /// The code fields are filled with the bytecode captured during tracing, see
+    /// [CallTrace::code](crate::tracing::types::CallTrace::code). For contract creations this is
+    /// the init code.
+    ///
+    /// See [Self::vm_trace_with_db] for loading the code of frames that were recorded without it.
     pub fn vm_trace(&self) -> VmTrace {
-        self.nodes.first().map(|node| self.make_vm_trace(node)).unwrap_or_default()
+        let Some(start) = self.nodes.first() else { return Default::default() };
+        let Ok(trace) = self.make_vm_trace(start, &mut |node| {
+            Ok::<_, Infallible>(captured_code(node).unwrap_or_default())
+        });
+        trace
+    }
+
+    /// Creates a VM trace by walking over `CallTraceNode`s
+    ///
+    /// This is the same as [Self::vm_trace], but the code of frames that was not captured during
+    /// tracing is loaded from the given [DatabaseRef], which should point to the state before the
+    /// transaction. Contract creations always use the init code.
+    pub fn vm_trace_with_db<DB: DatabaseRef>(&self, db: DB) -> Result<VmTrace, DB::Error> {
+        let Some(start) = self.nodes.first() else { return Ok(Default::default()) };
+        self.make_vm_trace(start, &mut |node| {
+            if let Some(code) = captured_code(node) {
+                return Ok(code);
+            }
+            // the address of a call node is the address of the executed code
+            let Some(account) = db.basic_ref(node.trace.address)? else {
+                return Ok(Default::default());
+            };
+            Ok(load_account_code(&db, &account).unwrap_or_default())
+        })
     }
 
-    /// Returns a VM trace without the code filled in
+    /// Returns a VM trace with the code of each frame provided by `code`
     ///
     /// Iteratively creates a VM trace by traversing the recorded nodes in the arena
-    fn make_vm_trace(&self, start: &CallTraceNode) -> VmTrace {
+    fn make_vm_trace<E>(
+        &self,
+        start: &CallTraceNode,
+        code: &mut impl FnMut(&CallTraceNode) -> Result<Bytes, E>,
+    ) -> Result<VmTrace, E> {
         let mut child_idx_stack = Vec::with_capacity(self.nodes.len());
         let mut sub_stack = VecDeque::with_capacity(self.nodes.len());
 
@@ -353,7 +468,7 @@ impl ParityTraceBuilder {
                     match current.parent {
                         Some(parent) => {
                             sub_stack.push_back(Some(VmTrace {
-                                code: Default::default(),
+                                code: code(current)?,
                                 ops: instructions,
                             }));
 
@@ -367,7 +482,7 @@ impl ParityTraceBuilder {
             }
         };
 
-        VmTrace { code: Default::default(), ops: instructions }
+        Ok(VmTrace { code: code(start)?, ops: instructions })
     }
 
     /// Creates a VM instruction from a [CallTraceStep] and a [VmTrace] for the subcall if there is
@@ -377,15 +492,16 @@ impl ParityTraceBuilder {
         step: &CallTraceStep,
         maybe_sub_call: Option<VmTrace>,
     ) -> VmInstruction {
+        // this includes both persistent (SSTORE) and transient (TSTORE) storage writes
         let maybe_storage = step.storage_change.map(|storage_change| StorageDelta {
             key: storage_change.key,
             val: storage_change.value,
         });
 
         let maybe_memory = step
-            .memory
+            .memory_change
             .as_ref()
-            .map(|memory| MemoryDelta { off: memory.len(), data: memory.as_bytes().clone() });
+            .map(|change| MemoryDelta { off: change.offset, data: change.data.clone() });
 
         let maybe_execution = Some(VmExecutedOperation {
             used: step.gas_remaining,
@@ -405,6 +521,16 @@ impl ParityTraceBuilder {
     }
 }
 
+/// Returns the parity style error message of the node, including the decoded revert reason if
+/// `revert_reasons` is set.
+fn parity_error_msg(node: &CallTraceNode, revert_reasons: bool) -> Option<String> {
+    let error = node.trace.as_error_msg(TraceStyle::Parity)?;
+    match node.trace.revert_reason().filter(|_| revert_reasons) {
+        Some(reason) => Some(format!("{error}: {reason}")),
+        None => Some(error),
+    }
+}
+
 /// An iterator for [TransactionTrace]s
 struct TransactionTraceIter<Iter: Iterator> {
     /// The iterator over all traces
@@ -455,46 +581,14 @@ where
     }
 }
 
-/// addresses are presorted via breadth first walk thru [CallTraceNode]s, this  can be done by a
-/// walker in [crate::tracing::builder::walker]
+/// Returns the code executed by the node, if it was captured during tracing.
 ///
-/// iteratively fill the [VmTrace] code fields
-pub(crate) fn populate_vm_trace_bytecodes<DB, I>(
-    db: DB,
-    trace: &mut VmTrace,
-    breadth_first_addresses: I,
-) -> Result<(), DB::Error>
-where
-    DB: DatabaseRef,
-    I: IntoIterator<Item = Address>,
-{
-    let mut stack: VecDeque<&mut VmTrace> = VecDeque::new();
-    stack.push_back(trace);
-
-    let mut addrs = breadth_first_addresses.into_iter();
-
-    while let Some(curr_ref) = stack.pop_front() {
-        for op in curr_ref.ops.iter_mut() {
-            if let Some(sub) = op.sub.as_mut() {
-                stack.push_back(sub);
-            }
-        }
-
-        let addr = addrs.next().expect("there should be an address");
-
-        let db_acc = db.basic_ref(addr)?.unwrap_or_default();
-
-        curr_ref.code = if let Some(code) = db_acc.code {
-            code.original_bytes()
-        } else {
-            let code_hash =
-                if db_acc.code_hash != KECCAK_EMPTY { db_acc.code_hash } else { continue };
-
-            db.code_by_hash_ref(code_hash)?.original_bytes()
-        };
+/// Contract creations always execute the init code.
+fn captured_code(node: &CallTraceNode) -> Option<Bytes> {
+    if node.kind().is_any_create() {
+        return Some(node.trace.data.clone());
     }
-
-    Ok(())
+    node.trace.code.clone()
 }
 
 /// Populates [StateDiff] given iterator over [Account]s and a [DatabaseRef].
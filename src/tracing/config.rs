@@ -1,6 +1,9 @@
-use alloy_primitives::{map::HashSet, U256};
+use alloy_primitives::{map::HashSet, Address, B256, U256};
 use alloy_rpc_types_trace::{
-    geth::{CallConfig, FlatCallConfig, GethDefaultTracingOptions, PreStateConfig},
+    geth::{
+        erc7562::Erc7562Config, CallConfig, FlatCallConfig, GethDefaultTracingOptions,
+        PreStateConfig,
+    },
     parity::TraceType,
 };
 use revm::bytecode::opcode::OpCode;
@@ -48,6 +51,87 @@ impl OpcodeFilter {
     }
 }
 
+/// Limits step recording to the frames of specific contracts, see
+/// [TracingInspector::with_steps_scope](crate::tracing::TracingInspector::with_steps_scope).
+///
+/// A frame is in scope if it is executed by one of the configured addresses or code hashes, and
+/// if its depth does not exceed the configured maximum depth. If no addresses and code hashes are
+/// configured, every frame up to the maximum depth is in scope.
+///
+/// The scope is not part of the [TracingInspectorConfig], which is `Copy`, so it is set on the
+/// inspector instead. Config driven setups, such as the
+/// [MuxInspector](crate::tracing::MuxInspector) or configs combined with
+/// [TracingInspectorConfig::merge], can therefore not limit step recording to a scope.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[must_use]
+pub struct StepScope {
+    addresses: HashSet<Address>,
+    code_hashes: HashSet<B256>,
+    max_depth: Option<usize>,
+}
+
+impl StepScope {
+    /// Returns a new [StepScope] that does not limit step recording.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the steps of frames that run at or load their code from the given address.
+    ///
+    /// This includes the frames of delegate calls from the given address, e.g. the
+    /// implementation of a proxy.
+    pub fn address(mut self, address: Address) -> Self {
+        self.addresses.insert(address);
+        self
+    }
+
+    /// Records the steps of frames that execute code with the given hash.
+    ///
+    /// This selects a contract regardless of the address it is deployed at, e.g. the
+    /// implementation behind multiple proxies.
+    pub fn code_hash(mut self, code_hash: B256) -> Self {
+        self.code_hashes.insert(code_hash);
+        self
+    }
+
+    /// Only records the steps of frames with a depth of at most `depth`, the root call has depth
+    /// `0`.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Returns `true` if the scope selects frames by address or code hash.
+    #[inline]
+    pub fn has_contracts(&self) -> bool {
+        !self.addresses.is_empty() || !self.code_hashes.is_empty()
+    }
+
+    /// Returns `true` if the scope selects frames by code hash.
+    #[inline]
+    pub fn has_code_hashes(&self) -> bool {
+        !self.code_hashes.is_empty()
+    }
+
+    /// Returns whether the steps of a frame should be recorded.
+    ///
+    /// `addresses` are the addresses the frame runs at and loads its code from, `code_hash` is the
+    /// hash of the executed code, which is only required if [Self::has_code_hashes].
+    pub fn contains(
+        &self,
+        depth: usize,
+        addresses: impl IntoIterator<Item = Address>,
+        code_hash: Option<B256>,
+    ) -> bool {
+        if self.max_depth.is_some_and(|max_depth| depth > max_depth) {
+            return false;
+        }
+        !self.has_contracts()
+            || addresses.into_iter().any(|address| self.addresses.contains(&address))
+            || code_hash.is_some_and(|hash| self.code_hashes.contains(&hash))
+    }
+}
+
 /// Gives guidance to the [TracingInspector](crate::tracing::TracingInspector).
 ///
 /// Use [TracingInspectorConfig::default_parity] or [TracingInspectorConfig::default_geth] to get
@@ -58,6 +142,9 @@ pub struct TracingInspectorConfig {
     pub record_steps: bool,
     /// Whether to record individual memory snapshots.
     pub record_memory_snapshots: bool,
+    /// Whether to record the memory range written by each step, see
+    /// [CallTraceStep::memory_change](crate::tracing::types::CallTraceStep::memory_change).
+    pub record_memory_deltas: bool,
     /// Whether to record individual stack snapshots.
     pub record_stack_snapshots: StackSnapshotType,
     /// Whether to record state diffs.
@@ -73,6 +160,16 @@ pub struct TracingInspectorConfig {
     pub record_logs: bool,
     /// Whether to record immediate bytes for opcodes.
     pub record_immediate_bytes: bool,
+    /// Whether to record the state changes made by each call, see
+    /// [CallTraceNode::state_changes](crate::tracing::types::CallTraceNode::state_changes).
+    pub record_call_state_changes: bool,
+    /// Optional upper bound for the number of recorded steps across the entire transaction.
+    ///
+    /// Once this many steps have been recorded, subsequent steps are no longer recorded.
+    pub record_steps_limit: Option<usize>,
+    /// Whether to record the accounts and storage slots accessed by each call, see
+    /// [CallTraceNode::accessed_state](crate::tracing::types::CallTraceNode::accessed_state).
+    pub record_accessed_state: bool,
 }
 
 impl TracingInspectorConfig {
@@ -81,6 +178,7 @@ impl TracingInspectorConfig {
         Self {
             record_steps: true,
             record_memory_snapshots: true,
+            record_memory_deltas: true,
             record_stack_snapshots: StackSnapshotType::Full,
             record_state_diff: true,
             record_returndata_snapshots: true,
@@ -88,6 +186,9 @@ impl TracingInspectorConfig {
             exclude_precompile_calls: false,
             record_logs: true,
             record_immediate_bytes: true,
+            record_call_state_changes: true,
+            record_steps_limit: None,
+            record_accessed_state: true,
         }
     }
 
@@ -96,6 +197,7 @@ impl TracingInspectorConfig {
         Self {
             record_steps: false,
             record_memory_snapshots: false,
+            record_memory_deltas: false,
             record_stack_snapshots: StackSnapshotType::None,
             record_state_diff: false,
             record_returndata_snapshots: false,
@@ -103,6 +205,9 @@ impl TracingInspectorConfig {
             record_logs: false,
             record_opcodes_filter: None,
             record_immediate_bytes: false,
+            record_call_state_changes: false,
+            record_steps_limit: None,
+            record_accessed_state: false,
         }
     }
 
@@ -113,6 +218,7 @@ impl TracingInspectorConfig {
         Self {
             record_steps: false,
             record_memory_snapshots: false,
+            record_memory_deltas: false,
             record_stack_snapshots: StackSnapshotType::None,
             record_state_diff: false,
             record_returndata_snapshots: false,
@@ -120,6 +226,9 @@ impl TracingInspectorConfig {
             record_logs: false,
             record_opcodes_filter: None,
             record_immediate_bytes: false,
+            record_call_state_changes: false,
+            record_steps_limit: None,
+            record_accessed_state: false,
         }
     }
 
@@ -134,11 +243,14 @@ impl TracingInspectorConfig {
     }
 
     /// Returns the [`TracingInspectorConfig`] for [`TraceType::VmTrace`].
+    ///
+    /// The vmTrace only reports the memory written by each instruction, so this records memory
+    /// deltas instead of full memory snapshots.
     pub const fn parity_vm_trace() -> Self {
         Self::default_parity()
             .set_steps(true)
             .set_stack_snapshots(StackSnapshotType::Pushes)
-            .set_memory_snapshots(true)
+            .set_memory_deltas(true)
     }
 
     /// Returns a config for geth style traces.
@@ -151,6 +263,7 @@ impl TracingInspectorConfig {
         Self {
             record_steps: true,
             record_memory_snapshots: false,
+            record_memory_deltas: false,
             record_stack_snapshots: StackSnapshotType::Full,
             record_state_diff: true,
             record_returndata_snapshots: false,
@@ -158,6 +271,9 @@ impl TracingInspectorConfig {
             record_logs: false,
             record_opcodes_filter: None,
             record_immediate_bytes: false,
+            record_call_state_changes: false,
+            record_steps_limit: None,
+            record_accessed_state: false,
         }
     }
 
@@ -173,7 +289,7 @@ impl TracingInspectorConfig {
         Self::default_parity()
             .set_steps(needs_vm_trace)
             .set_stack_snapshots(snap_type)
-            .set_memory_snapshots(needs_vm_trace)
+            .set_memory_deltas(needs_vm_trace)
     }
 
     /// Returns a config for geth style traces based on the given [GethDefaultTracingOptions].
@@ -190,6 +306,8 @@ impl TracingInspectorConfig {
                 StackSnapshotType::Full
             },
             record_state_diff: !config.disable_storage.unwrap_or_default(),
+            // a limit of 0 means no limit
+            record_steps_limit: config.limit.filter(|limit| *limit > 0).map(|limit| limit as usize),
             ..Self::default_geth()
         }
     }
@@ -220,12 +338,27 @@ impl TracingInspectorConfig {
 
     /// Returns a config for geth's [PrestateTracer](alloy_rpc_types_trace::geth::PreStateFrame).
     ///
-    /// Note: This currently returns [Self::none] because the prestate tracer result currently
-    /// relies on the execution result entirely, see
+    /// This returns [Self::none] and enables [TracingInspectorConfig::record_accessed_state], so
+    /// that accounts and storage slots that were only read are included in the prestate, see
     /// [GethTraceBuilder::geth_prestate_traces](crate::tracing::geth::GethTraceBuilder::geth_prestate_traces)
     #[inline]
     pub const fn from_geth_prestate_config(_config: &PreStateConfig) -> Self {
+        Self::none().record_accessed_state()
+    }
+
+    /// Returns a config for geth's
+    /// [Erc7562Tracer](alloy_rpc_types_trace::geth::erc7562::Erc7562Frame).
+    ///
+    /// This enables step recording with full stack and memory snapshots, which are required to
+    /// inspect storage and code accesses of each call frame, and enables
+    /// [TracingInspectorConfig::record_logs] if configured in the given [Erc7562Config]
+    #[inline]
+    pub fn from_geth_erc7562_config(config: &Erc7562Config) -> Self {
         Self::none()
+            .steps()
+            .set_stack_snapshots(StackSnapshotType::All)
+            .memory_snapshots()
+            .set_record_logs(config.with_log.unwrap_or_default())
     }
 
     /// Merge another config into this one.
@@ -233,6 +366,7 @@ impl TracingInspectorConfig {
     pub fn merge(&mut self, other: Self) -> &mut Self {
         self.record_steps |= other.record_steps;
         self.record_memory_snapshots |= other.record_memory_snapshots;
+        self.record_memory_deltas |= other.record_memory_deltas;
         self.record_stack_snapshots = other.record_stack_snapshots;
         self.record_state_diff |= other.record_state_diff;
         self.record_returndata_snapshots |= other.record_returndata_snapshots;
@@ -240,6 +374,12 @@ impl TracingInspectorConfig {
         self.record_logs |= other.record_logs;
         self.record_opcodes_filter = self.record_opcodes_filter.or(other.record_opcodes_filter);
         self.record_immediate_bytes |= other.record_immediate_bytes;
+        self.record_call_state_changes |= other.record_call_state_changes;
+        self.record_accessed_state |= other.record_accessed_state;
+        self.record_steps_limit = match (self.record_steps_limit, other.record_steps_limit) {
+            (Some(limit), Some(other)) => Some(limit.max(other)),
+            (limit, other) => limit.or(other),
+        };
         self
     }
 
@@ -283,6 +423,22 @@ impl TracingInspectorConfig {
         self
     }
 
+    /// Disable recording of the memory written by each step
+    pub const fn disable_memory_deltas(self) -> Self {
+        self.set_memory_deltas(false)
+    }
+
+    /// Enable recording of the memory written by each step
+    pub const fn memory_deltas(self) -> Self {
+        self.set_memory_deltas(true)
+    }
+
+    /// Configure whether the tracer should record the memory written by each step
+    pub const fn set_memory_deltas(mut self, record_memory_deltas: bool) -> Self {
+        self.record_memory_deltas = record_memory_deltas;
+        self
+    }
+
     /// Disable recording of individual stack snapshots
     pub const fn disable_stack_snapshots(self) -> Self {
         self.set_stack_snapshots(StackSnapshotType::None)
@@ -354,6 +510,35 @@ impl TracingInspectorConfig {
         self.set_immediate_bytes(true)
     }
 
+    /// Configure whether the state changes made by each call should be recorded.
+    pub const fn set_call_state_changes(mut self, record_call_state_changes: bool) -> Self {
+        self.record_call_state_changes = record_call_state_changes;
+        self
+    }
+
+    /// Enable recording of the state changes made by each call.
+    pub const fn record_call_state_changes(self) -> Self {
+        self.set_call_state_changes(true)
+    }
+
+    /// Configure the maximum number of steps to record, see
+    /// [TracingInspectorConfig::record_steps_limit].
+    pub const fn set_steps_limit(mut self, record_steps_limit: Option<usize>) -> Self {
+        self.record_steps_limit = record_steps_limit;
+        self
+    }
+
+    /// Configure whether the accounts and storage slots accessed by each call should be recorded.
+    pub const fn set_accessed_state(mut self, record_accessed_state: bool) -> Self {
+        self.record_accessed_state = record_accessed_state;
+        self
+    }
+
+    /// Enable recording of the accounts and storage slots accessed by each call.
+    pub const fn record_accessed_state(self) -> Self {
+        self.set_accessed_state(true)
+    }
+
     /// If [OpcodeFilter] is configured, returns whether the given opcode should be recorded.
     /// Otherwise, always returns true.
     #[inline]
@@ -404,7 +589,6 @@ pub(crate) enum TraceStyle {
     /// Parity style tracer
     Parity,
     /// Geth style tracer
-    #[allow(dead_code)]
     Geth,
 }
 
@@ -443,6 +627,20 @@ mod tests {
         assert!(!config.record_state_diff);
     }
 
+    #[test]
+    fn test_geth_config_limit() {
+        let config = TracingInspectorConfig::from_geth_config(&Default::default());
+        assert_eq!(config.record_steps_limit, None);
+
+        let opts = GethDefaultTracingOptions::default().with_limit(0);
+        let config = TracingInspectorConfig::from_geth_config(&opts);
+        assert_eq!(config.record_steps_limit, None);
+
+        let opts = GethDefaultTracingOptions::default().with_limit(10);
+        let config = TracingInspectorConfig::from_geth_config(&opts);
+        assert_eq!(config.record_steps_limit, Some(10));
+    }
+
     #[test]
     fn test_flat_call_config() {
         let config = FlatCallConfig { include_precompiles: Some(true), ..Default::default() };
@@ -0,0 +1,313 @@
+//! Structural diff of two [CallTraceArena]s.
+
+use super::{
+    types::{CallKind, CallTraceNode, CallTraceStep},
+    CallTraceArena,
+};
+use alloc::{boxed::Box, vec, vec::Vec};
+use alloy_primitives::{Address, Bytes, LogData, Selector, U256};
+use revm::{bytecode::OpCode, interpreter::InstructionResult};
+
+/// Configures what is compared by [CallTraceArena::diff].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallTraceDiffConfig {
+    compare_gas: bool,
+    compare_steps: bool,
+}
+
+impl Default for CallTraceDiffConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CallTraceDiffConfig {
+    /// Creates a new config that compares gas usage, but not steps.
+    pub const fn new() -> Self {
+        Self { compare_gas: true, compare_steps: false }
+    }
+
+    /// Compare the gas used by matched calls. Default: true.
+    pub const fn compare_gas(mut self, yes: bool) -> Self {
+        self.compare_gas = yes;
+        self
+    }
+
+    /// Find the first step at which matched calls diverge, see [CallChanges::first_diverging_step].
+    /// Default: false.
+    ///
+    /// This requires steps to be recorded for both arenas.
+    pub const fn compare_steps(mut self, yes: bool) -> Self {
+        self.compare_steps = yes;
+        self
+    }
+}
+
+/// The structural diff of two [CallTraceArena]s, see [CallTraceArena::diff].
+///
+/// This can be rendered with [TraceWriter::write_diff](crate::tracing::TraceWriter::write_diff).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallTraceDiff<'a> {
+    left: &'a CallTraceArena,
+    right: &'a CallTraceArena,
+    root: CallDiff,
+}
+
+impl<'a> CallTraceDiff<'a> {
+    /// Returns the arena that was diffed against, e.g. the trace before a change.
+    pub const fn left(&self) -> &'a CallTraceArena {
+        self.left
+    }
+
+    /// Returns the arena that was diffed, e.g. the trace after a change.
+    pub const fn right(&self) -> &'a CallTraceArena {
+        self.right
+    }
+
+    /// Returns the diff of the root calls, which are always matched.
+    pub const fn root(&self) -> &CallDiff {
+        &self.root
+    }
+
+    /// Returns `true` if the arenas do not differ.
+    pub fn is_empty(&self) -> bool {
+        self.root.is_unchanged()
+    }
+}
+
+/// The diff of a single call, see [CallTraceDiff].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallDiff {
+    /// The call only exists in the right arena, contains the index of the node in the right arena.
+    Added(usize),
+    /// The call only exists in the left arena, contains the index of the node in the left arena.
+    Removed(usize),
+    /// The call exists in both arenas.
+    Matched(Box<MatchedCall>),
+}
+
+impl CallDiff {
+    /// Returns `true` if this is a matched call without changes in itself and all of its children.
+    pub fn is_unchanged(&self) -> bool {
+        match self {
+            Self::Added(_) | Self::Removed(_) => false,
+            Self::Matched(call) => {
+                call.changes.is_empty() && call.children.iter().all(Self::is_unchanged)
+            }
+        }
+    }
+}
+
+/// A call that exists in both arenas, see [CallDiff::Matched].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchedCall {
+    /// The index of the node in the left arena.
+    pub left: usize,
+    /// The index of the node in the right arena.
+    pub right: usize,
+    /// The changes of the call itself.
+    pub changes: CallChanges,
+    /// The diffs of the sub-calls, in call order.
+    pub children: Vec<CallDiff>,
+}
+
+/// The changes of a [MatchedCall], every field contains the left and right value if they differ.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallChanges {
+    /// The address of the call.
+    ///
+    /// Sub-calls are only matched if their addresses are equal, so this can only be set for the
+    /// root call.
+    pub address: Option<(Address, Address)>,
+    /// The calldata or init code.
+    pub input: Option<(Bytes, Bytes)>,
+    /// The transferred value.
+    pub value: Option<(U256, U256)>,
+    /// The output.
+    pub output: Option<(Bytes, Bytes)>,
+    /// The final status.
+    pub status: Option<(Option<InstructionResult>, Option<InstructionResult>)>,
+    /// The gas used, only compared if [CallTraceDiffConfig::compare_gas] is enabled.
+    pub gas_used: Option<(u64, u64)>,
+    /// The logs emitted by the call itself that differ.
+    pub logs: Vec<LogChange>,
+    /// The first step at which the calls diverge, only compared if
+    /// [CallTraceDiffConfig::compare_steps] is enabled.
+    pub first_diverging_step: Option<StepDivergence>,
+}
+
+impl CallChanges {
+    /// Returns `true` if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// A log that differs between two matched calls, see [CallChanges::logs].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogChange {
+    /// The index of the log within the logs of the call.
+    pub index: usize,
+    /// The log in the left call, if any.
+    pub left: Option<LogData>,
+    /// The log in the right call, if any.
+    pub right: Option<LogData>,
+}
+
+/// The first step at which two matched calls diverge, see [CallChanges::first_diverging_step].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepDivergence {
+    /// The index of the step within the steps of the call.
+    pub index: usize,
+    /// The program counter and opcode of the left step, if the left call has this many steps.
+    pub left: Option<(usize, OpCode)>,
+    /// The program counter and opcode of the right step, if the right call has this many steps.
+    pub right: Option<(usize, OpCode)>,
+}
+
+impl CallTraceArena {
+    /// Computes the structural diff between this arena (left) and the `other` arena (right).
+    ///
+    /// The root calls are always matched. The sub-calls of two matched calls are aligned by their
+    /// longest common subsequence of call kind, address and selector, so an added or removed call
+    /// does not affect the matching of its siblings. Created addresses are not compared, so that
+    /// contract creations with different nonces still match.
+    pub fn diff<'a>(&'a self, other: &'a Self, config: CallTraceDiffConfig) -> CallTraceDiff<'a> {
+        let root = diff_nodes(self.nodes(), other.nodes(), 0, 0, config);
+        CallTraceDiff { left: self, right: other, root: CallDiff::Matched(Box::new(root)) }
+    }
+}
+
+/// The key sub-calls are aligned by.
+fn call_key(node: &CallTraceNode) -> (CallKind, Option<Address>, Option<Selector>) {
+    if node.trace.kind.is_any_create() {
+        (node.trace.kind, None, None)
+    } else {
+        (node.trace.kind, Some(node.trace.address), node.selector())
+    }
+}
+
+fn diff_nodes(
+    left: &[CallTraceNode],
+    right: &[CallTraceNode],
+    left_idx: usize,
+    right_idx: usize,
+    config: CallTraceDiffConfig,
+) -> MatchedCall {
+    let (l, r) = (&left[left_idx], &right[right_idx]);
+
+    let changes = CallChanges {
+        address: (!l.trace.kind.is_any_create() || !r.trace.kind.is_any_create())
+            .then(|| changed(&l.trace.address, &r.trace.address))
+            .flatten(),
+        input: changed(&l.trace.data, &r.trace.data),
+        value: changed(&l.trace.value, &r.trace.value),
+        output: changed(&l.trace.output, &r.trace.output),
+        status: changed(&l.trace.status, &r.trace.status),
+        gas_used: config
+            .compare_gas
+            .then(|| changed(&l.trace.gas_used, &r.trace.gas_used))
+            .flatten(),
+        logs: (0..l.logs.len().max(r.logs.len()))
+            .filter_map(|index| {
+                let left = l.logs.get(index).map(|log| &log.raw_log);
+                let right = r.logs.get(index).map(|log| &log.raw_log);
+                (left != right).then(|| LogChange {
+                    index,
+                    left: left.cloned(),
+                    right: right.cloned(),
+                })
+            })
+            .collect(),
+        first_diverging_step: config
+            .compare_steps
+            .then(|| first_diverging_step(&l.trace.steps, &r.trace.steps))
+            .flatten(),
+    };
+
+    let left_keys = l.children.iter().map(|idx| call_key(&left[*idx])).collect::<Vec<_>>();
+    let right_keys = r.children.iter().map(|idx| call_key(&right[*idx])).collect::<Vec<_>>();
+    let children = align(&left_keys, &right_keys)
+        .into_iter()
+        .map(|pair| match pair {
+            (Some(i), Some(j)) => CallDiff::Matched(Box::new(diff_nodes(
+                left,
+                right,
+                l.children[i],
+                r.children[j],
+                config,
+            ))),
+            (Some(i), None) => CallDiff::Removed(l.children[i]),
+            (None, Some(j)) => CallDiff::Added(r.children[j]),
+            (None, None) => unreachable!("every aligned pair has at least one side"),
+        })
+        .collect();
+
+    MatchedCall { left: left_idx, right: right_idx, changes, children }
+}
+
+/// Returns both values if they differ.
+fn changed<T: PartialEq + Clone>(left: &T, right: &T) -> Option<(T, T)> {
+    (left != right).then(|| (left.clone(), right.clone()))
+}
+
+/// Returns the first step at which the program counter or opcode of the steps differ.
+fn first_diverging_step(left: &[CallTraceStep], right: &[CallTraceStep]) -> Option<StepDivergence> {
+    let step = |steps: &[CallTraceStep], index: usize| {
+        steps.get(index).map(|step: &CallTraceStep| (step.pc, step.op))
+    };
+    (0..left.len().max(right.len()))
+        .map(|index| StepDivergence { index, left: step(left, index), right: step(right, index) })
+        .find(|divergence| divergence.left != divergence.right)
+}
+
+/// Aligns the two sequences by their longest common subsequence.
+///
+/// Returns pairs of indices into `left` and `right`, where unmatched items only have one side.
+fn align<T: PartialEq>(left: &[T], right: &[T]) -> Vec<(Option<usize>, Option<usize>)> {
+    // lcs[i][j] is the length of the longest common subsequence of left[i..] and right[j..]
+    let mut lcs = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            lcs[i][j] = if left[i] == right[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(left.len().max(right.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if left[i] == right[j] {
+            pairs.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            pairs.push((Some(i), None));
+            i += 1;
+        } else {
+            pairs.push((None, Some(j)));
+            j += 1;
+        }
+    }
+    pairs.extend((i..left.len()).map(|i| (Some(i), None)));
+    pairs.extend((j..right.len()).map(|j| (None, Some(j))));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align_sequences() {
+        assert_eq!(
+            align(&[1, 2, 3], &[1, 3, 4]),
+            [(Some(0), Some(0)), (Some(1), None), (Some(2), Some(1)), (None, Some(2))]
+        );
+        assert_eq!(align::<u8>(&[], &[1]), [(None, Some(0))]);
+        assert!(align::<u8>(&[], &[]).is_empty());
+    }
+}
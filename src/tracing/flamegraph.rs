@@ -0,0 +1,216 @@
+//! Gas flamegraph export of a [CallTraceArena] in the folded stacks format.
+
+use super::{
+    types::{CallTraceNode, DecodedTraceStep, TraceMemberOrder},
+    CallTraceArena,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use alloy_primitives::map::HashMap;
+use core::fmt::{self, Write};
+
+/// Configuration for a [`FlamegraphWriter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FlamegraphConfig {
+    internal_calls: bool,
+    opcodes: bool,
+}
+
+impl FlamegraphConfig {
+    /// Creates a new config that only includes calls.
+    pub const fn new() -> Self {
+        Self { internal_calls: false, opcodes: false }
+    }
+
+    /// Include internal function calls, as decoded in [DecodedTraceStep::InternalCall].
+    /// Default: false.
+    ///
+    /// This requires steps to be recorded.
+    pub const fn internal_calls(mut self, yes: bool) -> Self {
+        self.internal_calls = yes;
+        self
+    }
+
+    /// Returns `true` if internal function calls are included.
+    pub const fn get_internal_calls(&self) -> bool {
+        self.internal_calls
+    }
+
+    /// Include every executed opcode as a frame. Default: false.
+    ///
+    /// This requires steps to be recorded.
+    pub const fn opcodes(mut self, yes: bool) -> Self {
+        self.opcodes = yes;
+        self
+    }
+
+    /// Returns `true` if opcodes are included.
+    pub const fn get_opcodes(&self) -> bool {
+        self.opcodes
+    }
+}
+
+/// Writes the gas usage of [call traces](CallTraceArena) in the folded stacks format to a
+/// [`Write`] writer, e.g. for [inferno](https://github.com/jonhoo/inferno) or
+/// [flamegraph.pl](https://github.com/brendangregg/FlameGraph).
+///
+/// Every line contains a unique call path, with frames separated by `;`, followed by the gas that
+/// was used by the last frame itself. Calls are named by the
+/// [decoded label](crate::tracing::types::DecodedCallTrace::label) or address of the contract and
+/// the decoded function name or selector, e.g. `Token::transfer` or
+/// `0x00000000000000000000000000000000000000AA::0xa9059cbb`.
+///
+/// The gas used by a call itself excludes the gas used by its sub-calls. Call and create opcodes
+/// are charged the gas limit passed to the sub-call, so the weight of these opcodes excludes the
+/// gas that was forwarded, and the weights of all lines add up to the gas used by the root call.
+#[derive(Clone, Debug)]
+pub struct FlamegraphWriter<W> {
+    writer: W,
+    config: FlamegraphConfig,
+}
+
+impl<W: Write> FlamegraphWriter<W> {
+    /// Create a new `FlamegraphWriter` with the given writer.
+    #[inline]
+    pub const fn new(writer: W) -> Self {
+        Self::with_config(writer, FlamegraphConfig::new())
+    }
+
+    /// Create a new `FlamegraphWriter` with the given writer and configuration.
+    pub const fn with_config(writer: W, config: FlamegraphConfig) -> Self {
+        Self { writer, config }
+    }
+
+    /// Returns a reference to the inner writer.
+    #[inline]
+    pub const fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    #[inline]
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the `FlamegraphWriter` and returns the inner writer.
+    #[inline]
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+
+    /// Writes the folded stacks of the arena, in the order the call paths were first entered.
+    pub fn write_arena(&mut self, arena: &CallTraceArena) -> fmt::Result {
+        let mut stacks = FoldedStacks::default();
+        stacks.add_node(arena.nodes(), 0, &mut Vec::new(), &self.config);
+        for (stack, gas) in stacks.stacks {
+            if gas > 0 {
+                writeln!(self.writer, "{stack} {gas}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The folded stacks and their weights, in the order they were first added.
+#[derive(Default)]
+struct FoldedStacks {
+    stacks: Vec<(String, u64)>,
+    indices: HashMap<String, usize>,
+}
+
+impl FoldedStacks {
+    /// Adds the gas to the given call path.
+    fn add(&mut self, frames: &[String], gas: u64) {
+        let stack = frames.join(";");
+        match self.indices.get(&stack) {
+            Some(&idx) => self.stacks[idx].1 += gas,
+            None => {
+                self.indices.insert(stack.clone(), self.stacks.len());
+                self.stacks.push((stack, gas));
+            }
+        }
+    }
+
+    /// Adds the gas used by the node and its sub-calls, `frames` is the path of the parent.
+    fn add_node(
+        &mut self,
+        nodes: &[CallTraceNode],
+        idx: usize,
+        frames: &mut Vec<String>,
+        config: &FlamegraphConfig,
+    ) {
+        let node = &nodes[idx];
+        let depth = frames.len();
+        frames.push(call_frame(node));
+        self.add(frames, 0);
+
+        let children_gas = node.children.iter().map(|child| nodes[*child].trace.gas_used).sum();
+        let self_gas = node.trace.gas_used.saturating_sub(children_gas);
+        let steps = &node.trace.steps;
+        let use_steps = (config.internal_calls || config.opcodes) && !steps.is_empty();
+
+        // the gas attributed to frames below the call itself
+        let mut attributed = 0u64;
+        // the open internal calls and the index of their last step
+        let mut internal_calls: Vec<usize> = Vec::new();
+        for (i, order) in node.ordering.iter().enumerate() {
+            match *order {
+                TraceMemberOrder::Step(step_idx) if use_steps => {
+                    while internal_calls.last().is_some_and(|end| *end < step_idx) {
+                        internal_calls.pop();
+                        frames.pop();
+                    }
+                    let step = &steps[step_idx];
+                    if let Some(DecodedTraceStep::InternalCall(call, end)) = &step.decoded {
+                        if config.internal_calls {
+                            internal_calls.push(*end);
+                            frames.push(sanitize(&call.func_name));
+                        }
+                    }
+
+                    // call and create opcodes are charged the gas limit of the sub-call, this
+                    // includes the stipend of value transfers which is returned to the caller
+                    let mut gas = step.gas_cost;
+                    if let Some(TraceMemberOrder::Call(child)) = node.ordering.get(i + 1) {
+                        if step.is_calllike_op() {
+                            gas = gas.saturating_sub(nodes[node.children[*child]].trace.gas_limit);
+                        }
+                    }
+                    if config.opcodes {
+                        frames.push(step.op.as_str().to_string());
+                        self.add(frames, gas);
+                        frames.pop();
+                    } else if !internal_calls.is_empty() {
+                        self.add(frames, gas);
+                    } else {
+                        continue;
+                    }
+                    attributed += gas;
+                }
+                TraceMemberOrder::Call(child) => {
+                    self.add_node(nodes, node.children[child], frames, config)
+                }
+                _ => {}
+            }
+        }
+        frames.truncate(depth + 1);
+
+        self.add(frames, self_gas.saturating_sub(attributed));
+        frames.truncate(depth);
+    }
+}
+
+/// Returns the frame of a call, e.g. `Token::transfer`.
+fn call_frame(node: &CallTraceNode) -> String {
+    let (contract, function) = node.contract_and_function();
+    sanitize(&format!("{contract}::{function}"))
+}
+
+/// Replaces the characters that separate frames and lines.
+fn sanitize(frame: &str) -> String {
+    frame.replace([';', '\n'], "_")
+}
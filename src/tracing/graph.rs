@@ -0,0 +1,333 @@
+//! Call graph export of a [CallTraceArena] in the DOT and Mermaid formats.
+
+use super::{
+    types::{CallKind, CallTraceNode},
+    CallTraceArena,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use alloy_primitives::{Address, Selector, U256};
+use core::fmt::{self, Write};
+
+/// Configuration for a [`CallGraphWriter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CallGraphConfig {
+    max_depth: Option<usize>,
+    collapse_repeated: bool,
+}
+
+impl CallGraphConfig {
+    /// Creates a new config that draws all calls.
+    pub const fn new() -> Self {
+        Self { max_depth: None, collapse_repeated: false }
+    }
+
+    /// Only draw calls up to the given depth below the root call. Default: unlimited.
+    ///
+    /// Calls at the maximum depth are annotated with the number of hidden sub-calls.
+    pub const fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Returns the maximum depth of drawn calls, if any.
+    pub const fn get_max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Collapse consecutive sub-calls with the same kind, target, function and outcome into a
+    /// single node. Default: false.
+    ///
+    /// The edge of a collapsed node shows the number of calls and their total value and gas used.
+    /// Only the sub-calls of the first call are drawn, the sub-calls of the other calls are
+    /// annotated as hidden calls.
+    pub const fn collapse_repeated(mut self, yes: bool) -> Self {
+        self.collapse_repeated = yes;
+        self
+    }
+
+    /// Returns `true` if repeated calls are collapsed.
+    pub const fn get_collapse_repeated(&self) -> bool {
+        self.collapse_repeated
+    }
+}
+
+/// Renders [call traces](CallTraceArena) as call graphs to a [`Write`] writer.
+///
+/// Nodes are labelled with the [decoded label](crate::tracing::types::DecodedCallTrace::label) or
+/// address of the called contract and the decoded function name or selector. Edges are labelled
+/// with the [CallKind], the transferred value and the gas used. Calls that failed, and all of their
+/// sub-calls, are drawn in red with dashed lines.
+///
+/// The transaction sender is drawn as an additional node that calls the root call.
+#[derive(Clone, Debug)]
+pub struct CallGraphWriter<W> {
+    writer: W,
+    config: CallGraphConfig,
+}
+
+impl<W: Write> CallGraphWriter<W> {
+    /// Create a new `CallGraphWriter` with the given writer.
+    #[inline]
+    pub const fn new(writer: W) -> Self {
+        Self::with_config(writer, CallGraphConfig::new())
+    }
+
+    /// Create a new `CallGraphWriter` with the given writer and configuration.
+    pub const fn with_config(writer: W, config: CallGraphConfig) -> Self {
+        Self { writer, config }
+    }
+
+    /// Returns a reference to the inner writer.
+    #[inline]
+    pub const fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    #[inline]
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the `CallGraphWriter` and returns the inner writer.
+    #[inline]
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+
+    /// Writes the call graph of the arena in the Graphviz DOT format.
+    pub fn write_dot(&mut self, arena: &CallTraceArena) -> fmt::Result {
+        let graph = CallGraph::new(arena, &self.config);
+
+        writeln!(self.writer, "digraph calls {{")?;
+        writeln!(self.writer, "  node [shape=box];")?;
+        writeln!(self.writer, "  sender [label=\"{}\"];", graph.sender)?;
+        for node in &graph.nodes {
+            write!(self.writer, "  n{} [label=\"{}\"", node.idx, dot_escape(&node.label))?;
+            if node.failed {
+                write!(self.writer, ", color=red, fontcolor=red, style=dashed")?;
+            }
+            writeln!(self.writer, "];")?;
+        }
+        for node in &graph.nodes {
+            let from = node.parent.map_or_else(|| "sender".into(), |parent| format!("n{parent}"));
+            write!(
+                self.writer,
+                "  {from} -> n{} [label=\"{}\"",
+                node.idx,
+                dot_escape(&node.edge_label)
+            )?;
+            if node.failed {
+                write!(self.writer, ", color=red, fontcolor=red, style=dashed")?;
+            }
+            writeln!(self.writer, "];")?;
+        }
+        writeln!(self.writer, "}}")
+    }
+
+    /// Writes the call graph of the arena as a Mermaid flowchart.
+    pub fn write_mermaid(&mut self, arena: &CallTraceArena) -> fmt::Result {
+        let graph = CallGraph::new(arena, &self.config);
+
+        writeln!(self.writer, "flowchart TD")?;
+        writeln!(self.writer, "  sender[\"{}\"]", graph.sender)?;
+        for node in &graph.nodes {
+            writeln!(self.writer, "  n{}[\"{}\"]", node.idx, mermaid_escape(&node.label))?;
+        }
+        for node in &graph.nodes {
+            let from = node.parent.map_or_else(|| "sender".into(), |parent| format!("n{parent}"));
+            writeln!(
+                self.writer,
+                "  {from} -->|\"{}\"| n{}",
+                mermaid_escape(&node.edge_label),
+                node.idx
+            )?;
+        }
+
+        // edges are numbered in the order they are defined, which is the order of the nodes
+        let failed = graph.nodes.iter().enumerate().filter(|(_, node)| node.failed);
+        let (edges, nodes): (Vec<_>, Vec<_>) =
+            failed.map(|(edge, node)| (edge.to_string(), format!("n{}", node.idx))).unzip();
+        if !nodes.is_empty() {
+            writeln!(self.writer, "  classDef failed stroke:#d00,color:#d00,stroke-dasharray:4")?;
+            writeln!(self.writer, "  class {} failed", nodes.join(","))?;
+            writeln!(
+                self.writer,
+                "  linkStyle {} stroke:#d00,color:#d00,stroke-dasharray:4",
+                edges.join(",")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The nodes of a call graph, shared by all output formats.
+struct CallGraph {
+    /// The checksummed address of the transaction sender.
+    sender: String,
+    /// The drawn calls, in depth-first order.
+    nodes: Vec<GraphNode>,
+}
+
+/// A drawn call, see [CallGraph].
+struct GraphNode {
+    /// The index of the call in the arena, used as the node id.
+    idx: usize,
+    /// The arena index of the parent call, `None` for the root call.
+    parent: Option<usize>,
+    /// The label of the node, lines are separated by `\n`.
+    label: String,
+    /// The label of the edge from the parent, lines are separated by `\n`.
+    edge_label: String,
+    /// Whether the call or one of its parents failed.
+    failed: bool,
+}
+
+/// The key consecutive sub-calls are collapsed by, see [CallGraphConfig::collapse_repeated].
+type CollapseKey = (CallKind, Address, Option<Selector>, bool);
+
+impl CallGraph {
+    fn new(arena: &CallTraceArena, config: &CallGraphConfig) -> Self {
+        let nodes = arena.nodes();
+        let mut graph = Self {
+            sender: nodes[0].trace.caller.to_checksum(None),
+            nodes: Vec::with_capacity(nodes.len()),
+        };
+        graph.add_node(nodes, &[0], None, 0, false, config);
+        graph
+    }
+
+    /// Adds the given calls as a single node, the first call is drawn with its sub-calls.
+    fn add_node(
+        &mut self,
+        nodes: &[CallTraceNode],
+        calls: &[usize],
+        parent: Option<usize>,
+        depth: usize,
+        parent_failed: bool,
+        config: &CallGraphConfig,
+    ) {
+        let node = &nodes[calls[0]];
+        let failed = parent_failed || !node.trace.success;
+
+        let mut label = node_label(node);
+        let expand = config.max_depth.is_none_or(|max_depth| depth < max_depth);
+        // the sub-calls of collapsed calls other than the first are never drawn
+        let mut hidden =
+            calls[1..].iter().map(|idx| count_calls(nodes, &nodes[*idx]) - 1).sum::<usize>();
+        if !expand {
+            hidden += count_calls(nodes, node) - 1;
+        }
+        if hidden > 0 {
+            label.push_str(&format!("\n+{hidden} hidden calls"));
+        }
+
+        self.nodes.push(GraphNode {
+            idx: node.idx,
+            parent,
+            label,
+            edge_label: edge_label(nodes, calls),
+            failed,
+        });
+        if !expand {
+            return;
+        }
+
+        let mut children = node.children.as_slice();
+        while let Some(&first) = children.first() {
+            let len = if config.collapse_repeated {
+                let key = collapse_key(&nodes[first]);
+                children.iter().take_while(|&&child| collapse_key(&nodes[child]) == key).count()
+            } else {
+                1
+            };
+            let (group, rest) = children.split_at(len);
+            self.add_node(nodes, group, Some(node.idx), depth + 1, failed, config);
+            children = rest;
+        }
+    }
+}
+
+fn collapse_key(node: &CallTraceNode) -> CollapseKey {
+    (node.trace.kind, node.trace.address, node.selector(), node.trace.success)
+}
+
+/// Returns the number of calls in the subtree of the node, including the node itself.
+fn count_calls(nodes: &[CallTraceNode], node: &CallTraceNode) -> usize {
+    let mut count = 0;
+    let mut stack = vec![node.idx];
+    while let Some(idx) = stack.pop() {
+        count += 1;
+        stack.extend_from_slice(&nodes[idx].children);
+    }
+    count
+}
+
+/// Returns the contract and function of the call.
+fn node_label(node: &CallTraceNode) -> String {
+    let (contract, function) = node.contract_and_function();
+    format!("{contract}\n{function}")
+}
+
+/// Returns the kind, value and gas used of the calls.
+fn edge_label(nodes: &[CallTraceNode], calls: &[usize]) -> String {
+    let traces = calls.iter().map(|idx| &nodes[*idx].trace);
+    let value = traces.clone().fold(U256::ZERO, |value, trace| value.saturating_add(trace.value));
+    let gas_used = traces.map(|trace| trace.gas_used).sum::<u64>();
+
+    let mut label = String::new();
+    if calls.len() > 1 {
+        label.push_str(&format!("{}x ", calls.len()));
+    }
+    label.push_str(nodes[calls[0]].trace.kind.to_str());
+    if !value.is_zero() {
+        label.push_str(&format!("\nvalue: {value}"));
+    }
+    label.push_str(&format!("\ngas: {gas_used}"));
+    label
+}
+
+/// Escapes a label for a quoted DOT string.
+fn dot_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes a label for a quoted Mermaid string.
+fn mermaid_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("#quot;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '\n' => escaped.push_str("<br/>"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_labels() {
+        assert_eq!(dot_escape("a \"b\"\\\nc"), "a \\\"b\\\"\\\\\\nc");
+        assert_eq!(mermaid_escape("f(\"<x>\")\ng"), "f(#quot;#lt;x#gt;#quot;)<br/>g");
+    }
+}
@@ -0,0 +1,422 @@
+//! Standalone HTML rendering of a [CallTraceArena].
+
+use super::{
+    types::{CallKind, CallLog, CallTrace, CallTraceNode, DecodedTraceStep, TraceMemberOrder},
+    writer::{fmt_output, function_and_inputs, num_or_hex, storage_changes, CHEATCODE_ADDRESS},
+    CallTraceArena, TraceWriterConfig,
+};
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+};
+use revm::interpreter::InstructionResult;
+use std::io::{self, Write};
+
+const STYLE: &str = r#"
+body { background: #1e1e1e; color: #d4d4d4; font: 13px/1.5 ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; margin: 16px; }
+#search { background: #252526; color: inherit; border: 1px solid #3c3c3c; padding: 4px 8px; width: 480px; font: inherit; margin-bottom: 12px; }
+details > summary { cursor: pointer; list-style: none; white-space: pre; }
+details > summary::before { content: "▸ "; color: #808080; }
+details[open] > summary::before { content: "▾ "; }
+.items { margin-left: 8px; padding-left: 12px; border-left: 1px solid #3c3c3c; }
+.line { white-space: pre; }
+.gas, .dim { color: #808080; }
+.success { color: #4ec94e; }
+.failure { color: #f14c4c; }
+.cheatcode { color: #569cd6; }
+.kind { color: #dcdc6e; }
+.log { color: #4ec9d4; }
+.match > summary { background: #3a3d41; }
+.hidden { display: none; }
+table { border-collapse: collapse; }
+td { padding: 0 12px 0 0; white-space: pre; }
+"#;
+
+const SCRIPT: &str = r#"
+const search = document.getElementById("search");
+search.addEventListener("input", () => {
+  const query = search.value.trim().toLowerCase();
+  const calls = document.querySelectorAll("details.call");
+  for (const call of calls) {
+    call.classList.remove("match");
+    call.classList.toggle("hidden", query.length > 0);
+  }
+  if (query.length === 0) return;
+  for (const call of calls) {
+    if (!call.dataset.search.includes(query)) continue;
+    call.classList.add("match");
+    for (const child of call.querySelectorAll("details.call")) child.classList.remove("hidden");
+    for (let parent = call; parent; parent = parent.parentElement.closest("details.call")) {
+      parent.classList.remove("hidden");
+      if (parent !== call) parent.open = true;
+    }
+  }
+});
+"#;
+
+/// Renders [call traces](CallTraceArena) as a standalone HTML document to an [`Write`] writer.
+///
+/// The document contains the same information as the output of the
+/// [TraceWriter](crate::tracing::TraceWriter), with collapsible calls, internal calls, recorded
+/// steps and storage changes, and a search field that filters calls by address, label, function
+/// name or selector. Styles and scripts are inlined, so the document can be viewed offline.
+///
+/// Only the [bytecode](TraceWriterConfig::write_bytecodes) and
+/// [storage change](TraceWriterConfig::write_storage_changes) options of the
+/// [`TraceWriterConfig`] apply. All other options are ignored: every call is written in full,
+/// regardless of [max_depth](TraceWriterConfig::max_depth),
+/// [min_gas](TraceWriterConfig::min_gas),
+/// [collapse_successful](TraceWriterConfig::collapse_successful),
+/// [revert_path_only](TraceWriterConfig::revert_path_only) and
+/// [elide_repeated](TraceWriterConfig::elide_repeated), and can be collapsed in the document
+/// instead. Calls are always colored like in the [TraceWriter](crate::tracing::TraceWriter) with
+/// colors enabled, and are not formatted by a [TraceDecorator](crate::tracing::TraceDecorator).
+///
+/// Will never write invalid UTF-8.
+#[derive(Clone, Debug)]
+pub struct HtmlTraceWriter<W> {
+    writer: W,
+    config: TraceWriterConfig,
+}
+
+impl<W: Write> HtmlTraceWriter<W> {
+    /// Create a new `HtmlTraceWriter` with the given writer.
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        Self::with_config(writer, TraceWriterConfig::new())
+    }
+
+    /// Create a new `HtmlTraceWriter` with the given writer and configuration.
+    pub const fn with_config(writer: W, config: TraceWriterConfig) -> Self {
+        Self { writer, config }
+    }
+
+    /// Returns a reference to the inner writer.
+    #[inline]
+    pub const fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    #[inline]
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consumes the `HtmlTraceWriter` and returns the inner writer.
+    #[inline]
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+
+    /// Writes a call trace arena as a complete HTML document to the writer.
+    pub fn write_arena(&mut self, arena: &CallTraceArena) -> io::Result<()> {
+        writeln!(self.writer, "<!DOCTYPE html>")?;
+        writeln!(self.writer, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(self.writer, "<title>Call trace</title>\n<style>{STYLE}</style>\n</head>")?;
+        writeln!(self.writer, "<body>")?;
+        writeln!(
+            self.writer,
+            "<input id=\"search\" type=\"search\" \
+             placeholder=\"Search by address, label, function or selector\">"
+        )?;
+        self.write_node(arena.nodes(), 0)?;
+        writeln!(self.writer, "<script>{SCRIPT}</script>\n</body>\n</html>")?;
+        self.writer.flush()
+    }
+
+    /// Writes a single node and its children to the writer.
+    fn write_node(&mut self, nodes: &[CallTraceNode], idx: usize) -> io::Result<()> {
+        let node = &nodes[idx];
+        let trace = &node.trace;
+
+        let mut search = trace.address.to_string().to_lowercase();
+        if let Some(label) = &trace.decoded.label {
+            search.push(' ');
+            search.push_str(&label.to_lowercase());
+        }
+        if let Some(selector) = node.selector() {
+            search.push(' ');
+            search.push_str(&selector.to_string());
+        }
+        if let Some(call_data) = &trace.decoded.call_data {
+            search.push(' ');
+            search.push_str(&call_data.signature.to_lowercase());
+        }
+
+        writeln!(self.writer, "<details class=\"call\" open data-search=\"{}\">", escape(&search))?;
+        write!(self.writer, "<summary>")?;
+        self.write_trace_header(trace)?;
+        writeln!(self.writer, "</summary>\n<div class=\"items\">")?;
+
+        self.write_items(nodes, idx, 0, node.ordering.len())?;
+        self.write_steps(node)?;
+        if self.config.get_write_storage_changes() {
+            self.write_storage_changes(node)?;
+        }
+        self.write_trace_footer(trace)?;
+
+        writeln!(self.writer, "</div>\n</details>")
+    }
+
+    /// Writes the items of a single node in the given range to the writer.
+    fn write_items(
+        &mut self,
+        nodes: &[CallTraceNode],
+        node_idx: usize,
+        start: usize,
+        end: usize,
+    ) -> io::Result<()> {
+        let node = &nodes[node_idx];
+        let mut item_idx = start;
+        while item_idx < end {
+            item_idx = match &node.ordering[item_idx] {
+                TraceMemberOrder::Log(index) => {
+                    self.write_log(&node.logs[*index])?;
+                    item_idx + 1
+                }
+                TraceMemberOrder::Call(index) => {
+                    self.write_node(nodes, node.children[*index])?;
+                    item_idx + 1
+                }
+                TraceMemberOrder::Step(index) => {
+                    self.write_step(nodes, node_idx, item_idx, *index)?
+                }
+            };
+        }
+        Ok(())
+    }
+
+    /// Writes a decoded step of a single node to the writer. Returns the index of the next item to
+    /// be written.
+    fn write_step(
+        &mut self,
+        nodes: &[CallTraceNode],
+        node_idx: usize,
+        item_idx: usize,
+        step_idx: usize,
+    ) -> io::Result<usize> {
+        let node = &nodes[node_idx];
+        let step = &node.trace.steps[step_idx];
+        let Some(decoded) = &step.decoded else {
+            // raw steps are written separately, see `write_steps`
+            return Ok(item_idx + 1);
+        };
+
+        match decoded {
+            DecodedTraceStep::InternalCall(call, end_idx) => {
+                let gas_used = node.trace.steps[*end_idx].gas_used.saturating_sub(step.gas_used);
+                writeln!(
+                    self.writer,
+                    "<details class=\"internal\" open><summary><span class=\"gas\">[{gas_used}]\
+                     </span> {}{}</summary>\n<div class=\"items\">",
+                    escape(&call.func_name),
+                    escape(
+                        &call
+                            .args
+                            .as_ref()
+                            .map(|v| format!("({})", v.join(", ")))
+                            .unwrap_or_default()
+                    )
+                )?;
+
+                let end_item_idx = node
+                    .ordering
+                    .iter()
+                    .skip(item_idx + 1)
+                    .position(|item| matches!(item, TraceMemberOrder::Step(idx) if idx == end_idx))
+                    .map_or(node.ordering.len(), |pos| item_idx + 1 + pos);
+                self.write_items(nodes, node_idx, item_idx + 1, end_item_idx)?;
+
+                let outputs = call.return_data.as_ref().map(|v| v.join(", ")).unwrap_or_default();
+                writeln!(
+                    self.writer,
+                    "<div class=\"line\">← {}</div>\n</div>\n</details>",
+                    escape(&outputs)
+                )?;
+
+                Ok(end_item_idx + 1)
+            }
+            DecodedTraceStep::Line(line) => {
+                writeln!(self.writer, "<div class=\"line\">{}</div>", escape(line))?;
+                Ok(item_idx + 1)
+            }
+        }
+    }
+
+    /// Writes the header of a call trace.
+    fn write_trace_header(&mut self, trace: &CallTrace) -> io::Result<()> {
+        write!(self.writer, "<span class=\"gas\">[{}]</span> ", trace.gas_used)?;
+
+        let address = trace.address.to_checksum(None);
+        if trace.kind.is_any_create() {
+            write!(
+                self.writer,
+                "<span class=\"kind\">→ new</span> {}@{address}",
+                escape(trace.decoded.label.as_deref().unwrap_or("<unknown>"))
+            )?;
+            if self.config.get_write_bytecodes() {
+                write!(self.writer, "({})", trace.data)?;
+            }
+        } else {
+            let (func_name, inputs) = function_and_inputs(trace);
+            let class = self.trace_class(trace);
+            write!(
+                self.writer,
+                "<span class=\"{class}\">{}</span>::<span class=\"{class}\">{}</span>",
+                escape(trace.decoded.label.as_deref().unwrap_or(&address)),
+                escape(&func_name),
+            )?;
+            if !trace.value.is_zero() {
+                write!(self.writer, "{{value: {}}}", trace.value)?;
+            }
+            write!(self.writer, "({})", escape(&inputs))?;
+
+            let action = match trace.kind {
+                CallKind::Call => None,
+                CallKind::StaticCall => Some("staticcall"),
+                CallKind::CallCode => Some("callcode"),
+                CallKind::DelegateCall => Some("delegatecall"),
+                CallKind::AuthCall => Some("authcall"),
+                CallKind::Create | CallKind::Create2 => unreachable!(),
+            };
+            if let Some(action) = action {
+                write!(self.writer, " <span class=\"kind\">[{action}]</span>")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the footer of a call trace.
+    fn write_trace_footer(&mut self, trace: &CallTrace) -> io::Result<()> {
+        write!(
+            self.writer,
+            "<div class=\"line\"><span class=\"{class}\">← [{status:?}]</span>",
+            class = self.trace_class(trace),
+            status = trace.status.unwrap_or(InstructionResult::Stop),
+        )?;
+        if let Some(output) = fmt_output(trace, self.config.get_write_bytecodes()) {
+            write!(self.writer, " {}", escape(&output))?;
+        }
+        writeln!(self.writer, "</div>")
+    }
+
+    fn write_log(&mut self, log: &CallLog) -> io::Result<()> {
+        write!(self.writer, "<div class=\"line\">")?;
+        if let Some(name) = &log.decoded.name {
+            write!(self.writer, "emit {}(<span class=\"log\">", escape(name))?;
+            if let Some(params) = &log.decoded.params {
+                for (i, (param_name, value)) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, ", ")?;
+                    }
+                    write!(self.writer, "{}: {}", escape(param_name), escape(value))?;
+                }
+            }
+            write!(self.writer, "</span>)")?;
+        } else {
+            write!(self.writer, "emit")?;
+            for (i, topic) in log.raw_log.topics().iter().enumerate() {
+                write!(self.writer, "\n  topic {i}: <span class=\"log\">{topic}</span>")?;
+            }
+            write!(self.writer, "\n   data: <span class=\"log\">{}</span>", log.raw_log.data)?;
+        }
+        writeln!(self.writer, "</div>")
+    }
+
+    /// Writes all recorded steps of a single node as a collapsed table.
+    fn write_steps(&mut self, node: &CallTraceNode) -> io::Result<()> {
+        let steps = &node.trace.steps;
+        if steps.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            self.writer,
+            "<details class=\"steps\"><summary class=\"dim\">{} steps</summary>\n<table>",
+            steps.len()
+        )?;
+        writeln!(
+            self.writer,
+            "<tr class=\"dim\"><td>pc</td><td>op</td><td>gas</td><td>cost</td></tr>"
+        )?;
+        for step in steps {
+            writeln!(
+                self.writer,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                step.pc,
+                step.op.as_str(),
+                step.gas_remaining,
+                step.gas_cost
+            )?;
+        }
+        writeln!(self.writer, "</table>\n</details>")
+    }
+
+    fn write_storage_changes(&mut self, node: &CallTraceNode) -> io::Result<()> {
+        for (title, transient) in [("storage changes", false), ("transient storage changes", true)]
+        {
+            let changes = storage_changes(node, transient);
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(self.writer, "<details class=\"storage\" open><summary>{title}</summary>")?;
+            for (key, value_before, value_after) in changes {
+                writeln!(
+                    self.writer,
+                    "<div class=\"line\">  @ {}: {} → {}</div>",
+                    num_or_hex(key),
+                    num_or_hex(value_before),
+                    num_or_hex(value_after),
+                )?;
+            }
+            writeln!(self.writer, "</details>")?;
+        }
+        Ok(())
+    }
+
+    /// Returns the CSS class of a call, see [TraceWriter](crate::tracing::TraceWriter).
+    fn trace_class(&self, trace: &CallTrace) -> &'static str {
+        if self.config.get_color_cheatcodes() && trace.address == CHEATCODE_ADDRESS {
+            "cheatcode"
+        } else if trace.success {
+            "success"
+        } else {
+            "failure"
+        }
+    }
+}
+
+/// Escapes the HTML special characters of the given text.
+fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 16);
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_html() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+}
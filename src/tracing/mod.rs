@@ -3,10 +3,10 @@ use crate::{
     tracing::{
         arena::PushTraceKind,
         types::{
-            CallKind, CallTraceNode, RecordedMemory, StorageChange, StorageChangeReason,
-            TraceMemberOrder,
+            CallKind, CallStateChange, CallStateChangeKind, CallTraceNode, MemoryChange,
+            RecordedMemory, StorageChange, StorageChangeReason, TraceMemberOrder,
         },
-        utils::gas_used,
+        utils::{gas_used, memory_write_range},
     },
 };
 use alloc::vec::Vec;
@@ -17,7 +17,9 @@ use revm::{
     context_interface::ContextTr,
     inspector::JournalExt,
     interpreter::{
-        interpreter_types::{Immediates, InputsTr, Jumps, LoopControl, ReturnData, RuntimeFlag},
+        interpreter_types::{
+            Immediates, InputsTr, Jumps, LoopControl, MemoryTr, ReturnData, RuntimeFlag,
+        },
         CallInput, CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, Interpreter,
         InterpreterResult,
     },
@@ -25,38 +27,85 @@ use revm::{
     Inspector, JournalEntry,
 };
 
+#[cfg(feature = "abi-decoder")]
+mod abi;
+#[cfg(feature = "abi-decoder")]
+pub use abi::AbiDecoder;
+
 mod arena;
 pub use arena::CallTraceArena;
 
+mod binary;
+pub use binary::{BinaryTraceError, CallTraceArenaReader, BINARY_FORMAT_VERSION};
+
 mod builder;
+#[cfg(feature = "std")]
+pub use builder::eip3155::{self, Eip3155Summary, Eip3155Writer};
 pub use builder::{
     geth::{self, GethTraceBuilder},
     parity::{self, ParityTraceBuilder},
 };
 
 mod config;
-pub use config::{OpcodeFilter, StackSnapshotType, TracingInspectorConfig};
+pub use config::{OpcodeFilter, StackSnapshotType, StepScope, TracingInspectorConfig};
+
+mod diff;
+pub use diff::{
+    CallChanges, CallDiff, CallTraceDiff, CallTraceDiffConfig, LogChange, MatchedCall,
+    StepDivergence,
+};
+
+mod flamegraph;
+pub use flamegraph::{FlamegraphConfig, FlamegraphWriter};
 
 mod fourbyte;
 pub use fourbyte::FourByteInspector;
 
+mod graph;
+pub use graph::{CallGraphConfig, CallGraphWriter};
+
+#[cfg(feature = "std")]
+mod html;
+#[cfg(feature = "std")]
+pub use html::HtmlTraceWriter;
+
 mod opcount;
 pub use opcount::OpcodeCountInspector;
 
+mod query;
+pub use query::{CallTraceFilter, CallTraceMatch};
+
+mod revert;
+pub use revert::RevertReason;
+
+mod sink;
+pub use sink::{NoopStepSink, StepSink};
+
+mod sourcemap;
+pub use sourcemap::{
+    parse_source_map, ContractSourceMap, FunctionRange, Jump, SourceElement, SourceMapDecoder,
+    SourceMapError,
+};
+
 pub mod types;
 use types::{CallLog, CallTrace, CallTraceStep};
 
 mod utils;
 
-#[cfg(feature = "std")]
+mod walker;
+pub use walker::CallTraceVisitor;
+
 mod writer;
-#[cfg(feature = "std")]
-pub use writer::{TraceWriter, TraceWriterConfig};
+pub use writer::{
+    DefaultTraceDecorator, FmtWriter, TraceDecorator, TraceOutput, TraceWriter, TraceWriterConfig,
+};
 
 #[cfg(feature = "js-tracer")]
 pub mod js;
 
 mod mux;
+#[cfg(feature = "js-tracer")]
+pub use mux::MuxJsTracers;
 pub use mux::{Error as MuxError, MuxInspector};
 
 /// An inspector that collects call traces.
@@ -67,8 +116,11 @@ pub use mux::{Error as MuxError, MuxInspector};
 /// The [TracingInspector] keeps track of everything by:
 ///   1. start tracking steps/calls on [Inspector::step] and [Inspector::call]
 ///   2. complete steps/calls on [Inspector::step_end] and [Inspector::call_end]
+///
+/// Recorded steps are stored in the arena by default. If a [StepSink] is installed via
+/// [TracingInspector::with_step_sink], steps are handed to the sink instead, see [StepSink].
 #[derive(Clone, Debug, Default)]
-pub struct TracingInspector {
+pub struct TracingInspector<S = NoopStepSink> {
     /// Configures what and how the inspector records traces.
     config: TracingInspectorConfig,
     /// Records all call traces
@@ -77,6 +129,30 @@ pub struct TracingInspector {
     trace_stack: Vec<usize>,
     /// Tracks active steps
     step_stack: Vec<StackStep>,
+    /// The sink recorded steps are streamed to, if any.
+    ///
+    /// If this is `None`, steps are stored in the arena.
+    step_sink: Option<S>,
+    /// Steps that are in progress and will be handed to the [StepSink] once completed.
+    pending_steps: Vec<CallTraceStep>,
+    /// Number of steps streamed to the [StepSink] per trace node, indexed by the node's idx.
+    streamed_steps: Vec<usize>,
+    /// The trace and step index of the last started step, `None` if that step was not recorded.
+    last_step: Option<(usize, usize)>,
+    /// The index of the recorded step in the parent trace that started each trace node, indexed
+    /// by the node's idx.
+    call_steps: Vec<Option<usize>>,
+    /// The scope that limits step recording to the frames of specific contracts, if any.
+    steps_scope: Option<StepScope>,
+    /// Whether the steps of each trace node are in the [Self::steps_scope], indexed by the node's
+    /// idx.
+    steps_in_scope: Vec<bool>,
+    /// Total number of recorded steps, used to enforce
+    /// [TracingInspectorConfig::record_steps_limit].
+    recorded_steps: usize,
+    /// The number of journal entries that were already attributed to a call, used to record
+    /// [CallTraceNode::state_changes].
+    state_changes_journal_len: usize,
     /// Tracks the return value of the last call
     last_call_return_data: Option<Bytes>,
     /// Tracks the journal len in the step, used in step_end to check if the journal has changed
@@ -94,6 +170,94 @@ impl TracingInspector {
     pub fn new(config: TracingInspectorConfig) -> Self {
         Self { config, ..Default::default() }
     }
+}
+
+impl<S> TracingInspector<S> {
+    /// Installs the given [StepSink], recorded steps will be handed to the sink instead of being
+    /// stored in the arena.
+    pub fn with_step_sink<T: StepSink>(self, sink: T) -> TracingInspector<T> {
+        let Self {
+            config,
+            traces,
+            trace_stack,
+            step_stack,
+            last_call_return_data,
+            last_journal_len,
+            spec_id,
+            pending_steps,
+            streamed_steps,
+            last_step,
+            call_steps,
+            steps_scope,
+            steps_in_scope,
+            recorded_steps,
+            state_changes_journal_len,
+            step_sink: _,
+        } = self;
+        TracingInspector {
+            config,
+            traces,
+            trace_stack,
+            step_stack,
+            step_sink: Some(sink),
+            pending_steps,
+            streamed_steps,
+            last_step,
+            call_steps,
+            steps_scope,
+            steps_in_scope,
+            recorded_steps,
+            state_changes_journal_len,
+            last_call_return_data,
+            last_journal_len,
+            spec_id,
+        }
+    }
+
+    /// Returns a reference to the installed [StepSink], if any.
+    pub const fn step_sink(&self) -> Option<&S> {
+        self.step_sink.as_ref()
+    }
+
+    /// Returns a mutable reference to the installed [StepSink], if any.
+    pub fn step_sink_mut(&mut self) -> Option<&mut S> {
+        self.step_sink.as_mut()
+    }
+
+    /// Limits step recording to the frames in the given [StepScope].
+    ///
+    /// The steps of frames outside of the scope are not recorded. This only has an effect if
+    /// [TracingInspectorConfig::record_steps] is enabled.
+    pub fn with_steps_scope(mut self, scope: StepScope) -> Self {
+        self.steps_scope = Some(scope);
+        self
+    }
+
+    /// Sets the [StepScope] that limits step recording, see [Self::with_steps_scope].
+    pub fn set_steps_scope(&mut self, scope: Option<StepScope>) {
+        self.steps_scope = scope;
+    }
+
+    /// Returns the configured [StepScope], if any.
+    pub const fn steps_scope(&self) -> Option<&StepScope> {
+        self.steps_scope.as_ref()
+    }
+
+    /// Removes the installed [StepSink] and returns it.
+    ///
+    /// Subsequently recorded steps will be stored in the arena.
+    ///
+    /// This must only be called between transactions, e.g. right before [Self::fuse]. The step
+    /// indices of a node that already streamed steps would otherwise be reused by the steps that
+    /// are stored in the arena.
+    ///
+    /// # Panics
+    ///
+    /// The inspector panics on the next [Inspector::step_end] if this is called while a step is
+    /// being executed, e.g. from the [Inspector::step] hook of another inspector.
+    pub fn take_step_sink(&mut self) -> Option<S> {
+        self.step_sink.take()
+    }
 
     /// Resets the inspector to its initial state of [Self::new].
     /// This makes the inspector ready to be used again.
@@ -108,12 +272,28 @@ impl TracingInspector {
             last_call_return_data,
             last_journal_len,
             spec_id,
+            pending_steps,
+            streamed_steps,
+            last_step,
+            call_steps,
+            steps_in_scope,
+            recorded_steps,
+            state_changes_journal_len,
             // kept
             config: _,
+            step_sink: _,
+            steps_scope: _,
         } = self;
         traces.clear();
         trace_stack.clear();
         step_stack.clear();
+        pending_steps.clear();
+        streamed_steps.clear();
+        last_step.take();
+        call_steps.clear();
+        steps_in_scope.clear();
+        *recorded_steps = 0;
+        *state_changes_journal_len = 0;
         last_call_return_data.take();
         spec_id.take();
         *last_journal_len = 0;
@@ -321,7 +501,12 @@ impl TracingInspector {
             PushTraceKind::PushAndAttachToParent
         };
 
-        self.trace_stack.push(self.traces.push_trace(
+        // the step that started this call, if it was recorded in the parent trace
+        let parent = self.trace_stack.last().copied();
+        let call_step =
+            self.last_step.take().filter(|(idx, _)| Some(*idx) == parent).map(|(_, step)| step);
+
+        let trace_idx = self.traces.push_trace(
             0,
             push_kind,
             CallTrace {
@@ -336,7 +521,22 @@ impl TracingInspector {
                 gas_limit,
                 ..Default::default()
             },
-        ));
+        );
+        self.trace_stack.push(trace_idx);
+
+        if self.call_steps.len() <= trace_idx {
+            self.call_steps.resize(trace_idx + 1, None);
+        }
+        self.call_steps[trace_idx] = call_step;
+
+        if self.config.record_accessed_state {
+            let precompiles = context.journal_ref().precompile_addresses();
+            for account in [caller, address] {
+                if !precompiles.contains(&account) {
+                    self.traces.arena[trace_idx].accessed_state.entry(account).or_default();
+                }
+            }
+        }
     }
 
     /// Fills the current trace with the outcome of a call.
@@ -368,8 +568,221 @@ impl TracingInspector {
             // A new contract was created via CREATE
             trace.address = address;
         }
+
+        if !trace.success {
+            // all nodes recorded after this one are part of this call, so their changes are
+            // reverted as well
+            for node in &mut self.traces.arena[trace_idx..] {
+                for change in &mut node.state_changes {
+                    change.reverted = true;
+                }
+            }
+        }
+    }
+
+    /// Records the return data that is copied into the caller's memory as the
+    /// [CallTraceStep::memory_change] of the `CALL` step that started the current call.
+    ///
+    /// This must be called before the call trace is popped in [Self::fill_trace_on_call_end].
+    fn record_call_output_memory(&mut self, outcome: &CallOutcome) {
+        // the step was already handed to the sink
+        if self.step_sink.is_some() {
+            return;
+        }
+        let trace_idx = self.last_trace_idx();
+        let Some(parent) = self.traces.arena[trace_idx].parent else { return };
+        // the step that started the call may not have been recorded, e.g. if it was filtered out
+        let Some(step_idx) = self.call_steps.get(trace_idx).copied().flatten() else { return };
+        let step = &mut self.traces.arena[parent].trace.steps[step_idx];
+
+        // only the part of the output that fits into the reserved memory range is written
+        let output = outcome.output();
+        let size = output.len().min(outcome.memory_offset.len());
+        if size != 0 {
+            step.memory_change = Some(MemoryChange {
+                offset: outcome.memory_offset.start,
+                data: output.slice(..size),
+            });
+        }
+    }
+
+    /// Attributes all journal entries that were added since the last invocation to the given
+    /// trace.
+    ///
+    /// Entries of reverted calls are removed from the journal before the call ends, changes that
+    /// were already recorded for these calls are marked as reverted in
+    /// [Self::fill_trace_on_call_end].
+    fn record_state_changes<CTX: ContextTr<Journal: JournalExt>>(
+        &mut self,
+        context: &CTX,
+        trace_idx: usize,
+    ) {
+        let journal = context.journal_ref().journal();
+        if journal.len() <= self.state_changes_journal_len {
+            // nothing new, or the journal was reverted
+            self.state_changes_journal_len = journal.len();
+            return;
+        }
+
+        let state = context.journal_ref().evm_state();
+        let node = &mut self.traces.arena[trace_idx];
+        for entry in &journal[self.state_changes_journal_len..] {
+            let (address, kind) = match *entry {
+                JournalEntry::BalanceTransfer { balance, from, to } => {
+                    (from, CallStateChangeKind::Transfer { to, value: balance })
+                }
+                JournalEntry::BalanceChange { old_balance, address } => {
+                    let balance = state.get(&address).map(|acc| acc.info.balance);
+                    (
+                        address,
+                        CallStateChangeKind::BalanceChange {
+                            had_balance: old_balance,
+                            balance: balance.unwrap_or_default(),
+                        },
+                    )
+                }
+                JournalEntry::NonceChange { address } => {
+                    let nonce = state.get(&address).map(|acc| acc.info.nonce);
+                    (address, CallStateChangeKind::NonceChange { nonce: nonce.unwrap_or_default() })
+                }
+                JournalEntry::CodeChange { address } => {
+                    let code_hash = state.get(&address).map(|acc| acc.info.code_hash);
+                    (
+                        address,
+                        CallStateChangeKind::CodeChange {
+                            code_hash: code_hash.unwrap_or_default(),
+                        },
+                    )
+                }
+                JournalEntry::AccountCreated { address, .. } => {
+                    (address, CallStateChangeKind::Created)
+                }
+                JournalEntry::StorageChanged { key, had_value, address } => {
+                    let value = state
+                        .get(&address)
+                        .and_then(|acc| acc.storage.get(&key))
+                        .map(|slot| slot.present_value);
+                    (
+                        address,
+                        CallStateChangeKind::Storage {
+                            key,
+                            had_value,
+                            value: value.unwrap_or_default(),
+                        },
+                    )
+                }
+                JournalEntry::AccountDestroyed { had_balance, address, target, .. } => {
+                    (address, CallStateChangeKind::SelfDestruct { target, balance: had_balance })
+                }
+                // warm loads, touches and transient storage are not persisted
+                _ => continue,
+            };
+            node.state_changes.push(CallStateChange { address, kind, reverted: false });
+        }
+        self.state_changes_journal_len = journal.len();
+    }
+
+    /// Attributes pending journal entries to the active call before a new call is entered.
+    ///
+    /// For the root call, all entries made before execution, e.g. by the transaction validation,
+    /// are skipped.
+    fn record_state_changes_on_call<CTX: ContextTr<Journal: JournalExt>>(&mut self, context: &CTX) {
+        if let Some(trace_idx) = self.trace_stack.last().copied() {
+            self.record_state_changes(context, trace_idx);
+        } else {
+            self.state_changes_journal_len = context.journal_ref().journal().len();
+        }
+    }
+
+    /// Records the account or storage slot the current opcode is about to access in the active
+    /// trace, see [CallTraceNode::accessed_state].
+    ///
+    /// See also <https://github.com/ethereum/go-ethereum/blob/master/eth/tracers/native/prestate.go>
+    fn record_accessed_state<CTX: ContextTr>(&mut self, interp: &Interpreter, context: &CTX) {
+        let stack = &interp.stack;
+        let (address, slot) = match interp.bytecode.opcode() {
+            opcode::SLOAD | opcode::SSTORE => {
+                let Ok(slot) = stack.peek(0) else { return };
+                (interp.input.target_address(), Some(B256::from(slot)))
+            }
+            opcode::BALANCE
+            | opcode::EXTCODESIZE
+            | opcode::EXTCODECOPY
+            | opcode::EXTCODEHASH
+            | opcode::SELFDESTRUCT => {
+                let Ok(address) = stack.peek(0) else { return };
+                (Address::from_word(address.into()), None)
+            }
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL => {
+                let Ok(address) = stack.peek(1) else { return };
+                (Address::from_word(address.into()), None)
+            }
+            _ => return,
+        };
+        // like geth, precompiles are never part of the prestate
+        if slot.is_none() && context.journal_ref().precompile_addresses().contains(&address) {
+            return;
+        }
+
+        let slots = self.last_trace().accessed_state.entry(address).or_default();
+        if let Some(slot) = slot {
+            slots.insert(slot);
+        }
+    }
+
+    /// Returns true if the configured [TracingInspectorConfig::record_steps_limit] is reached.
+    #[inline]
+    fn is_steps_limit_reached(&self) -> bool {
+        self.config.record_steps_limit.is_some_and(|limit| self.recorded_steps >= limit)
+    }
+
+    /// Determines whether the frame of the current trace is in the configured
+    /// [StepScope], see [Self::with_steps_scope].
+    ///
+    /// Invoked on [Inspector::initialize_interp].
+    fn init_step_scope(&mut self, interp: &mut Interpreter) {
+        let Some(scope) = &self.steps_scope else { return };
+        let trace_idx = self.last_trace_idx();
+        let code_hash = scope
+            .has_code_hashes()
+            .then(|| interp.bytecode.hash().unwrap_or_else(|| interp.bytecode.regenerate_hash()));
+        let addresses =
+            [Some(interp.input.target_address()), interp.input.bytecode_address().copied()];
+        let in_scope = scope.contains(
+            self.traces.arena[trace_idx].trace.depth,
+            addresses.into_iter().flatten(),
+            code_hash,
+        );
+
+        if self.steps_in_scope.len() <= trace_idx {
+            self.steps_in_scope.resize(trace_idx + 1, false);
+        }
+        self.steps_in_scope[trace_idx] = in_scope;
     }
 
+    /// Returns true if the steps of the current frame should be recorded, see
+    /// [Self::with_steps_scope].
+    #[inline]
+    fn is_step_in_scope(&self) -> bool {
+        self.steps_scope.is_none()
+            || self.steps_in_scope.get(self.last_trace_idx()).copied().unwrap_or_default()
+    }
+
+    /// Returns the index the next step of the given trace will have and advances the counter of
+    /// streamed steps if the step is recorded.
+    fn next_streamed_step_idx(&mut self, trace_idx: usize, record: bool) -> usize {
+        if self.streamed_steps.len() <= trace_idx {
+            self.streamed_steps.resize(trace_idx + 1, 0);
+        }
+        let step_idx = self.streamed_steps[trace_idx];
+        if record {
+            self.streamed_steps[trace_idx] += 1;
+        }
+        step_idx
+    }
+}
+
+impl<S: StepSink> TracingInspector<S> {
     /// Starts tracking a step
     ///
     /// Invoked on [Inspector::step]
@@ -385,20 +798,32 @@ impl TracingInspector {
         context: &mut CTX,
     ) {
         let trace_idx = self.last_trace_idx();
-        let trace = &mut self.traces.arena[trace_idx];
-
-        let step_idx = trace.trace.steps.len();
         // We always want an OpCode, even it is unknown because it could be an additional opcode
         // that not a known constant.
         let op = unsafe { OpCode::new_unchecked(interp.bytecode.opcode()) };
 
-        let record = self.config.should_record_opcode(op);
+        let record = self.config.should_record_opcode(op) && !self.is_steps_limit_reached();
+
+        let streaming = self.step_sink.is_some();
+        let step_idx = if streaming {
+            self.next_streamed_step_idx(trace_idx, record)
+        } else {
+            self.traces.arena[trace_idx].trace.steps.len()
+        };
 
-        self.step_stack.push(StackStep { trace_idx, step_idx, record });
+        let memory_write = (record && self.config.record_memory_deltas)
+            .then(|| memory_write_range(op.get(), &interp.stack))
+            .flatten();
+
+        self.step_stack.push(StackStep { trace_idx, step_idx, record, memory_write });
+        self.last_step = record.then_some((trace_idx, step_idx));
 
         if !record {
             return;
         }
+        self.recorded_steps += 1;
+
+        let trace = &mut self.traces.arena[trace_idx];
 
         // Reuse the memory from the previous step if:
         // - there is not opcode filter -- in this case we cannot rely on the order of steps
@@ -446,7 +871,24 @@ impl TracingInspector {
 
         self.last_journal_len = context.journal_ref().journal().len();
 
-        trace.trace.steps.push(CallTraceStep {
+        // The key and new value of transient storage writes are only available on the stack, the
+        // previous value is filled in from the journal once the step is executed.
+        let storage_change = if self.config.record_state_diff && op.get() == opcode::TSTORE {
+            match (interp.stack.peek(0), interp.stack.peek(1)) {
+                (Ok(key), Ok(value)) => Some(StorageChange {
+                    key,
+                    value,
+                    // the journal is only updated if the value changes
+                    had_value: Some(value),
+                    reason: StorageChangeReason::TSTORE,
+                }),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let step = CallTraceStep {
             depth: context.journal().depth() as u64,
             pc: interp.bytecode.pc(),
             op,
@@ -454,6 +896,7 @@ impl TracingInspector {
             stack,
             push_stack: None,
             memory,
+            memory_size: interp.memory.size(),
             returndata,
             gas_remaining: interp.gas.remaining(),
             gas_refund_counter: interp.gas.refunded() as u64,
@@ -463,9 +906,16 @@ impl TracingInspector {
 
             // fields will be populated end of call
             gas_cost: 0,
-            storage_change: None,
+            storage_change,
+            memory_change: None,
             status: None,
-        });
+        };
+
+        if streaming {
+            self.pending_steps.push(step);
+        } else {
+            trace.trace.steps.push(step);
+        }
 
         trace.ordering.push(TraceMemberOrder::Step(step_idx));
     }
@@ -479,14 +929,18 @@ impl TracingInspector {
         interp: &mut Interpreter,
         context: &mut CTX,
     ) {
-        let StackStep { trace_idx, step_idx, record } =
+        let StackStep { trace_idx, step_idx, record, memory_write } =
             self.step_stack.pop().expect("can't fill step without starting a step first");
 
         if !record {
             return;
         }
 
-        let step = &mut self.traces.arena[trace_idx].trace.steps[step_idx];
+        let step = if self.step_sink.is_some() {
+            self.pending_steps.last_mut().expect("can't fill step without starting a step first")
+        } else {
+            &mut self.traces.arena[trace_idx].trace.steps[step_idx]
+        };
 
         if self.config.record_stack_snapshots.is_all()
             || self.config.record_stack_snapshots.is_pushes()
@@ -521,6 +975,14 @@ impl TracingInspector {
                         StorageChange { key: *key, value, had_value: Some(*had_value), reason };
                     Some(change)
                 }
+                (
+                    opcode::TSTORE,
+                    Some(JournalEntry::TransientStorageChange { key, had_value, .. }),
+                ) => step
+                    .storage_change
+                    .filter(|change| change.key == *key)
+                    .map(|change| StorageChange { had_value: Some(*had_value), ..change }),
+                (opcode::TSTORE, _) => step.storage_change,
                 _ => None,
             };
         }
@@ -531,26 +993,57 @@ impl TracingInspector {
         step.gas_cost = step.gas_remaining.saturating_sub(interp.gas.remaining());
 
         // set the status
-        step.status = interp.bytecode.action().as_ref().and_then(|i| i.instruction_result())
+        step.status = interp.bytecode.action().as_ref().and_then(|i| i.instruction_result());
+
+        if step.status.is_some_and(|status| !status.is_ok()) {
+            // the step failed, e.g. a TSTORE in a static context, nothing was written
+            step.storage_change = None;
+        } else if let Some((offset, size)) = memory_write {
+            let memory = interp.memory.borrow();
+            let memory = memory.context_memory();
+            step.memory_change = memory
+                .get(offset..offset + size)
+                .map(|data| MemoryChange { offset, data: Bytes::copy_from_slice(data) });
+        }
+
+        if let Some(sink) = &mut self.step_sink {
+            let step = self.pending_steps.pop().expect("can't fill step without starting a step");
+            sink.record_step(&self.traces.arena[trace_idx], step_idx, step);
+        }
     }
 }
 
-impl<CTX> Inspector<CTX> for TracingInspector
+impl<CTX, S> Inspector<CTX> for TracingInspector<S>
 where
     CTX: ContextTr<Journal: JournalExt>,
+    S: StepSink,
 {
     #[inline]
-    fn step(&mut self, interp: &mut Interpreter, context: &mut CTX) {
+    fn initialize_interp(&mut self, interp: &mut Interpreter, _context: &mut CTX) {
         if self.config.record_steps {
+            self.last_trace().trace.code = Some(interp.bytecode.original_bytes());
+            self.init_step_scope(interp);
+        }
+    }
+
+    #[inline]
+    fn step(&mut self, interp: &mut Interpreter, context: &mut CTX) {
+        if self.config.record_steps && self.is_step_in_scope() {
             self.start_step(interp, context);
         }
+        if self.config.record_accessed_state {
+            self.record_accessed_state(interp, context);
+        }
     }
 
     #[inline]
     fn step_end(&mut self, interp: &mut Interpreter, context: &mut CTX) {
-        if self.config.record_steps {
+        if self.config.record_steps && self.is_step_in_scope() {
             self.fill_step_on_step_end(interp, context);
         }
+        if self.config.record_call_state_changes {
+            self.record_state_changes(context, self.last_trace_idx());
+        }
     }
 
     fn log(&mut self, _interp: &mut Interpreter, _context: &mut CTX, log: Log) {
@@ -587,6 +1080,10 @@ where
             .exclude_precompile_calls
             .then(|| self.is_precompile_call(context, &to, &value));
 
+        if self.config.record_call_state_changes {
+            self.record_state_changes_on_call(context);
+        }
+
         let input = inputs.input_data(context);
         self.start_trace_on_call(
             context,
@@ -602,11 +1099,21 @@ where
         None
     }
 
-    fn call_end(&mut self, _: &mut CTX, _inputs: &CallInputs, outcome: &mut CallOutcome) {
+    fn call_end(&mut self, context: &mut CTX, _inputs: &CallInputs, outcome: &mut CallOutcome) {
+        if self.config.record_call_state_changes {
+            self.record_state_changes(context, self.last_trace_idx());
+        }
+        if self.config.record_steps && self.config.record_memory_deltas {
+            self.record_call_output_memory(outcome);
+        }
         self.fill_trace_on_call_end(&outcome.result, None);
     }
 
     fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        if self.config.record_call_state_changes {
+            self.record_state_changes_on_call(context);
+        }
+
         let nonce = context.journal_mut().load_account(inputs.caller).ok()?.info.nonce;
         self.start_trace_on_call(
             context,
@@ -623,10 +1130,13 @@ where
 
     fn create_end(
         &mut self,
-        _context: &mut CTX,
+        context: &mut CTX,
         _inputs: &CreateInputs,
         outcome: &mut CreateOutcome,
     ) {
+        if self.config.record_call_state_changes {
+            self.record_state_changes(context, self.last_trace_idx());
+        }
         self.fill_trace_on_call_end(&outcome.result, outcome.address);
     }
 
@@ -653,6 +1163,9 @@ struct StackStep {
     /// Please note that if `record` is `false`, this will still contain a value, but the step will
     /// not appear in the steps list.
     step_idx: usize,
+    /// The `(offset, size)` of the memory range this step writes to, if memory deltas are
+    /// recorded.
+    memory_write: Option<(usize, usize)>,
 }
 
 /// Contains some contextual infos for a transaction execution that is made available to the JS
@@ -1,32 +1,48 @@
-use crate::tracing::{FourByteInspector, TracingInspector, TracingInspectorConfig};
-use alloc::vec::Vec;
+#[cfg(feature = "js-tracer")]
+use crate::tracing::js::JsInspector;
+use crate::tracing::{
+    FourByteInspector, StackSnapshotType, TracingInspector, TracingInspectorConfig,
+};
+#[cfg(feature = "js-tracer")]
+use alloc::string::ToString;
+use alloc::{string::String, vec::Vec};
 use alloy_primitives::{map::HashMap, Address, Log, U256};
 use alloy_rpc_types_eth::TransactionInfo;
 use alloy_rpc_types_trace::geth::{
+    erc7562::Erc7562Config,
     mux::{MuxConfig, MuxFrame},
     CallConfig, FlatCallConfig, FourByteFrame, GethDebugBuiltInTracerType, NoopFrame,
     PreStateConfig,
 };
+#[cfg(feature = "js-tracer")]
+use alloy_rpc_types_trace::geth::{GethDebugTracerConfig, GethTrace};
+#[cfg(feature = "js-tracer")]
+use revm::context_interface::{Block, Transaction};
 use revm::{
     context_interface::{
         result::{HaltReasonTr, ResultAndState},
         ContextTr,
     },
-    inspector::JournalExt,
+    inspector::{JournalExt, NoOpInspector},
     interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter},
     DatabaseRef, Inspector,
 };
 use thiserror::Error;
 
 /// Mux tracing inspector that runs and collects results of multiple inspectors at once.
+///
+/// `J` runs next to the built-in tracers, with the `js-tracer` feature this is used to run
+/// JavaScript tracers, see `MuxInspector::with_js_tracer`.
 #[derive(Clone, Debug)]
-pub struct MuxInspector {
+pub struct MuxInspector<J = NoOpInspector> {
     /// An instance of FourByteInspector that can be reused
     four_byte: Option<FourByteInspector>,
     /// An instance of TracingInspector that can be reused
     tracing: Option<TracingInspector>,
     /// Configurations for different Geth trace types
     configs: Vec<(GethDebugBuiltInTracerType, TraceConfig)>,
+    /// The inspector that runs next to the built-in tracers
+    js: J,
 }
 
 /// Holds all Geth supported trace configurations
@@ -35,6 +51,7 @@ enum TraceConfig {
     Call(CallConfig),
     PreState(PreStateConfig),
     FlatCall(FlatCallConfig),
+    Erc7562(Erc7562Config),
     Noop,
 }
 
@@ -87,17 +104,129 @@ impl MuxInspector {
                         .merge(TracingInspectorConfig::from_flat_call_config(&flatcall_config));
                     configs.push((tracer_type, TraceConfig::FlatCall(flatcall_config)));
                 }
+                GethDebugBuiltInTracerType::Erc7562Tracer => {
+                    let erc7562_config = tracer_config
+                        .map(|config| serde_json::from_value(config.into_json()))
+                        .transpose()?
+                        .unwrap_or_default();
+
+                    inspector_config
+                        .merge(TracingInspectorConfig::from_geth_erc7562_config(&erc7562_config));
+                    configs.push((tracer_type, TraceConfig::Erc7562(erc7562_config)));
+                }
                 GethDebugBuiltInTracerType::MuxTracer => {
                     return Err(Error::UnexpectedConfig(tracer_type));
                 }
             }
         }
 
+        // the erc7562 tracer requires all stack snapshots, which merging does not preserve
+        if configs.iter().any(|(_, config)| matches!(config, TraceConfig::Erc7562(_))) {
+            inspector_config = inspector_config.set_stack_snapshots(StackSnapshotType::All);
+        }
+
         let tracing = (!configs.is_empty()).then(|| TracingInspector::new(inspector_config));
 
-        Ok(MuxInspector { four_byte, tracing, configs })
+        Ok(MuxInspector { four_byte, tracing, configs, js: NoOpInspector })
+    }
+
+    /// Adds a [JsInspector] that runs next to the configured built-in tracers.
+    ///
+    /// Its result is reported under the given `key` by [MuxInspector::try_into_mux_trace].
+    #[cfg(feature = "js-tracer")]
+    pub fn with_js_tracer(
+        self,
+        key: impl Into<String>,
+        inspector: JsInspector,
+    ) -> MuxInspector<MuxJsTracers> {
+        let Self { four_byte, tracing, configs, js: _ } = self;
+        MuxInspector { four_byte, tracing, configs, js: MuxJsTracers::default() }
+            .with_js_tracer(key, inspector)
+    }
+}
+
+#[cfg(feature = "js-tracer")]
+impl MuxInspector<MuxJsTracers> {
+    /// Try creating a new instance of [MuxInspector] from a geth `muxTracer` config that may
+    /// contain JavaScript tracers.
+    ///
+    /// Like geth, every key that is not the name of a built-in tracer is evaluated as the code of
+    /// a JavaScript tracer, and its result is reported under the same key.
+    pub fn try_from_js_config(config: GethDebugTracerConfig) -> Result<Self, Error> {
+        let tracers: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_value(config.into_json())?;
+
+        let mut builtin = MuxConfig::default();
+        let mut js = MuxJsTracers::default();
+        for (key, config) in tracers {
+            let config = Some(config).filter(|config| !config.is_null());
+            let tracer_type = serde_json::Value::String(key.clone());
+            match serde_json::from_value::<GethDebugBuiltInTracerType>(tracer_type) {
+                Ok(tracer_type) => {
+                    builtin.0.insert(tracer_type, config.map(GethDebugTracerConfig));
+                }
+                Err(_) => {
+                    let inspector = JsInspector::new(key.clone(), config.unwrap_or_default())
+                        .map_err(|err| Error::JsTracer(err.to_string()))?;
+                    js.0.push((key, inspector));
+                }
+            }
+        }
+
+        let MuxInspector { four_byte, tracing, configs, js: _ } =
+            MuxInspector::try_from_config(builtin)?;
+        Ok(Self { four_byte, tracing, configs, js })
+    }
+
+    /// Adds a [JsInspector] that runs next to the configured built-in tracers.
+    ///
+    /// Its result is reported under the given `key` by [MuxInspector::try_into_mux_trace].
+    pub fn with_js_tracer(mut self, key: impl Into<String>, inspector: JsInspector) -> Self {
+        self.js.0.push((key.into(), inspector));
+        self
     }
 
+    /// Try converting this [MuxInspector] into the result of geth's `muxTracer`, including the
+    /// results of all JavaScript tracers under their configured keys.
+    ///
+    /// [MuxFrame] can only hold the results of built-in tracers, so the combined frame is returned
+    /// as a [GethTrace::JS] object that serializes to the [MuxFrame] extended by the JavaScript
+    /// results.
+    ///
+    /// Note: This is supposed to be called after the inspection has finished, see
+    /// [JsInspector::json_result].
+    pub fn try_into_mux_trace<DB>(
+        &mut self,
+        result: &ResultAndState<impl HaltReasonTr>,
+        tx: &impl Transaction,
+        block: &impl Block,
+        db: &DB,
+        tx_info: TransactionInfo,
+    ) -> Result<GethTrace, Error>
+    where
+        DB: DatabaseRef,
+        <DB as DatabaseRef>::Error: core::fmt::Display,
+    {
+        let frame = self
+            .try_into_mux_frame(result, db, tx_info)
+            .map_err(|err| Error::Database(err.to_string()))?;
+        let serde_json::Value::Object(mut frame) =
+            serde_json::to_value(frame).expect("mux frame serializes to a JSON object")
+        else {
+            unreachable!("mux frame serializes to a JSON object")
+        };
+
+        for (key, inspector) in &mut self.js.0 {
+            let trace = inspector
+                .json_result(result.clone(), tx, block, db)
+                .map_err(|err| Error::JsTracer(err.to_string()))?;
+            frame.insert(key.clone(), trace);
+        }
+        Ok(GethTrace::JS(frame.into()))
+    }
+}
+
+impl<J> MuxInspector<J> {
     /// Try converting this [MuxInspector] into a [MuxFrame].
     pub fn try_into_mux_frame<DB: DatabaseRef>(
         &self,
@@ -129,12 +258,28 @@ impl MuxInspector {
                         continue;
                     }
                 }
-                TraceConfig::FlatCall(_flatcall_config) => {
+                TraceConfig::FlatCall(flatcall_config) => {
                     if let Some(inspector) = &self.tracing {
                         inspector
                             .clone()
                             .into_parity_builder()
-                            .into_localized_transaction_traces(tx_info)
+                            .with_transaction_gas_used(result.result.gas_used())
+                            .into_flat_call_traces(flatcall_config, tx_info)
+                            .into()
+                    } else {
+                        continue;
+                    }
+                }
+                TraceConfig::Erc7562(erc7562_config) => {
+                    if let Some(inspector) = &self.tracing {
+                        inspector
+                            .geth_builder()
+                            .geth_erc7562_traces(
+                                erc7562_config,
+                                result.result.gas_used(),
+                                result,
+                                db,
+                            )?
                             .into()
                     } else {
                         continue;
@@ -158,9 +303,10 @@ impl MuxInspector {
     }
 }
 
-impl<CTX> Inspector<CTX> for MuxInspector
+impl<CTX, J> Inspector<CTX> for MuxInspector<J>
 where
     CTX: ContextTr<Journal: JournalExt>,
+    J: Inspector<CTX>,
 {
     #[inline]
     fn initialize_interp(&mut self, interp: &mut Interpreter, context: &mut CTX) {
@@ -170,6 +316,7 @@ where
         if let Some(ref mut inspector) = self.tracing {
             inspector.initialize_interp(interp, context);
         }
+        self.js.initialize_interp(interp, context);
     }
 
     #[inline]
@@ -180,6 +327,7 @@ where
         if let Some(ref mut inspector) = self.tracing {
             inspector.step(interp, context);
         }
+        self.js.step(interp, context);
     }
 
     #[inline]
@@ -190,6 +338,7 @@ where
         if let Some(ref mut inspector) = self.tracing {
             inspector.step_end(interp, context);
         }
+        self.js.step_end(interp, context);
     }
 
     #[inline]
@@ -197,6 +346,7 @@ where
         if let Some(ref mut inspector) = self.four_byte {
             inspector.log(interp, context, log.clone());
         }
+        self.js.log(interp, context, log.clone());
         if let Some(ref mut inspector) = self.tracing {
             inspector.log(interp, context, log);
         }
@@ -207,10 +357,12 @@ where
         if let Some(ref mut inspector) = self.four_byte {
             let _ = inspector.call(context, inputs);
         }
+        // a JavaScript tracer aborts the call if it fails
+        let outcome = self.js.call(context, inputs);
         if let Some(ref mut inspector) = self.tracing {
-            return inspector.call(context, inputs);
+            return inspector.call(context, inputs).or(outcome);
         }
-        None
+        outcome
     }
 
     #[inline]
@@ -218,6 +370,7 @@ where
         if let Some(ref mut inspector) = self.four_byte {
             inspector.call_end(context, inputs, outcome);
         }
+        self.js.call_end(context, inputs, outcome);
         if let Some(ref mut inspector) = self.tracing {
             inspector.call_end(context, inputs, outcome);
         }
@@ -228,10 +381,12 @@ where
         if let Some(ref mut inspector) = self.four_byte {
             let _ = inspector.create(context, inputs);
         }
+        // a JavaScript tracer aborts the call if it fails
+        let outcome = self.js.create(context, inputs);
         if let Some(ref mut inspector) = self.tracing {
-            return inspector.create(context, inputs);
+            return inspector.create(context, inputs).or(outcome);
         }
-        None
+        outcome
     }
 
     #[inline]
@@ -244,6 +399,7 @@ where
         if let Some(ref mut inspector) = self.four_byte {
             inspector.create_end(context, inputs, outcome);
         }
+        self.js.create_end(context, inputs, outcome);
         if let Some(ref mut inspector) = self.tracing {
             inspector.create_end(context, inputs, outcome);
         }
@@ -257,6 +413,87 @@ where
         if let Some(ref mut inspector) = self.tracing {
             <TracingInspector as Inspector<CTX>>::selfdestruct(inspector, contract, target, value);
         }
+        self.js.selfdestruct(contract, target, value);
+    }
+}
+
+/// The JavaScript tracers of a [MuxInspector] and the keys their results are reported under, see
+/// [MuxInspector::with_js_tracer].
+#[cfg(feature = "js-tracer")]
+#[derive(Debug, Default)]
+pub struct MuxJsTracers(Vec<(String, JsInspector)>);
+
+#[cfg(feature = "js-tracer")]
+impl<CTX> Inspector<CTX> for MuxJsTracers
+where
+    CTX: ContextTr<Journal: JournalExt, Db: DatabaseRef>,
+{
+    #[inline]
+    fn initialize_interp(&mut self, interp: &mut Interpreter, context: &mut CTX) {
+        for (_, inspector) in &mut self.0 {
+            inspector.initialize_interp(interp, context);
+        }
+    }
+
+    #[inline]
+    fn step(&mut self, interp: &mut Interpreter, context: &mut CTX) {
+        for (_, inspector) in &mut self.0 {
+            inspector.step(interp, context);
+        }
+    }
+
+    #[inline]
+    fn step_end(&mut self, interp: &mut Interpreter, context: &mut CTX) {
+        for (_, inspector) in &mut self.0 {
+            inspector.step_end(interp, context);
+        }
+    }
+
+    #[inline]
+    fn log(&mut self, interp: &mut Interpreter, context: &mut CTX, log: Log) {
+        for (_, inspector) in &mut self.0 {
+            inspector.log(interp, context, log.clone());
+        }
+    }
+
+    #[inline]
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.0
+            .iter_mut()
+            .fold(None, |outcome, (_, inspector)| outcome.or(inspector.call(context, inputs)))
+    }
+
+    #[inline]
+    fn call_end(&mut self, context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
+        for (_, inspector) in &mut self.0 {
+            inspector.call_end(context, inputs, outcome);
+        }
+    }
+
+    #[inline]
+    fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.0
+            .iter_mut()
+            .fold(None, |outcome, (_, inspector)| outcome.or(inspector.create(context, inputs)))
+    }
+
+    #[inline]
+    fn create_end(
+        &mut self,
+        context: &mut CTX,
+        inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        for (_, inspector) in &mut self.0 {
+            inspector.create_end(context, inputs, outcome);
+        }
+    }
+
+    #[inline]
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        for (_, inspector) in &mut self.0 {
+            <JsInspector as Inspector<CTX>>::selfdestruct(inspector, contract, target, value);
+        }
     }
 }
 
@@ -272,4 +509,10 @@ pub enum Error {
     /// Error when deserializing the config
     #[error("error deserializing config: {0}")]
     InvalidConfig(#[from] serde_json::Error),
+    /// A JavaScript tracer could not be created or failed to produce its result
+    #[error("JavaScript tracer error: {0}")]
+    JsTracer(String),
+    /// Error when reading from the database
+    #[error("database error: {0}")]
+    Database(String),
 }
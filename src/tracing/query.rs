@@ -0,0 +1,350 @@
+//! Querying and pruning of a [CallTraceArena].
+
+use super::{
+    types::{CallKind, CallTraceNode, TraceMemberOrder},
+    CallTraceArena,
+};
+use alloc::{vec, vec::Vec};
+use alloy_primitives::{Address, Selector, U256};
+use revm::interpreter::InstructionResult;
+
+/// A filter for [CallTraceNode]s, see [CallTraceArena::query].
+///
+/// A node matches the filter if it matches all configured conditions, the default filter matches
+/// every node.
+///
+/// # Example
+///
+/// Find all reverted calls to `transfer(address,uint256)` of a token:
+///
+/// ```
+/// use alloy_primitives::{address, hex};
+/// use revm_inspectors::tracing::{CallTraceArena, CallTraceFilter};
+///
+/// let filter = CallTraceFilter::new()
+///     .address(address!("0xdac17f958d2ee523a2206206994597c13d831ec7"))
+///     .selector(hex!("a9059cbb").into())
+///     .success(false);
+/// let matches = CallTraceArena::default().query(&filter);
+/// assert!(matches.is_empty());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallTraceFilter {
+    address: Option<Address>,
+    caller: Option<Address>,
+    selector: Option<Selector>,
+    kinds: Vec<CallKind>,
+    min_depth: Option<usize>,
+    max_depth: Option<usize>,
+    success: Option<bool>,
+    status: Option<InstructionResult>,
+    min_value: Option<U256>,
+    min_gas_used: Option<u64>,
+    max_gas_used: Option<u64>,
+}
+
+impl CallTraceFilter {
+    /// Creates a new filter that matches every node.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match calls to the given address, see
+    /// [CallTrace::address](super::types::CallTrace::address).
+    pub const fn address(mut self, address: Address) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Only match calls made by the given address.
+    pub const fn caller(mut self, caller: Address) -> Self {
+        self.caller = Some(caller);
+        self
+    }
+
+    /// Only match calls with the given selector, see [CallTraceNode::selector].
+    pub const fn selector(mut self, selector: Selector) -> Self {
+        self.selector = Some(selector);
+        self
+    }
+
+    /// Only match calls of the given kind.
+    ///
+    /// This can be called multiple times to match any of the given kinds.
+    pub fn kind(mut self, kind: CallKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Only match calls with a depth of at least `depth`, the root call has depth `0`.
+    pub const fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = Some(depth);
+        self
+    }
+
+    /// Only match calls with a depth of at most `depth`, the root call has depth `0`.
+    pub const fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Only match successful calls if `true`, or failed calls if `false`.
+    pub const fn success(mut self, success: bool) -> Self {
+        self.success = Some(success);
+        self
+    }
+
+    /// Only match calls that ended with the given status.
+    pub const fn status(mut self, status: InstructionResult) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Only match calls that transfer at least the given value.
+    pub const fn min_value(mut self, value: U256) -> Self {
+        self.min_value = Some(value);
+        self
+    }
+
+    /// Only match calls that used at least the given amount of gas.
+    pub const fn min_gas_used(mut self, gas: u64) -> Self {
+        self.min_gas_used = Some(gas);
+        self
+    }
+
+    /// Only match calls that used at most the given amount of gas.
+    pub const fn max_gas_used(mut self, gas: u64) -> Self {
+        self.max_gas_used = Some(gas);
+        self
+    }
+
+    /// Returns `true` if the node matches all conditions of this filter.
+    pub fn matches(&self, node: &CallTraceNode) -> bool {
+        let trace = &node.trace;
+        self.address.is_none_or(|address| trace.address == address)
+            && self.caller.is_none_or(|caller| trace.caller == caller)
+            && self.selector.is_none_or(|selector| node.selector() == Some(selector))
+            && (self.kinds.is_empty() || self.kinds.contains(&trace.kind))
+            && self.min_depth.is_none_or(|depth| trace.depth >= depth)
+            && self.max_depth.is_none_or(|depth| trace.depth <= depth)
+            && self.success.is_none_or(|success| trace.success == success)
+            && self.status.is_none_or(|status| trace.status == Some(status))
+            && self.min_value.is_none_or(|value| trace.value >= value)
+            && self.min_gas_used.is_none_or(|gas| trace.gas_used >= gas)
+            && self.max_gas_used.is_none_or(|gas| trace.gas_used <= gas)
+    }
+}
+
+/// A node matched by [CallTraceArena::query].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallTraceMatch {
+    /// The index of the node in the arena.
+    pub idx: usize,
+    /// The parity style trace address of the node, i.e. the indices of the node and its ancestors
+    /// within their parent's [CallTraceNode::children], starting at the root.
+    ///
+    /// For nodes of a [pruned](CallTraceArena::prune) arena this is the trace address in the
+    /// original arena, see [CallTraceNode::original_position].
+    pub trace_address: Vec<usize>,
+}
+
+impl CallTraceArena {
+    /// Returns all nodes that match the given filter, in depth-first order.
+    pub fn query(&self, filter: &CallTraceFilter) -> Vec<CallTraceMatch> {
+        self.query_by(|node| filter.matches(node))
+    }
+
+    /// Returns all nodes for which the given predicate returns `true`, in depth-first order.
+    ///
+    /// Nodes that are not attached to their parent, e.g. excluded precompile calls, are not
+    /// considered.
+    pub fn query_by<F>(&self, mut predicate: F) -> Vec<CallTraceMatch>
+    where
+        F: FnMut(&CallTraceNode) -> bool,
+    {
+        let mut matches = Vec::new();
+        // the nodes to visit and their trace address
+        let mut stack = vec![(0, Vec::new())];
+        while let Some((idx, trace_address)) = stack.pop() {
+            let node = &self.arena[idx];
+            for (child_pos, child) in node.children.iter().enumerate().rev() {
+                let mut child_address = trace_address.clone();
+                child_address.push(self.arena[*child].original_position.unwrap_or(child_pos));
+                stack.push((*child, child_address));
+            }
+            if predicate(node) {
+                matches.push(CallTraceMatch { idx, trace_address });
+            }
+        }
+        matches
+    }
+
+    /// Returns a new arena that only contains the nodes that match the given filter and their
+    /// ancestors, see [Self::prune].
+    pub fn prune_to_matches(&self, filter: &CallTraceFilter) -> Self {
+        self.prune(self.query(filter).into_iter().map(|m| m.idx))
+    }
+
+    /// Returns a new arena that only contains the nodes with the given indices and their
+    /// ancestors.
+    ///
+    /// The root node is always retained. Nodes are re-indexed, and the
+    /// [ordering](CallTraceNode::ordering), [children](CallTraceNode::children) and log positions
+    /// of the retained nodes are updated accordingly, so the pruned arena can be rendered by any
+    /// of the trace builders or the [TraceWriter](crate::tracing::TraceWriter). Steps and logs of
+    /// retained nodes are kept.
+    ///
+    /// Every retained node remembers its [original position](CallTraceNode::original_position)
+    /// in its parent, so trace addresses, e.g. of the parity trace builder or [Self::query], are
+    /// the same as in this arena. The number of subtraces only counts retained children.
+    ///
+    /// # Panics
+    ///
+    /// If any of the indices is out of bounds.
+    pub fn prune(&self, indices: impl IntoIterator<Item = usize>) -> Self {
+        let mut retain = vec![false; self.arena.len()];
+        retain[0] = true;
+        for mut idx in indices {
+            while !retain[idx] {
+                retain[idx] = true;
+                let Some(parent) = self.arena[idx].parent else { break };
+                idx = parent;
+            }
+        }
+
+        // assign new indices in depth-first order
+        let mut new_idx = vec![None; self.arena.len()];
+        let retained = self
+            .iter_pre_order()
+            .filter(|node| retain[node.idx])
+            .enumerate()
+            .map(|(idx, node)| {
+                new_idx[node.idx] = Some(idx);
+                node
+            })
+            .collect::<Vec<_>>();
+
+        let arena = retained
+            .into_iter()
+            .map(|node| {
+                let mut children = Vec::new();
+                let mut ordering = Vec::with_capacity(node.ordering.len());
+                let mut logs = node.logs.clone();
+                for order in &node.ordering {
+                    match *order {
+                        TraceMemberOrder::Call(child_idx) => {
+                            if let Some(child) = new_idx[node.children[child_idx]] {
+                                ordering.push(TraceMemberOrder::Call(children.len()));
+                                children.push(child);
+                            }
+                        }
+                        TraceMemberOrder::Log(log_idx) => {
+                            logs[log_idx].position = children.len() as u64;
+                            ordering.push(*order);
+                        }
+                        TraceMemberOrder::Step(_) => ordering.push(*order),
+                    }
+                }
+                CallTraceNode {
+                    parent: node.parent.and_then(|parent| new_idx[parent]),
+                    children,
+                    idx: new_idx[node.idx].expect("retained node has an index"),
+                    trace: node.trace.clone(),
+                    logs,
+                    ordering,
+                    state_changes: node.state_changes.clone(),
+                    accessed_state: node.accessed_state.clone(),
+                    original_position: node
+                        .parent
+                        .and_then(|parent| node.position_in_parent(&self.arena[parent])),
+                }
+            })
+            .collect();
+
+        Self { arena }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an arena with the call graph `0 -> (1 -> (2, 3), 4 -> 5)`, where `3` and `5` are
+    /// reverted delegate calls.
+    fn arena() -> CallTraceArena {
+        let mut arena = CallTraceArena::from_depths([0, 1, 2, 2, 1, 2]);
+        for node in arena.nodes_mut() {
+            let reverted = matches!(node.idx, 3 | 5);
+            if reverted {
+                node.trace.kind = CallKind::DelegateCall;
+                node.trace.status = Some(InstructionResult::Revert);
+            } else {
+                node.trace.success = true;
+                node.trace.status = Some(InstructionResult::Stop);
+            }
+            node.trace.gas_used = 100 * node.idx as u64;
+        }
+        arena
+    }
+
+    #[test]
+    fn query_arena() {
+        let arena = arena();
+
+        let matches = arena.query(&CallTraceFilter::new().success(false));
+        assert_eq!(
+            matches,
+            [
+                CallTraceMatch { idx: 3, trace_address: vec![0, 1] },
+                CallTraceMatch { idx: 5, trace_address: vec![1, 0] },
+            ]
+        );
+
+        let filter = CallTraceFilter::new().kind(CallKind::DelegateCall).min_gas_used(400);
+        assert_eq!(arena.query(&filter), [CallTraceMatch { idx: 5, trace_address: vec![1, 0] }]);
+
+        let filter = CallTraceFilter::new().address(Address::with_last_byte(4)).max_depth(1);
+        assert_eq!(arena.query(&filter), [CallTraceMatch { idx: 4, trace_address: vec![1] }]);
+
+        assert_eq!(arena.query(&CallTraceFilter::new()).len(), 6);
+        assert!(arena.query(&CallTraceFilter::new().min_depth(3)).is_empty());
+    }
+
+    #[test]
+    fn prune_arena() {
+        let arena = arena();
+        let pruned =
+            arena.prune_to_matches(&CallTraceFilter::new().status(InstructionResult::Revert));
+
+        let addresses =
+            pruned.nodes().iter().map(|node| node.trace.address.0[19]).collect::<Vec<_>>();
+        assert_eq!(addresses, [0, 1, 3, 4, 5]);
+        let children = pruned.nodes().iter().map(|node| node.children.clone()).collect::<Vec<_>>();
+        assert_eq!(children, [vec![1, 3], vec![2], vec![], vec![4], vec![]]);
+        // the steps of the pruned call are kept, the log is positioned after the retained call
+        assert_eq!(
+            pruned.nodes()[1].ordering,
+            [
+                TraceMemberOrder::Step(0),
+                TraceMemberOrder::Step(1),
+                TraceMemberOrder::Call(0),
+                TraceMemberOrder::Log(0)
+            ]
+        );
+        assert_eq!(pruned.nodes()[1].logs[0].position, 1);
+        assert_eq!(pruned.nodes()[4].parent, Some(3));
+
+        // the trace addresses of the matches are the same as in the original arena
+        let matches = pruned.query(&CallTraceFilter::new().success(false));
+        assert_eq!(matches[0].trace_address, [0, 1]);
+        assert_eq!(matches[1].trace_address, [1, 0]);
+        assert_eq!(pruned.nodes()[2].original_position, Some(1));
+
+        // pruning a pruned arena keeps the original trace addresses
+        let pruned = pruned.prune([2]);
+        assert_eq!(pruned.query(&CallTraceFilter::new().success(false))[0].trace_address, [0, 1]);
+
+        // pruning to the root only retains the root
+        assert_eq!(arena.prune([]).nodes().len(), 1);
+    }
+}
@@ -0,0 +1,178 @@
+//! Decoding of revert payloads.
+
+use super::{types::CallTrace, CallTraceArena};
+use alloc::string::String;
+use alloy_primitives::{hex, Bytes, Selector};
+use alloy_sol_types::{ContractError, GenericContractError, Panic, SolInterface};
+use core::fmt;
+
+/// A decoded revert payload, see [RevertReason::decode].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevertReason {
+    /// A `revert("reason")` or `require(false, "reason")`, encoded as `Error(string)`.
+    Error(String),
+    /// A failed assertion or runtime error, encoded as `Panic(uint256)`.
+    ///
+    /// Known panic codes are displayed with their meaning, e.g. `panic: division or modulo by
+    /// zero (0x12)`.
+    Panic(Panic),
+    /// A custom error that can not be decoded without its ABI.
+    CustomError {
+        /// The selector of the error.
+        selector: Selector,
+        /// The ABI-encoded parameters of the error.
+        data: Bytes,
+    },
+    /// A payload that is a printable UTF-8 string, as returned by some non-Solidity contracts.
+    RawString(String),
+}
+
+impl RevertReason {
+    /// Decodes a revert payload, returns `None` if it is empty or not a known format.
+    ///
+    /// Payloads that are printable UTF-8 strings are considered raw strings, other payloads that
+    /// consist of a selector and ABI-encoded words are considered custom errors.
+    pub fn decode(output: &[u8]) -> Option<Self> {
+        if let Ok(error) = GenericContractError::abi_decode(output) {
+            return match error {
+                ContractError::Revert(revert) => Some(Self::Error(revert.reason)),
+                ContractError::Panic(panic) => Some(Self::Panic(panic)),
+                ContractError::CustomError(never) => match never {},
+            };
+        }
+        // printable strings never contain the zero padding of ABI-encoded words, so they are
+        // checked first, e.g. `fail` would otherwise be a custom error without parameters
+        if let Ok(reason) = core::str::from_utf8(output) {
+            if !reason.is_empty() && !reason.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+                return Some(Self::RawString(reason.into()));
+            }
+        }
+        (output.len() >= 4 && (output.len() - 4).is_multiple_of(32)).then(|| Self::CustomError {
+            selector: Selector::from_slice(&output[..4]),
+            data: Bytes::copy_from_slice(&output[4..]),
+        })
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error(reason) | Self::RawString(reason) => f.write_str(reason),
+            Self::Panic(panic) => panic.fmt(f),
+            Self::CustomError { selector, data } => {
+                write!(f, "custom error {selector}")?;
+                if !data.is_empty() {
+                    write!(f, "({})", hex::encode_prefixed(data))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl CallTrace {
+    /// Returns the decoded revert payload if the call reverted, see [RevertReason::decode].
+    pub fn revert_reason(&self) -> Option<RevertReason> {
+        if !self.status.is_some_and(|status| status.is_revert()) {
+            return None;
+        }
+        RevertReason::decode(&self.output)
+    }
+}
+
+impl CallTraceArena {
+    /// Returns the index of the node where the revert of the given node originated.
+    ///
+    /// Reverts are commonly bubbled up from a failed sub-call by reverting with the same payload.
+    /// This follows the sub-calls that reverted with the same non-empty payload as their parent,
+    /// and returns the deepest one, which is the given node itself if the revert was not bubbled
+    /// up. Returns `None` if the node did not revert.
+    ///
+    /// # Panics
+    ///
+    /// If the index is out of bounds.
+    pub fn revert_origin(&self, idx: usize) -> Option<usize> {
+        let is_revert =
+            |idx: usize| self.arena[idx].trace.status.is_some_and(|status| status.is_revert());
+        if !is_revert(idx) {
+            return None;
+        }
+
+        let mut origin = idx;
+        loop {
+            let node = &self.arena[origin];
+            if node.trace.output.is_empty() {
+                return Some(origin);
+            }
+            let child = node.children.iter().rev().copied().find(|&child| {
+                is_revert(child) && self.arena[child].trace.output == node.trace.output
+            });
+            match child {
+                Some(child) => origin = child,
+                None => return Some(origin),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracing::arena::PushTraceKind;
+    use alloc::string::ToString;
+    use alloy_primitives::{bytes, U256};
+    use alloy_sol_types::{PanicKind, Revert, SolError};
+    use revm::interpreter::InstructionResult;
+
+    #[test]
+    fn decode_revert_reasons() {
+        let error = Revert::from("my revert").abi_encode();
+        assert_eq!(RevertReason::decode(&error), Some(RevertReason::Error("my revert".into())));
+
+        let panic = Panic::from(PanicKind::DivisionByZero).abi_encode();
+        let reason = RevertReason::decode(&panic).unwrap();
+        assert_eq!(reason, RevertReason::Panic(Panic { code: U256::from(0x12) }));
+        assert_eq!(reason.to_string(), "panic: division or modulo by zero (0x12)");
+
+        let custom =
+            bytes!("deadbeef000000000000000000000000000000000000000000000000000000000000002a");
+        let reason = RevertReason::decode(&custom).unwrap();
+        assert_eq!(
+            reason.to_string(),
+            "custom error 0xdeadbeef(0x000000000000000000000000000000000000000000000000000000000000002a)"
+        );
+        assert_eq!(
+            RevertReason::decode(&custom[..4]).unwrap().to_string(),
+            "custom error 0xdeadbeef"
+        );
+
+        assert_eq!(RevertReason::decode(b"fail!"), Some(RevertReason::RawString("fail!".into())));
+        // printable payloads that look like a selector with ABI-encoded words are raw strings
+        assert_eq!(RevertReason::decode(b"fail"), Some(RevertReason::RawString("fail".into())));
+        assert_eq!(RevertReason::decode(&[0; 32]), None);
+        assert_eq!(RevertReason::decode(&[]), None);
+    }
+
+    #[test]
+    fn bubbled_revert_origin() {
+        let error = Bytes::from(Revert::from("my revert").abi_encode());
+        let mut arena = CallTraceArena::default();
+        // 0 -> (1, 2 -> 3), where 3 reverted and 2 and 0 bubbled up the revert
+        for (depth, status, output) in [
+            (0, InstructionResult::Revert, error.clone()),
+            (1, InstructionResult::Revert, bytes!("01")),
+            (1, InstructionResult::Revert, error.clone()),
+            (2, InstructionResult::Revert, error),
+        ] {
+            let trace = CallTrace { depth, status: Some(status), output, ..Default::default() };
+            arena.push_trace(0, PushTraceKind::PushAndAttachToParent, trace);
+        }
+        assert_eq!(arena.revert_origin(0), Some(3));
+        assert_eq!(arena.revert_origin(1), Some(1));
+        assert_eq!(arena.revert_origin(3), Some(3));
+
+        arena.nodes_mut()[3].trace.status = Some(InstructionResult::Stop);
+        assert_eq!(arena.revert_origin(0), Some(2));
+        assert_eq!(arena.revert_origin(3), None);
+    }
+}
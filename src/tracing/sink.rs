@@ -0,0 +1,47 @@
+use crate::tracing::types::{CallTraceNode, CallTraceStep};
+
+/// A consumer of recorded [`CallTraceStep`]s.
+///
+/// If a sink is installed via
+/// [`TracingInspector::with_step_sink`](crate::tracing::TracingInspector::with_step_sink), every
+/// recorded step is handed to the sink as soon as it has been executed, instead of being stored in
+/// [`CallTrace::steps`](crate::tracing::types::CallTrace::steps). This keeps memory usage flat for
+/// long running transactions, e.g. when the steps are serialized into struct logs on the fly.
+///
+/// Call nodes, logs and the [`TraceMemberOrder`](crate::tracing::types::TraceMemberOrder) of each
+/// node are still recorded in the arena, so
+/// [`TraceMemberOrder::Step`](crate::tracing::types::TraceMemberOrder::Step) indices can be used to
+/// correlate streamed steps with their position in the call graph.
+///
+/// Steps are handed to the sink in execution order.
+pub trait StepSink {
+    /// Invoked once a step has been executed and all of its fields are populated.
+    ///
+    /// `node` is the call trace node the step belongs to and `step_idx` is the index of the step
+    /// within that node, as referenced by
+    /// [`TraceMemberOrder::Step`](crate::tracing::types::TraceMemberOrder::Step).
+    fn record_step(&mut self, node: &CallTraceNode, step_idx: usize, step: CallTraceStep);
+}
+
+impl<F> StepSink for F
+where
+    F: FnMut(&CallTraceNode, usize, CallTraceStep),
+{
+    fn record_step(&mut self, node: &CallTraceNode, step_idx: usize, step: CallTraceStep) {
+        self(node, step_idx, step)
+    }
+}
+
+/// A [`StepSink`] that discards all steps.
+///
+/// This is the default sink type of the
+/// [`TracingInspector`](crate::tracing::TracingInspector). Note that an inspector without an
+/// installed sink stores steps in the arena; installing this sink explicitly drops them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NoopStepSink;
+
+impl StepSink for NoopStepSink {
+    #[inline]
+    fn record_step(&mut self, _node: &CallTraceNode, _step_idx: usize, _step: CallTraceStep) {}
+}
@@ -0,0 +1,473 @@
+//! Decoding of internal function calls from Solidity source maps.
+//!
+//! See <https://docs.soliditylang.org/en/latest/internals/source_mappings.html>
+
+use super::{
+    types::{CallTraceNode, CallTraceStep, DecodedInternalCall, DecodedTraceStep},
+    CallTraceArena,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use alloy_primitives::{map::HashMap, Address, B256, I256, U256};
+use revm::bytecode::opcode;
+use serde_json::Value;
+
+/// Errors that can occur when parsing a source map.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum SourceMapError {
+    /// The entry with the given index is malformed.
+    #[error("invalid source map entry {0}")]
+    InvalidEntry(usize),
+}
+
+/// The jump annotation of a [SourceElement].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Jump {
+    /// A jump into a function.
+    In,
+    /// A return from a function.
+    Out,
+    /// A regular jump, e.g. as part of a loop, or no jump at all.
+    #[default]
+    Regular,
+}
+
+/// A decompressed entry of a source map, describing the source range of a single instruction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SourceElement {
+    /// The byte offset of the range in the source file.
+    pub offset: u32,
+    /// The length of the range in bytes.
+    pub length: u32,
+    /// The index of the source file, `None` if the instruction is not associated with any source
+    /// file, e.g. for compiler generated code.
+    pub index: Option<u32>,
+    /// The jump annotation of the instruction.
+    pub jump: Jump,
+    /// The modifier depth of the instruction.
+    pub modifier_depth: u32,
+}
+
+impl SourceElement {
+    /// Returns `true` if the range of this element is contained in the given range.
+    fn is_within(&self, index: u32, offset: u32, length: u32) -> bool {
+        self.index == Some(index)
+            && self.offset >= offset
+            && self.offset as u64 + self.length as u64 <= offset as u64 + length as u64
+    }
+}
+
+/// Parses a compressed source map, as emitted by solc in `evm.deployedBytecode.sourceMap`.
+pub fn parse_source_map(source_map: &str) -> Result<Vec<SourceElement>, SourceMapError> {
+    let mut elements = Vec::new();
+    if source_map.is_empty() {
+        return Ok(elements);
+    }
+
+    let mut last = SourceElement::default();
+    for (entry_idx, entry) in source_map.split(';').enumerate() {
+        let err = || SourceMapError::InvalidEntry(entry_idx);
+        for (field_idx, field) in entry.split(':').enumerate() {
+            if field.is_empty() {
+                continue;
+            }
+            match field_idx {
+                0 => last.offset = field.parse().map_err(|_| err())?,
+                1 => last.length = field.parse().map_err(|_| err())?,
+                2 => {
+                    last.index = match field.parse::<i64>().map_err(|_| err())? {
+                        -1 => None,
+                        index => Some(u32::try_from(index).map_err(|_| err())?),
+                    }
+                }
+                3 => {
+                    last.jump = match field {
+                        "i" => Jump::In,
+                        "o" => Jump::Out,
+                        "-" => Jump::Regular,
+                        _ => return Err(err()),
+                    }
+                }
+                4 => last.modifier_depth = field.parse().map_err(|_| err())?,
+                _ => return Err(err()),
+            }
+        }
+        elements.push(last);
+    }
+    Ok(elements)
+}
+
+/// The source range and signature of a function definition, see [FunctionRange::from_ast].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionRange {
+    /// The name of the function, prefixed with the name of its contract, e.g. `Token::_transfer`.
+    pub name: String,
+    /// The index of the source file.
+    pub index: u32,
+    /// The byte offset of the function definition in the source file.
+    pub offset: u32,
+    /// The length of the function definition in bytes.
+    pub length: u32,
+    /// The types of the parameters, e.g. `uint256` or `string memory`.
+    pub parameters: Vec<String>,
+    /// The types of the return values.
+    pub returns: Vec<String>,
+}
+
+impl FunctionRange {
+    /// Collects all function definitions of a solc AST, as emitted in `sources.<file>.ast`.
+    ///
+    /// Definitions with a malformed `src` attribute are skipped.
+    pub fn from_ast(ast: &Value) -> Vec<Self> {
+        let mut functions = Vec::new();
+        collect_functions(ast, None, &mut functions);
+        functions
+    }
+}
+
+fn collect_functions(node: &Value, contract: Option<&str>, functions: &mut Vec<FunctionRange>) {
+    match node {
+        Value::Array(nodes) => {
+            for node in nodes {
+                collect_functions(node, contract, functions);
+            }
+        }
+        Value::Object(object) => {
+            let contract = match object.get("nodeType").and_then(Value::as_str) {
+                Some("ContractDefinition") => object.get("name").and_then(Value::as_str),
+                Some("FunctionDefinition") => {
+                    functions.extend(function_range(node, contract));
+                    contract
+                }
+                _ => contract,
+            };
+            for value in object.values() {
+                collect_functions(value, contract, functions);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn function_range(node: &Value, contract: Option<&str>) -> Option<FunctionRange> {
+    let mut src = node["src"].as_str()?.split(':').map(str::parse::<u32>);
+    let (offset, length, index) = (src.next()?.ok()?, src.next()?.ok()?, src.next()?.ok()?);
+
+    // constructors, fallback and receive functions are unnamed
+    let name = match node["name"].as_str() {
+        Some(name) if !name.is_empty() => name,
+        _ => node["kind"].as_str().unwrap_or("<unknown>"),
+    };
+    let types = |params: &Value| {
+        params["parameters"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|param| param["typeDescriptions"]["typeString"].as_str().unwrap_or("").to_string())
+            .collect()
+    };
+
+    Some(FunctionRange {
+        name: match contract {
+            Some(contract) => format!("{contract}::{name}"),
+            None => name.to_string(),
+        },
+        index,
+        offset,
+        length,
+        parameters: types(&node["parameters"]),
+        returns: types(&node["returnParameters"]),
+    })
+}
+
+/// The source map of the runtime bytecode of a single contract, see [SourceMapDecoder].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContractSourceMap {
+    /// Maps program counters to instruction indices, `None` for push immediates.
+    pc_to_ic: Vec<Option<usize>>,
+    /// The source elements, indexed by instruction index.
+    elements: Vec<SourceElement>,
+    /// The function definitions of the contract and its dependencies.
+    functions: Vec<FunctionRange>,
+}
+
+impl ContractSourceMap {
+    /// Creates a new source map from the runtime bytecode, the compressed source map of the
+    /// runtime bytecode and the function definitions of all sources, see
+    /// [FunctionRange::from_ast].
+    pub fn new(
+        runtime_bytecode: &[u8],
+        source_map: &str,
+        functions: Vec<FunctionRange>,
+    ) -> Result<Self, SourceMapError> {
+        let elements = parse_source_map(source_map)?;
+        let mut pc_to_ic = vec![None; runtime_bytecode.len()];
+        let (mut pc, mut ic) = (0, 0);
+        while pc < runtime_bytecode.len() {
+            pc_to_ic[pc] = Some(ic);
+            let op = runtime_bytecode[pc];
+            pc += 1;
+            if (opcode::PUSH1..=opcode::PUSH32).contains(&op) {
+                pc += (op - opcode::PUSH0) as usize;
+            }
+            ic += 1;
+        }
+        Ok(Self { pc_to_ic, elements, functions })
+    }
+
+    /// Returns the source element of the instruction at the given program counter.
+    pub fn element_at(&self, pc: usize) -> Option<&SourceElement> {
+        self.elements.get((*self.pc_to_ic.get(pc)?)?)
+    }
+
+    /// Returns the innermost function whose definition contains the given source element.
+    pub fn function_at(&self, element: &SourceElement) -> Option<&FunctionRange> {
+        self.functions
+            .iter()
+            .filter(|function| element.is_within(function.index, function.offset, function.length))
+            .min_by_key(|function| function.length)
+    }
+}
+
+/// Decodes internal function calls of Solidity contracts, see [DecodedTraceStep::InternalCall].
+///
+/// Internal calls are detected from the jump annotations of the source maps: a jump into a
+/// function starts an internal call, which ends with the matching jump out of the function. The
+/// called function is determined by the source range of the jump destination.
+///
+/// Arguments and return values of value types are decoded from the stack, which requires stack
+/// snapshots to be recorded, see
+/// [TracingInspectorConfig::record_stack_snapshots](crate::tracing::TracingInspectorConfig::record_stack_snapshots).
+/// Arguments and return values are omitted if any of them has a reference type.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMapDecoder {
+    contracts: HashMap<Address, ContractSourceMap>,
+}
+
+impl SourceMapDecoder {
+    /// Creates a new decoder without any contracts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the source map of the contract deployed at the given address.
+    pub fn with_contract(mut self, address: Address, contract: ContractSourceMap) -> Self {
+        self.insert_contract(address, contract);
+        self
+    }
+
+    /// Adds the source map of the contract deployed at the given address.
+    pub fn insert_contract(&mut self, address: Address, contract: ContractSourceMap) {
+        self.contracts.insert(address, contract);
+    }
+
+    /// Decodes the internal calls of all nodes with a known contract.
+    pub fn decode_arena(&self, arena: &mut CallTraceArena) {
+        for node in arena.nodes_mut() {
+            self.decode_node(node);
+        }
+    }
+
+    /// Decodes the internal calls of the given node, if its contract is known.
+    ///
+    /// Internal calls that do not return, e.g. because execution reverted, are not decoded.
+    pub fn decode_node(&self, node: &mut CallTraceNode) {
+        if node.trace.kind.is_any_create() {
+            return;
+        }
+        let Some(contract) = self.contracts.get(&node.trace.address) else { return };
+
+        // the open internal calls: the index of the entry step and the called function
+        let mut open = Vec::<(usize, &FunctionRange)>::new();
+        let steps = &mut node.trace.steps;
+        for idx in 0..steps.len() {
+            let step = &steps[idx];
+            if step.op.get() != opcode::JUMP {
+                continue;
+            }
+            let Some(element) = contract.element_at(step.pc) else { continue };
+            match element.jump {
+                Jump::In => {
+                    let function = steps
+                        .get(idx + 1)
+                        .and_then(|dest| contract.element_at(dest.pc))
+                        .and_then(|dest| contract.function_at(dest));
+                    if let Some(function) = function {
+                        open.push((idx, function));
+                    }
+                }
+                Jump::Out => {
+                    let Some((start, function)) = open.pop() else { continue };
+                    let call = DecodedInternalCall {
+                        func_name: function.name.clone(),
+                        args: decode_stack_values(&steps[start], &function.parameters),
+                        return_data: decode_stack_values(step, &function.returns),
+                    };
+                    steps[start].decoded = Some(DecodedTraceStep::InternalCall(call, idx));
+                }
+                Jump::Regular => {}
+            }
+        }
+    }
+}
+
+/// Decodes the values below the jump destination or return address on top of the stack of the
+/// given jump step.
+fn decode_stack_values(step: &CallTraceStep, types: &[String]) -> Option<Vec<String>> {
+    let stack = step.stack.as_deref()?;
+    let end = stack.len().checked_sub(1)?;
+    let values = &stack[end.checked_sub(types.len())?..end];
+    types.iter().zip(values).map(|(ty, value)| decode_value(ty, *value)).collect()
+}
+
+/// Decodes a stack value of the given Solidity value type.
+fn decode_value(ty: &str, value: U256) -> Option<String> {
+    let ty = ty.strip_suffix(" payable").unwrap_or(ty);
+    if ty == "address" || ty.starts_with("contract ") {
+        return Some(Address::from_word(value.into()).to_checksum(None));
+    }
+    if ty == "bool" {
+        return Some((!value.is_zero()).to_string());
+    }
+    if ty.starts_with("enum ") {
+        return Some(value.to_string());
+    }
+    if let Some(bits) = ty.strip_prefix("uint") {
+        let bits = if bits.is_empty() { 256 } else { bits.parse().ok()? };
+        return Some(value.wrapping_shl(256 - bits).wrapping_shr(256 - bits).to_string());
+    }
+    if let Some(bits) = ty.strip_prefix("int") {
+        let bits = if bits.is_empty() { 256 } else { bits.parse().ok()? };
+        return Some(I256::from_raw(value.wrapping_shl(256 - bits)).asr(256 - bits).to_string());
+    }
+    if let Some(len) = ty.strip_prefix("bytes") {
+        let len = len.parse::<usize>().ok().filter(|len| (1..=32).contains(len))?;
+        return Some(alloy_primitives::hex::encode_prefixed(&B256::from(value)[..len]));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_compressed_source_map() {
+        let elements = parse_source_map("1:2:0:-;:5;::-1:i;;7:::o:1").unwrap();
+        assert_eq!(
+            elements,
+            [
+                SourceElement { offset: 1, length: 2, index: Some(0), ..Default::default() },
+                SourceElement { offset: 1, length: 5, index: Some(0), ..Default::default() },
+                SourceElement {
+                    offset: 1,
+                    length: 5,
+                    index: None,
+                    jump: Jump::In,
+                    modifier_depth: 0
+                },
+                SourceElement {
+                    offset: 1,
+                    length: 5,
+                    index: None,
+                    jump: Jump::In,
+                    modifier_depth: 0
+                },
+                SourceElement {
+                    offset: 7,
+                    length: 5,
+                    index: None,
+                    jump: Jump::Out,
+                    modifier_depth: 1
+                },
+            ]
+        );
+        assert_eq!(parse_source_map("1:2;x"), Err(SourceMapError::InvalidEntry(1)));
+        assert_eq!(parse_source_map(":::j"), Err(SourceMapError::InvalidEntry(0)));
+        assert!(parse_source_map("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn element_within_range() {
+        let element = SourceElement { offset: 10, length: 5, index: Some(0), ..Default::default() };
+        assert!(element.is_within(0, 10, 5));
+        assert!(!element.is_within(0, 11, 5));
+        assert!(!element.is_within(1, 10, 5));
+
+        // ranges at the end of the `u32` range don't overflow
+        let element = SourceElement { offset: u32::MAX, length: u32::MAX, ..element };
+        assert!(element.is_within(0, u32::MAX, u32::MAX));
+        assert!(!element.is_within(0, u32::MAX, 1));
+    }
+
+    #[test]
+    fn decode_values() {
+        assert_eq!(decode_value("uint8", U256::from(0x1ff)).unwrap(), "255");
+        assert_eq!(decode_value("int8", U256::from(0xff)).unwrap(), "-1");
+        assert_eq!(decode_value("int256", U256::MAX).unwrap(), "-1");
+        assert_eq!(decode_value("bool", U256::from(2)).unwrap(), "true");
+        assert_eq!(
+            decode_value("address payable", U256::from(0xaa)).unwrap(),
+            "0x00000000000000000000000000000000000000AA"
+        );
+        assert_eq!(decode_value("bytes2", U256::from(0xabcd) << 240).unwrap(), "0xabcd");
+        assert_eq!(decode_value("string memory", U256::from(0x80)), None);
+    }
+
+    #[test]
+    fn functions_from_ast() {
+        let ast = serde_json::json!({
+            "nodeType": "SourceUnit",
+            "nodes": [{
+                "nodeType": "ContractDefinition",
+                "name": "C",
+                "nodes": [
+                    {
+                        "nodeType": "FunctionDefinition",
+                        "name": "f",
+                        "kind": "function",
+                        "src": "10:50:0",
+                        "parameters": {
+                            "parameters": [{ "typeDescriptions": { "typeString": "uint256" } }]
+                        },
+                        "returnParameters": {
+                            "parameters": [{ "typeDescriptions": { "typeString": "bool" } }]
+                        }
+                    },
+                    {
+                        "nodeType": "FunctionDefinition",
+                        "name": "",
+                        "kind": "constructor",
+                        "src": "70:20:0",
+                        "parameters": { "parameters": [] },
+                        "returnParameters": { "parameters": [] }
+                    }
+                ]
+            }]
+        });
+        assert_eq!(
+            FunctionRange::from_ast(&ast),
+            [
+                FunctionRange {
+                    name: "C::f".to_string(),
+                    index: 0,
+                    offset: 10,
+                    length: 50,
+                    parameters: vec!["uint256".to_string()],
+                    returns: vec!["bool".to_string()],
+                },
+                FunctionRange {
+                    name: "C::constructor".to_string(),
+                    index: 0,
+                    offset: 70,
+                    length: 20,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+}
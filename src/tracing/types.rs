@@ -2,13 +2,13 @@
 
 use crate::tracing::{config::TraceStyle, utils, utils::convert_memory};
 use alloc::{
-    collections::VecDeque,
+    collections::{BTreeMap, BTreeSet, VecDeque},
     format,
     string::{String, ToString},
     vec::Vec,
 };
 pub use alloy_primitives::Log;
-use alloy_primitives::{Address, Bytes, FixedBytes, LogData, U256};
+use alloy_primitives::{Address, Bytes, FixedBytes, LogData, B256, U256};
 use alloy_rpc_types_trace::{
     geth::{CallFrame, CallLogFrame, GethDefaultTracingOptions, StructLog},
     parity::{
@@ -87,6 +87,10 @@ pub struct CallTrace {
     pub data: Bytes,
     /// The return data, or the runtime bytecode of the created contract.
     pub output: Bytes,
+    /// The bytecode executed by the call, or the init code for contract creations.
+    ///
+    /// This is only recorded if steps are recorded and is `None` if no code was executed.
+    pub code: Option<Bytes>,
     /// The total gas cost of the call.
     pub gas_used: u64,
     /// The gas limit of the call.
@@ -180,6 +184,69 @@ impl CallLog {
     }
 }
 
+/// A change to the state of an account, made by a single call frame.
+///
+/// This is derived from the journal entries the frame produced, see
+/// [TracingInspectorConfig::record_call_state_changes](crate::tracing::TracingInspectorConfig::record_call_state_changes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CallStateChange {
+    /// The account that was changed.
+    pub address: Address,
+    /// What changed.
+    pub kind: CallStateChangeKind,
+    /// Whether the change was reverted, because this frame or one of its parents failed.
+    pub reverted: bool,
+}
+
+/// The kind of a [CallStateChange].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CallStateChangeKind {
+    /// Value was transferred from the account to `to`.
+    Transfer {
+        /// The receiver of the value.
+        to: Address,
+        /// The transferred value.
+        value: U256,
+    },
+    /// The balance of the account was changed, e.g. by a selfdestruct.
+    BalanceChange {
+        /// The balance before the change.
+        had_balance: U256,
+        /// The balance after the change.
+        balance: U256,
+    },
+    /// The nonce of the account was incremented.
+    NonceChange {
+        /// The nonce after the change.
+        nonce: u64,
+    },
+    /// Code was deployed to the account.
+    CodeChange {
+        /// The hash of the new code.
+        code_hash: B256,
+    },
+    /// The account was created.
+    Created,
+    /// A storage slot of the account was written.
+    Storage {
+        /// The storage slot.
+        key: U256,
+        /// The value before the write.
+        had_value: U256,
+        /// The value after the write.
+        value: U256,
+    },
+    /// The account was selfdestructed.
+    SelfDestruct {
+        /// The beneficiary of the account's balance.
+        target: Address,
+        /// The balance that was transferred to the beneficiary.
+        balance: U256,
+    },
+}
+
 /// A node in the arena
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -196,9 +263,33 @@ pub struct CallTraceNode {
     pub logs: Vec<CallLog>,
     /// Ordering of child calls and logs
     pub ordering: Vec<TraceMemberOrder>,
+    /// State changes made by this call, if enabled, in the order they were made.
+    ///
+    /// This does not include changes made by child calls.
+    pub state_changes: Vec<CallStateChange>,
+    /// Accounts and their storage slots accessed by this call, if enabled.
+    ///
+    /// This includes the caller and address of the call, storage slots read or written via
+    /// `SLOAD` and `SSTORE`, accounts inspected via `BALANCE` and `EXTCODE*`, targets of `CALL*`
+    /// and beneficiaries of `SELFDESTRUCT`. Precompiles and accesses made by child calls are not
+    /// included.
+    pub accessed_state: BTreeMap<Address, BTreeSet<B256>>,
+    /// The position of this node in its parent's [children](Self::children) in the arena it was
+    /// recorded in, if this node is part of a [pruned](super::CallTraceArena::prune) arena.
+    ///
+    /// Pruning removes siblings, so this is used instead of the position in the pruned parent to
+    /// keep the trace address of the original transaction.
+    pub original_position: Option<usize>,
 }
 
 impl CallTraceNode {
+    /// Returns the position of this node in its parent's children, as used for its trace
+    /// address.
+    pub(crate) fn position_in_parent(&self, parent: &Self) -> Option<usize> {
+        self.original_position
+            .or_else(|| parent.children.iter().position(|child| *child == self.idx))
+    }
+
     /// Returns the call context's execution address
     ///
     /// See `Inspector::call` impl of [TracingInspector](crate::tracing::TracingInspector)
@@ -265,6 +356,26 @@ impl CallTraceNode {
         (self.trace.data.len() >= 4).then(|| FixedBytes::from_slice(&self.trace.data[..4]))
     }
 
+    /// Returns the decoded label or checksummed address of the contract, and the decoded function
+    /// name or selector of the call, e.g. `("Token", "transfer")`.
+    pub(crate) fn contract_and_function(&self) -> (String, String) {
+        let trace = &self.trace;
+        let contract = match &trace.decoded.label {
+            Some(label) => label.clone(),
+            None => trace.address.to_checksum(None),
+        };
+        let function = if trace.kind.is_any_create() {
+            "new".into()
+        } else if let Some(call_data) = &trace.decoded.call_data {
+            call_data.signature.split('(').next().unwrap_or_default().into()
+        } else if let Some(selector) = self.selector() {
+            selector.to_string()
+        } else {
+            "fallback".into()
+        };
+        (contract, function)
+    }
+
     /// Returns `true` if this trace was a selfdestruct.
     ///
     /// See [`CallTrace::is_selfdestruct`] for more details.
@@ -415,6 +526,7 @@ impl CallTraceNode {
                     topics: Some(log.raw_log.topics().to_vec()),
                     data: Some(log.raw_log.data.clone()),
                     position: Some(log.position),
+                    index: None,
                 })
                 .collect();
         }
@@ -615,6 +727,8 @@ pub struct CallTraceStep {
     ///
     /// This will be `None` only if memory capture is disabled.
     pub memory: Option<RecordedMemory>,
+    /// Size of the memory in bytes before step execution
+    pub memory_size: usize,
     /// Returndata before step execution
     pub returndata: Bytes,
     /// Remaining gas before step execution
@@ -628,6 +742,14 @@ pub struct CallTraceStep {
     pub gas_cost: u64,
     /// Change of the contract state after step execution (effect of the SLOAD/SSTORE instructions)
     pub storage_change: Option<StorageChange>,
+    /// The memory range written by the step, if any.
+    ///
+    /// This is only recorded if
+    /// [TracingInspectorConfig::record_memory_deltas](crate::tracing::TracingInspectorConfig::record_memory_deltas)
+    /// is enabled. For `CALL`-like steps this is the return data copied into memory once the call
+    /// returned, which is not available if steps are streamed to a
+    /// [StepSink](crate::tracing::StepSink).
+    pub memory_change: Option<MemoryChange>,
     /// Final status of the step
     ///
     /// This is set after the step was executed.
@@ -643,14 +765,15 @@ pub struct CallTraceStep {
 impl CallTraceStep {
     /// Converts this step into a geth [StructLog]
     ///
-    /// This sets memory and stack capture based on the `opts` parameter.
-    pub(crate) fn convert_to_geth_struct_log(&self, opts: &GethDefaultTracingOptions) -> StructLog {
+    /// This sets memory and stack capture based on the `opts` parameter. Storage and return data
+    /// are not populated, since they depend on the surrounding trace.
+    pub fn convert_to_geth_struct_log(&self, opts: &GethDefaultTracingOptions) -> StructLog {
         let mut log = StructLog {
             depth: self.depth,
             error: self.as_error(),
             gas: self.gas_remaining,
             gas_cost: self.gas_cost,
-            op: self.op.to_string(),
+            op: self.op.to_string().into(),
             pc: self.pc as u64,
             refund_counter: (self.gas_refund_counter > 0).then_some(self.gas_refund_counter),
             // Filled, if not disabled manually
@@ -723,6 +846,16 @@ pub enum StorageChangeReason {
     SLOAD,
     /// SSTORE opcode
     SSTORE,
+    /// TSTORE opcode, a write to transient storage (EIP-1153)
+    TSTORE,
+}
+
+impl StorageChangeReason {
+    /// Returns true if this change targets transient storage.
+    #[inline]
+    pub const fn is_transient(&self) -> bool {
+        matches!(self, Self::TSTORE)
+    }
 }
 
 /// Represents a storage change during execution.
@@ -732,6 +865,9 @@ pub enum StorageChangeReason {
 ///
 /// It is used to track both storage change and warm load of a storage slot. For warm load in regard
 /// to EIP-2929 AccessList had_value will be None.
+///
+/// Writes to transient storage are tracked with [StorageChangeReason::TSTORE], see
+/// [JournalEntry::TransientStorageChange](revm::JournalEntry::TransientStorageChange).
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StorageChange {
@@ -745,6 +881,16 @@ pub struct StorageChange {
     pub reason: StorageChangeReason,
 }
 
+/// Represents a write to memory made by a single step.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryChange {
+    /// Offset of the first written byte
+    pub offset: usize,
+    /// The written bytes
+    pub data: Bytes,
+}
+
 /// Represents the memory captured during execution
 ///
 /// This is a wrapper around the [SharedMemory](revm::interpreter::SharedMemory) context memory.
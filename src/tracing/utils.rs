@@ -1,14 +1,14 @@
 //! Utility functions for revm related ops
-use crate::tracing::config::TraceStyle;
+use crate::tracing::{config::TraceStyle, RevertReason};
 use alloc::{
     format,
     string::{String, ToString},
     vec::Vec,
 };
 use alloy_primitives::{hex, Bytes};
-use alloy_sol_types::{ContractError, GenericRevertReason};
 use revm::{
-    interpreter::InstructionResult,
+    bytecode::opcode,
+    interpreter::{InstructionResult, Stack},
     primitives::{hardfork::SpecId, KECCAK_EMPTY},
     DatabaseRef,
 };
@@ -71,6 +71,31 @@ pub(crate) fn fmt_error_msg(res: InstructionResult, kind: TraceStyle) -> Option<
     Some(msg)
 }
 
+/// Converts a geth style error message, as returned by [`fmt_error_msg`] with
+/// [`TraceStyle::Geth`], to the error message parity would return.
+///
+/// Messages that have no parity equivalent are returned unchanged.
+///
+/// See also <https://github.com/ethereum/go-ethereum/blob/34d507215951fb3f4a5983b65e127577989a6db8/eth/tracers/native/call_flat.go#L39-L55>
+pub(crate) fn convert_error_to_parity(msg: &str) -> String {
+    let converted = match msg {
+        "contract creation code storage out of gas"
+        | "gas uint64 overflow"
+        | "max code size exceeded" => "Out of gas",
+        "execution reverted" => "Reverted",
+        "invalid jump destination" => "Bad jump destination",
+        "return data out of bounds" => "Out of bounds",
+        "stack limit reached 1024 (1023)" => "Out of stack",
+        "precompiled failed" | "invalid input length" => "Built-in failed",
+        // this also covers the more specific out of gas errors, e.g. `out of gas: out of memory`
+        msg if msg.starts_with("out of gas") => "Out of gas",
+        msg if msg.starts_with("invalid opcode") => "Bad instruction",
+        msg if msg.starts_with("stack underflow") => "Stack underflow",
+        msg => msg,
+    };
+    converted.to_string()
+}
+
 /// Formats memory data into a list of 32-byte hex-encoded chunks.
 ///
 /// See: <https://github.com/ethereum/go-ethereum/blob/366d2169fbc0e0f803b68c042b77b6b480836dbc/eth/tracers/logger/logger.go#L450-L452>
@@ -96,6 +121,26 @@ pub(crate) fn gas_used(spec: SpecId, spent: u64, refunded: u64) -> u64 {
     spent - (refunded).min(spent / refund_quotient)
 }
 
+/// Returns the `(offset, size)` of the memory range the given opcode writes to, based on the stack
+/// before the opcode is executed.
+///
+/// Returns `None` if the opcode does not write to memory or writes zero bytes. The return data of
+/// `CALL`-like opcodes is written once the call returns, so these are not included.
+#[inline]
+pub(crate) fn memory_write_range(op: u8, stack: &Stack) -> Option<(usize, usize)> {
+    let read = |n: usize| stack.peek(n).ok().and_then(|value| usize::try_from(value).ok());
+    let (offset, size) = match op {
+        opcode::MSTORE => (read(0)?, 32),
+        opcode::MSTORE8 => (read(0)?, 1),
+        opcode::MCOPY | opcode::CALLDATACOPY | opcode::CODECOPY | opcode::RETURNDATACOPY => {
+            (read(0)?, read(2)?)
+        }
+        opcode::EXTCODECOPY => (read(1)?, read(3)?),
+        _ => return None,
+    };
+    (size != 0).then_some((offset, size))
+}
+
 /// Loads the code for the given account from the account itself or the database
 ///
 /// Returns None if the code hash is the KECCAK_EMPTY hash
@@ -114,17 +159,15 @@ pub(crate) fn load_account_code<DB: DatabaseRef>(
 }
 
 /// Returns a non-empty revert reason if the output is a revert/error.
+///
+/// Like geth, this does not report custom errors.
 #[inline]
 pub(crate) fn maybe_revert_reason(output: &[u8]) -> Option<String> {
-    let reason = match GenericRevertReason::decode(output)? {
-        GenericRevertReason::ContractError(err) => {
-            match err {
-                // return the raw revert reason and don't use the revert's display message
-                ContractError::Revert(revert) => revert.reason,
-                err => err.to_string(),
-            }
-        }
-        GenericRevertReason::RawString(err) => err,
+    let reason = match RevertReason::decode(output)? {
+        // return the raw revert reason and don't use the revert's display message
+        RevertReason::Error(reason) | RevertReason::RawString(reason) => reason,
+        RevertReason::CustomError { .. } => return None,
+        reason @ RevertReason::Panic(_) => reason.to_string(),
     };
     if reason.is_empty() {
         None
@@ -153,4 +196,28 @@ mod tests {
         let reason = maybe_revert_reason(&err[..]).unwrap();
         assert_eq!(reason, "UniswapV2: INSUFFICIENT_INPUT_AMOUNT");
     }
+
+    #[test]
+    fn decode_raw_string_revert_reason() {
+        assert_eq!(maybe_revert_reason(b"fail").as_deref(), Some("fail"));
+        assert_eq!(maybe_revert_reason(&hex!("deadbeef")), None);
+    }
+
+    #[test]
+    fn convert_geth_errors_to_parity() {
+        for (status, parity) in [
+            (InstructionResult::Revert, "Reverted"),
+            (InstructionResult::OutOfGas, "Out of gas"),
+            (InstructionResult::MemoryOOG, "Out of gas"),
+            (InstructionResult::InvalidJump, "Bad jump destination"),
+            (InstructionResult::OpcodeNotFound, "Bad instruction"),
+            (InstructionResult::InvalidFEOpcode, "Bad instruction"),
+            (InstructionResult::PrecompileError, "Built-in failed"),
+            (InstructionResult::StackOverflow, "Out of stack"),
+        ] {
+            let geth = fmt_error_msg(status, TraceStyle::Geth).unwrap();
+            assert_eq!(convert_error_to_parity(&geth), parity, "{status:?}");
+        }
+        assert_eq!(convert_error_to_parity("unknown error"), "unknown error");
+    }
 }
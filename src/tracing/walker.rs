@@ -0,0 +1,166 @@
+//! Depth-first traversal of a [CallTraceArena].
+
+use super::{
+    types::{CallLog, CallTraceNode, CallTraceStep, TraceMemberOrder},
+    CallTraceArena,
+};
+use alloc::{vec, vec::Vec};
+
+/// A visitor for the items of a [CallTraceArena], see [CallTraceArena::walk].
+///
+/// All methods have empty default implementations, so implementors only need to override the
+/// callbacks they are interested in.
+pub trait CallTraceVisitor {
+    /// Invoked when a call node is entered, before any of its logs, steps and sub-calls.
+    fn enter_node(&mut self, node: &CallTraceNode) {
+        let _ = node;
+    }
+
+    /// Invoked when a call node is exited, after all of its logs, steps and sub-calls.
+    fn exit_node(&mut self, node: &CallTraceNode) {
+        let _ = node;
+    }
+
+    /// Invoked for every log emitted by `node`, in the order it was emitted.
+    fn visit_log(&mut self, node: &CallTraceNode, log: &CallLog) {
+        let _ = (node, log);
+    }
+
+    /// Invoked for every recorded step of `node`, in the order it was executed.
+    fn visit_step(&mut self, node: &CallTraceNode, step: &CallTraceStep) {
+        let _ = (node, step);
+    }
+}
+
+impl CallTraceArena {
+    /// Walks the arena depth-first, starting at the root node.
+    ///
+    /// Logs, steps and sub-calls of each node are visited in the order they occurred during
+    /// execution, as recorded in [CallTraceNode::ordering], so the items of a sub-call are visited
+    /// between the items of its parent that happened before and after the call.
+    ///
+    /// Nodes that are not attached to their parent, e.g. excluded precompile calls, are not
+    /// visited. Steps that are not stored in the arena, e.g. because they were handed to a
+    /// [StepSink](crate::tracing::StepSink), are skipped.
+    pub fn walk<V: CallTraceVisitor>(&self, visitor: &mut V) {
+        self.walk_node(0, visitor);
+    }
+
+    /// Walks the node with the given index and its sub-calls, see [Self::walk].
+    ///
+    /// # Panics
+    ///
+    /// If the index is out of bounds.
+    pub fn walk_node<V: CallTraceVisitor>(&self, idx: usize, visitor: &mut V) {
+        let node = &self.arena[idx];
+        visitor.enter_node(node);
+        for order in &node.ordering {
+            match *order {
+                TraceMemberOrder::Log(log_idx) => visitor.visit_log(node, &node.logs[log_idx]),
+                TraceMemberOrder::Call(child_idx) => {
+                    self.walk_node(node.children[child_idx], visitor)
+                }
+                TraceMemberOrder::Step(step_idx) => {
+                    if let Some(step) = node.trace.steps.get(step_idx) {
+                        visitor.visit_step(node, step)
+                    }
+                }
+            }
+        }
+        visitor.exit_node(node);
+    }
+
+    /// Returns an iterator over the nodes in depth-first pre-order, i.e. every node is yielded
+    /// before its children.
+    ///
+    /// Children are visited in call order. Nodes that are not attached to their parent, e.g.
+    /// excluded precompile calls, are not yielded.
+    pub fn iter_pre_order(&self) -> impl Iterator<Item = &CallTraceNode> + '_ {
+        let mut stack = vec![0];
+        core::iter::from_fn(move || {
+            let node = &self.arena[stack.pop()?];
+            stack.extend(node.children.iter().rev());
+            Some(node)
+        })
+    }
+
+    /// Returns an iterator over the nodes in depth-first post-order, i.e. every node is yielded
+    /// after its children.
+    ///
+    /// Children are visited in call order. Nodes that are not attached to their parent, e.g.
+    /// excluded precompile calls, are not yielded.
+    pub fn iter_post_order(&self) -> impl Iterator<Item = &CallTraceNode> + '_ {
+        // the node indices and the number of their children that were already visited
+        let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+        core::iter::from_fn(move || loop {
+            let (idx, visited) = stack.last_mut()?;
+            let node = &self.arena[*idx];
+            match node.children.get(*visited) {
+                Some(&child) => {
+                    *visited += 1;
+                    stack.push((child, 0));
+                }
+                None => {
+                    stack.pop();
+                    return Some(node);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, string::String};
+
+    /// Builds an arena with the call graph `0 -> (1 -> 2, 3)` with a step before each call and
+    /// a log at the end of each node.
+    fn arena() -> CallTraceArena {
+        CallTraceArena::from_depths([0, 1, 2, 1])
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl CallTraceVisitor for Recorder {
+        fn enter_node(&mut self, node: &CallTraceNode) {
+            self.0.push(format!("enter {}", node.idx));
+        }
+
+        fn exit_node(&mut self, node: &CallTraceNode) {
+            self.0.push(format!("exit {}", node.idx));
+        }
+
+        fn visit_log(&mut self, node: &CallTraceNode, _log: &CallLog) {
+            self.0.push(format!("log {}", node.idx));
+        }
+
+        fn visit_step(&mut self, node: &CallTraceNode, step: &CallTraceStep) {
+            self.0.push(format!("step {} {}", node.idx, step.pc));
+        }
+    }
+
+    #[test]
+    fn walk_arena() {
+        let arena = arena();
+        let mut recorder = Recorder::default();
+        arena.walk(&mut recorder);
+        assert_eq!(
+            recorder.0,
+            [
+                "enter 0", "step 0 0", "enter 1", "step 1 0", "enter 2", "log 2", "exit 2",
+                "log 1", "exit 1", "step 0 1", "enter 3", "log 3", "exit 3", "log 0", "exit 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn iter_arena() {
+        let arena = arena();
+        let pre_order = arena.iter_pre_order().map(|node| node.idx).collect::<Vec<_>>();
+        assert_eq!(pre_order, [0, 1, 2, 3]);
+        let post_order = arena.iter_post_order().map(|node| node.idx).collect::<Vec<_>>();
+        assert_eq!(post_order, [2, 1, 3, 0]);
+    }
+}
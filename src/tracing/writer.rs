@@ -1,18 +1,26 @@
 use super::{
+    diff::{CallChanges, CallDiff, CallTraceDiff, MatchedCall},
     types::{
         CallKind, CallLog, CallTrace, CallTraceNode, DecodedCallData, DecodedTraceStep,
         TraceMemberOrder,
     },
     CallTraceArena,
 };
-use alloc::{format, string::String, vec::Vec};
-use alloy_primitives::{address, hex, map::HashMap, Address, B256, U256};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use alloy_primitives::{address, hex, map::HashMap, Address, LogData, B256, U256};
 use anstyle::{AnsiColor, Color, Style};
 use colorchoice::ColorChoice;
+use core::fmt;
 use revm::interpreter::InstructionResult;
-use std::io::{self, Write};
+#[cfg(feature = "std")]
+use std::io;
 
-const CHEATCODE_ADDRESS: Address = address!("7109709ECfa91a80626fF3989D68f67F5b1DD12D");
+pub(crate) const CHEATCODE_ADDRESS: Address = address!("7109709ECfa91a80626fF3989D68f67F5b1DD12D");
 
 const PIPE: &str = "  │ ";
 const EDGE: &str = "  └─ ";
@@ -22,6 +30,9 @@ const RETURN: &str = "← ";
 
 const TRACE_KIND_STYLE: Style = AnsiColor::Yellow.on_default();
 const LOG_STYLE: Style = AnsiColor::Cyan.on_default();
+const ADDED_STYLE: Style = AnsiColor::Green.on_default();
+const REMOVED_STYLE: Style = AnsiColor::Red.on_default();
+const CHANGED_STYLE: Style = AnsiColor::Yellow.on_default();
 
 /// Configuration for a [`TraceWriter`].
 #[derive(Clone, Debug)]
@@ -31,6 +42,11 @@ pub struct TraceWriterConfig {
     color_cheatcodes: bool,
     write_bytecodes: bool,
     write_storage_changes: bool,
+    max_depth: Option<usize>,
+    min_gas: u64,
+    collapse_successful: bool,
+    revert_path_only: bool,
+    elide_repeated: bool,
 }
 
 impl Default for TraceWriterConfig {
@@ -47,10 +63,17 @@ impl TraceWriterConfig {
             color_cheatcodes: false,
             write_bytecodes: false,
             write_storage_changes: false,
+            max_depth: None,
+            min_gas: 0,
+            collapse_successful: false,
+            revert_path_only: false,
+            elide_repeated: false,
         }
     }
 
     /// Use colors in the output. Default: [`Auto`](ColorChoice::Auto).
+    ///
+    /// `Auto` enables colors if stdout is a terminal, and never without the `std` feature.
     pub fn color_choice(mut self, choice: ColorChoice) -> Self {
         self.use_colors = use_colors(choice);
         self
@@ -94,19 +117,229 @@ impl TraceWriterConfig {
     pub fn get_write_storage_changes(&self) -> bool {
         self.write_storage_changes
     }
+
+    /// Only write the contents of calls up to the given depth below the root call.
+    /// Default: unlimited.
+    ///
+    /// The contents of calls at the maximum depth are replaced by a summary line.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Returns the maximum depth of calls whose contents are written, if any.
+    pub fn get_max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Hide sub-calls that used less than the given amount of gas, including their sub-calls.
+    /// Default: 0.
+    ///
+    /// Consecutive hidden calls are replaced by a summary line.
+    pub fn min_gas(mut self, gas: u64) -> Self {
+        self.min_gas = gas;
+        self
+    }
+
+    /// Returns the minimum amount of gas used by written sub-calls.
+    pub fn get_min_gas(&self) -> u64 {
+        self.min_gas
+    }
+
+    /// Collapse sub-calls that succeeded together with all of their sub-calls. Default: false.
+    ///
+    /// The contents of collapsed calls are replaced by a summary line.
+    pub fn collapse_successful(mut self, yes: bool) -> Self {
+        self.collapse_successful = yes;
+        self
+    }
+
+    /// Returns `true` if successful subtrees are collapsed.
+    pub fn get_collapse_successful(&self) -> bool {
+        self.collapse_successful
+    }
+
+    /// Only write the calls on the path from the root call to the first call that failed.
+    /// Default: false.
+    ///
+    /// Consecutive calls that are not on the path are replaced by a summary line. All calls are
+    /// written if no call failed.
+    pub fn revert_path_only(mut self, yes: bool) -> Self {
+        self.revert_path_only = yes;
+        self
+    }
+
+    /// Returns `true` if only the path to the first failed call is written.
+    pub fn get_revert_path_only(&self) -> bool {
+        self.revert_path_only
+    }
+
+    /// Write consecutive identical sibling calls only once, with a `×N` marker. Default: false.
+    ///
+    /// Calls are identical if their kind, target, input, value, status and output are equal.
+    pub fn elide_repeated(mut self, yes: bool) -> Self {
+        self.elide_repeated = yes;
+        self
+    }
+
+    /// Returns `true` if repeated sibling calls are elided.
+    pub fn get_elide_repeated(&self) -> bool {
+        self.elide_repeated
+    }
+}
+
+/// Customizes how a [`TraceWriter`] labels and formats calls.
+///
+/// All methods have default implementations that produce the default output, so implementors
+/// only need to override the parts they want to change.
+pub trait TraceDecorator {
+    /// Returns the label that is written instead of the address of the called or created contract.
+    ///
+    /// Defaults to the [decoded label](crate::tracing::types::DecodedCallTrace::label).
+    fn label(&self, trace: &CallTrace) -> Option<String> {
+        trace.decoded.label.clone()
+    }
+
+    /// Returns the function name and the formatted arguments of a call.
+    ///
+    /// Defaults to the [decoded call data](crate::tracing::types::DecodedCallTrace::call_data),
+    /// otherwise the hex encoded selector and arguments, or `fallback` and the hex encoded call
+    /// data if it is shorter than a selector.
+    fn function_and_inputs(&self, trace: &CallTrace) -> (String, String) {
+        function_and_inputs(trace)
+    }
+
+    /// Formats the value transferred by a call. Defaults to the value in wei.
+    fn fmt_value(&self, value: U256) -> String {
+        value.to_string()
+    }
+
+    /// Returns the name of the call kind, which is written after the arguments in brackets.
+    ///
+    /// Defaults to the lowercase name for all kinds but [`CallKind::Call`], e.g. `staticcall`.
+    /// Not used for creations.
+    fn call_kind(&self, kind: CallKind) -> Option<String> {
+        match kind {
+            CallKind::Call | CallKind::Create | CallKind::Create2 => None,
+            kind => Some(kind.to_str().to_lowercase()),
+        }
+    }
+
+    /// Formats a storage slot or value of a storage change.
+    ///
+    /// Defaults to a decimal number if it is short, otherwise a hex encoded word.
+    fn fmt_storage_word(&self, word: U256) -> String {
+        num_or_hex(word)
+    }
+
+    /// Returns an annotation that is written at the end of the header of the node with the given
+    /// index, e.g. the share of the total gas used. Defaults to none.
+    fn annotation(&self, nodes: &[CallTraceNode], idx: usize) -> Option<String> {
+        let _ = (nodes, idx);
+        None
+    }
+}
+
+/// The [`TraceDecorator`] that produces the default output of a [`TraceWriter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DefaultTraceDecorator;
+
+impl TraceDecorator for DefaultTraceDecorator {}
+
+/// The output a [`TraceWriter`] writes to.
+///
+/// This is implemented for all [`std::io::Write`] writers when the `std` feature is enabled, and
+/// for [`core::fmt::Write`] writers, such as [`String`], wrapped in a [`FmtWriter`].
+pub trait TraceOutput {
+    /// The error returned when writing fails.
+    type Error;
+
+    /// Writes a string slice.
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error>;
+
+    /// Writes formatted arguments, used by the [`write!`] and [`writeln!`] macros.
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<(), Self::Error>;
+
+    /// Flushes buffered output, invoked after every written arena or diff.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> TraceOutput for W {
+    type Error = io::Error;
+
+    #[inline]
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        self.write_all(s.as_bytes())
+    }
+
+    #[inline]
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> io::Result<()> {
+        io::Write::write_fmt(self, args)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(self)
+    }
+}
+
+/// Adapts a [`core::fmt::Write`] writer, such as [`String`], to a [`TraceOutput`].
+///
+/// This does not require the `std` feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FmtWriter<W>(pub W);
+
+impl<W> FmtWriter<W> {
+    /// Wraps the given writer.
+    #[inline]
+    pub const fn new(writer: W) -> Self {
+        Self(writer)
+    }
+
+    /// Consumes the `FmtWriter` and returns the inner writer.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+impl<W: fmt::Write> TraceOutput for FmtWriter<W> {
+    type Error = fmt::Error;
+
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s)
+    }
+
+    #[inline]
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        self.0.write_fmt(args)
+    }
 }
 
-/// Formats [call traces](CallTraceArena) to an [`Write`] writer.
+/// Formats [call traces](CallTraceArena) to a [`TraceOutput`], which is either an
+/// [`std::io::Write`] writer or a [`core::fmt::Write`] writer wrapped in a [`FmtWriter`].
 ///
 /// Will never write invalid UTF-8.
+///
+/// How calls are labelled and formatted can be customized with a [`TraceDecorator`], see
+/// [`TraceWriter::with_decorator`].
 #[derive(Clone, Debug)]
-pub struct TraceWriter<W> {
+pub struct TraceWriter<W, D = DefaultTraceDecorator> {
     writer: W,
     indentation_level: u16,
     config: TraceWriterConfig,
+    decorator: D,
+    /// Whether each node is on the path to the first failed call, empty if not filtered.
+    revert_path: Vec<bool>,
+    /// The consecutive items that were elided and not summarized yet.
+    elided: Option<Elided>,
 }
 
-impl<W: Write> TraceWriter<W> {
+impl<W: TraceOutput> TraceWriter<W> {
     /// Create a new `TraceWriter` with the given writer.
     #[inline]
     pub fn new(writer: W) -> Self {
@@ -115,7 +348,28 @@ impl<W: Write> TraceWriter<W> {
 
     /// Create a new `TraceWriter` with the given writer and configuration.
     pub fn with_config(writer: W, config: TraceWriterConfig) -> Self {
-        Self { writer, indentation_level: 0, config }
+        Self {
+            writer,
+            indentation_level: 0,
+            config,
+            decorator: DefaultTraceDecorator,
+            revert_path: Vec::new(),
+            elided: None,
+        }
+    }
+}
+
+impl<W: TraceOutput, D: TraceDecorator> TraceWriter<W, D> {
+    /// Sets the decorator that labels and formats calls.
+    pub fn with_decorator<T: TraceDecorator>(self, decorator: T) -> TraceWriter<W, T> {
+        let Self { writer, indentation_level, config, decorator: _, revert_path, elided } = self;
+        TraceWriter { writer, indentation_level, config, decorator, revert_path, elided }
+    }
+
+    /// Returns a reference to the decorator.
+    #[inline]
+    pub const fn decorator(&self) -> &D {
+        &self.decorator
     }
 
     /// Sets the color choice.
@@ -172,11 +426,161 @@ impl<W: Write> TraceWriter<W> {
     }
 
     /// Writes a call trace arena to the writer.
-    pub fn write_arena(&mut self, arena: &CallTraceArena) -> io::Result<()> {
-        self.write_node(arena.nodes(), 0)?;
+    pub fn write_arena(&mut self, arena: &CallTraceArena) -> Result<(), W::Error> {
+        self.revert_path =
+            if self.config.revert_path_only { revert_path(arena.nodes()) } else { Vec::new() };
+        self.write_node(arena.nodes(), 0, 1)?;
+        self.writer.flush()
+    }
+
+    /// Writes the structural diff of two call trace arenas to the writer.
+    ///
+    /// Every call is prefixed with a marker: `+` for calls that were added, `-` for calls that
+    /// were removed, `~` for calls that changed themselves or in any of their sub-calls, and `=`
+    /// for unchanged calls. Added, removed and unchanged calls are written as a single line,
+    /// changed calls are expanded with their changes and sub-calls.
+    pub fn write_diff(&mut self, diff: &CallTraceDiff<'_>) -> Result<(), W::Error> {
+        self.write_call_diff(diff, diff.root())?;
         self.writer.flush()
     }
 
+    /// Writes a single call diff and its children to the writer.
+    fn write_call_diff(
+        &mut self,
+        diff: &CallTraceDiff<'_>,
+        call: &CallDiff,
+    ) -> Result<(), W::Error> {
+        let (left, right) = (diff.left().nodes(), diff.right().nodes());
+        let unchanged = call.is_unchanged();
+        let call = match call {
+            CallDiff::Added(idx) => return self.write_diff_line(&right[*idx].trace, '+'),
+            CallDiff::Removed(idx) => return self.write_diff_line(&left[*idx].trace, '-'),
+            CallDiff::Matched(call) if unchanged => {
+                return self.write_diff_line(&right[call.right].trace, '=');
+            }
+            CallDiff::Matched(call) => call,
+        };
+        let MatchedCall { left: left_idx, right: right_idx, changes, children } = &**call;
+        let trace = &right[*right_idx].trace;
+
+        // Write header.
+        self.write_branch()?;
+        self.write_diff_marker('~')?;
+        self.write_trace_header(trace)?;
+        if let Some((before, after)) = changes.gas_used {
+            write!(self.writer, " ({:+} gas)", after as i128 - before as i128)?;
+        }
+        self.writer.write_str("\n")?;
+
+        // Write changes and subcalls.
+        self.indentation_level += 1;
+        self.write_call_changes(changes)?;
+        for child in children {
+            self.write_call_diff(diff, child)?;
+        }
+
+        // Write return data.
+        self.write_edge()?;
+        self.write_trace_footer(trace)?;
+        if changes.status.is_some() {
+            let status = left[*left_idx].trace.status.unwrap_or(InstructionResult::Stop);
+            write!(self.writer, " (was {status:?})")?;
+        }
+        self.writer.write_str("\n")?;
+
+        self.indentation_level -= 1;
+
+        Ok(())
+    }
+
+    /// Writes the changes of a matched call, one per line.
+    fn write_call_changes(&mut self, changes: &CallChanges) -> Result<(), W::Error> {
+        let CallChanges {
+            address,
+            input,
+            value,
+            output,
+            status: _,
+            gas_used: _,
+            logs,
+            first_diverging_step,
+        } = changes;
+
+        if let Some((before, after)) = address {
+            self.write_change("address", before, after)?;
+        }
+        if let Some((before, after)) = input {
+            self.write_change("input", before, after)?;
+        }
+        if let Some((before, after)) = value {
+            self.write_change("value", before, after)?;
+        }
+        if let Some((before, after)) = output {
+            self.write_change("output", before, after)?;
+        }
+        for log in logs {
+            self.write_change(
+                &format!("log {}", log.index),
+                fmt_diff_log(log.left.as_ref()),
+                fmt_diff_log(log.right.as_ref()),
+            )?;
+        }
+        if let Some(step) = first_diverging_step {
+            let fmt_step = |step: Option<(usize, revm::bytecode::OpCode)>| match step {
+                Some((pc, op)) => format!("{op} @ {pc}"),
+                None => "<none>".to_string(),
+            };
+            self.write_change(
+                &format!("step {}", step.index),
+                fmt_step(step.left),
+                fmt_step(step.right),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn write_change(
+        &mut self,
+        name: &str,
+        before: impl core::fmt::Display,
+        after: impl core::fmt::Display,
+    ) -> Result<(), W::Error> {
+        self.write_branch()?;
+        self.write_diff_marker('~')?;
+        writeln!(
+            self.writer,
+            "{name}: {removed}{before}{removed:#} → {added}{after}{added:#}",
+            removed = self.diff_style('-'),
+            added = self.diff_style('+'),
+        )
+    }
+
+    /// Writes a single line diff of a call that is added, removed or unchanged.
+    fn write_diff_line(&mut self, trace: &CallTrace, marker: char) -> Result<(), W::Error> {
+        self.write_branch()?;
+        self.write_diff_marker(marker)?;
+        self.write_trace_header(trace)?;
+        self.writer.write_str("\n")
+    }
+
+    fn write_diff_marker(&mut self, marker: char) -> Result<(), W::Error> {
+        let style = self.diff_style(marker);
+        write!(self.writer, "{style}{marker}{style:#} ")
+    }
+
+    fn diff_style(&self, marker: char) -> Style {
+        if !self.config.use_colors {
+            return Style::default();
+        }
+        match marker {
+            '+' => ADDED_STYLE,
+            '-' => REMOVED_STYLE,
+            '~' => CHANGED_STYLE,
+            _ => Style::default(),
+        }
+    }
+
     /// Writes a single item of a single node to the writer. Returns the index of the next item to
     /// be written.
     ///
@@ -187,21 +591,104 @@ impl<W: Write> TraceWriter<W> {
         nodes: &[CallTraceNode],
         node_idx: usize,
         item_idx: usize,
-    ) -> io::Result<usize> {
+    ) -> Result<usize, W::Error> {
         let node = &nodes[node_idx];
         match &node.ordering[item_idx] {
             TraceMemberOrder::Log(index) => {
+                self.write_elided()?;
                 self.write_log(&node.logs[*index])?;
                 Ok(item_idx + 1)
             }
             TraceMemberOrder::Call(index) => {
-                self.write_node(nodes, node.children[*index])?;
-                Ok(item_idx + 1)
+                let child = node.children[*index];
+                if let Some(reason) = self.hidden_reason(nodes, child) {
+                    let (calls, logs) = count_items(nodes, child);
+                    self.elide(calls, logs, reason)?;
+                    return Ok(item_idx + 1);
+                }
+                let (repeats, next_item_idx) = self.repeated_calls(nodes, node_idx, item_idx);
+                self.write_elided()?;
+                self.write_node(nodes, child, repeats)?;
+                Ok(next_item_idx)
             }
             TraceMemberOrder::Step(index) => self.write_step(nodes, node_idx, item_idx, *index),
         }
     }
 
+    /// Returns the reason why the given sub-call is hidden, if it is.
+    fn hidden_reason(&self, nodes: &[CallTraceNode], idx: usize) -> Option<ElisionReason> {
+        if !self.revert_path.is_empty() && !self.revert_path[idx] {
+            Some(ElisionReason::RevertPath)
+        } else if nodes[idx].trace.gas_used < self.config.min_gas {
+            Some(ElisionReason::MinGas(self.config.min_gas))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the reason why the contents of the given call are collapsed, if they are.
+    fn collapsed_reason(&self, nodes: &[CallTraceNode], idx: usize) -> Option<ElisionReason> {
+        let depth = nodes[idx].trace.depth.saturating_sub(nodes[0].trace.depth);
+        if self.config.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            Some(ElisionReason::MaxDepth)
+        } else if self.config.collapse_successful && idx != 0 && subtree_succeeded(nodes, idx) {
+            Some(ElisionReason::Successful)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of consecutive identical calls starting at the given item, and the index
+    /// of the item after them.
+    ///
+    /// Steps that are not written are skipped, any other item ends the repetition.
+    fn repeated_calls(
+        &self,
+        nodes: &[CallTraceNode],
+        node_idx: usize,
+        item_idx: usize,
+    ) -> (usize, usize) {
+        let node = &nodes[node_idx];
+        if !self.config.elide_repeated {
+            return (1, item_idx + 1);
+        }
+        let TraceMemberOrder::Call(first) = node.ordering[item_idx] else { unreachable!() };
+        let first = &nodes[node.children[first]].trace;
+
+        let (mut repeats, mut next_item_idx) = (1, item_idx + 1);
+        for (idx, item) in node.ordering.iter().enumerate().skip(item_idx + 1) {
+            match *item {
+                TraceMemberOrder::Step(step) if node.trace.steps[step].decoded.is_none() => {}
+                TraceMemberOrder::Call(child)
+                    if is_repeated_call(first, &nodes[node.children[child]].trace) =>
+                {
+                    repeats += 1;
+                    next_item_idx = idx + 1;
+                }
+                _ => break,
+            }
+        }
+        (repeats, next_item_idx)
+    }
+
+    /// Adds the given items to the elided items, which are summarized before the next written item.
+    fn elide(&mut self, calls: usize, logs: usize, reason: ElisionReason) -> Result<(), W::Error> {
+        if self.elided.is_some_and(|elided| elided.reason != reason) {
+            self.write_elided()?;
+        }
+        let elided = self.elided.get_or_insert(Elided { calls: 0, logs: 0, reason });
+        elided.calls += calls;
+        elided.logs += logs;
+        Ok(())
+    }
+
+    /// Writes the summary of the elided items, if any.
+    fn write_elided(&mut self) -> Result<(), W::Error> {
+        let Some(elided) = self.elided.take() else { return Ok(()) };
+        self.write_branch()?;
+        writeln!(self.writer, "{elided}")
+    }
+
     /// Writes items of a single node to the writer, starting from the given index, and until the
     /// given predicate is false.
     ///
@@ -212,33 +699,54 @@ impl<W: Write> TraceWriter<W> {
         node_idx: usize,
         first_item_idx: usize,
         f: impl Fn(usize) -> bool,
-    ) -> io::Result<usize> {
+    ) -> Result<usize, W::Error> {
         let mut item_idx = first_item_idx;
         while !f(item_idx) {
             item_idx = self.write_item(nodes, node_idx, item_idx)?;
         }
+        self.write_elided()?;
         Ok(item_idx)
     }
 
     /// Writes all items of a single node to the writer.
-    fn write_items(&mut self, nodes: &[CallTraceNode], node_idx: usize) -> io::Result<()> {
+    fn write_items(&mut self, nodes: &[CallTraceNode], node_idx: usize) -> Result<(), W::Error> {
         let items_cnt = nodes[node_idx].ordering.len();
         self.write_items_until(nodes, node_idx, 0, |idx| idx == items_cnt)?;
         Ok(())
     }
 
-    /// Writes a single node and its children to the writer.
-    fn write_node(&mut self, nodes: &[CallTraceNode], idx: usize) -> io::Result<()> {
+    /// Writes a single node and its children to the writer, `repeats` is the number of identical
+    /// calls the node stands for.
+    fn write_node(
+        &mut self,
+        nodes: &[CallTraceNode],
+        idx: usize,
+        repeats: usize,
+    ) -> Result<(), W::Error> {
         let node = &nodes[idx];
 
         // Write header.
         self.write_branch()?;
         self.write_trace_header(&node.trace)?;
-        self.writer.write_all(b"\n")?;
+        if repeats > 1 {
+            write!(self.writer, " ×{repeats}")?;
+        }
+        if let Some(annotation) = self.decorator.annotation(nodes, idx) {
+            write!(self.writer, " {annotation}")?;
+        }
+        self.writer.write_str("\n")?;
 
         // Write logs and subcalls.
         self.indentation_level += 1;
-        self.write_items(nodes, idx)?;
+        if let Some(reason) = self.collapsed_reason(nodes, idx) {
+            let (calls, logs) = count_items(nodes, idx);
+            if calls > 1 || logs > 0 {
+                self.elide(calls - 1, logs, reason)?;
+                self.write_elided()?;
+            }
+        } else {
+            self.write_items(nodes, idx)?;
+        }
 
         if self.config.write_storage_changes {
             self.write_storage_changes(node)?;
@@ -247,7 +755,7 @@ impl<W: Write> TraceWriter<W> {
         // Write return data.
         self.write_edge()?;
         self.write_trace_footer(&node.trace)?;
-        self.writer.write_all(b"\n")?;
+        self.writer.write_str("\n")?;
 
         self.indentation_level -= 1;
 
@@ -255,67 +763,47 @@ impl<W: Write> TraceWriter<W> {
     }
 
     /// Writes the header of a call trace.
-    fn write_trace_header(&mut self, trace: &CallTrace) -> io::Result<()> {
+    fn write_trace_header(&mut self, trace: &CallTrace) -> Result<(), W::Error> {
         write!(self.writer, "[{}] ", trace.gas_used)?;
 
         let trace_kind_style = self.trace_kind_style();
         let address = trace.address.to_checksum_buffer(None);
+        let label = self.decorator.label(trace);
 
         if trace.kind.is_any_create() {
             write!(
                 self.writer,
                 "{trace_kind_style}{CALL}new{trace_kind_style:#} {label}@{address}",
-                label = trace.decoded.label.as_deref().unwrap_or("<unknown>")
+                label = label.as_deref().unwrap_or("<unknown>")
             )?;
             if self.config.write_bytecodes {
                 write!(self.writer, "({})", trace.data)?;
             }
         } else {
-            let (func_name, inputs) = match &trace.decoded.call_data {
-                Some(DecodedCallData { signature, args }) => {
-                    let name = signature.split('(').next().unwrap();
-                    (name.to_string(), args.join(", "))
-                }
-                None => {
-                    if trace.data.len() < 4 {
-                        ("fallback".to_string(), hex::encode(&trace.data))
-                    } else {
-                        let (selector, data) = trace.data.split_at(4);
-                        (hex::encode(selector), hex::encode(data))
-                    }
-                }
-            };
+            let (func_name, inputs) = self.decorator.function_and_inputs(trace);
 
             write!(
                 self.writer,
                 "{style}{addr}{style:#}::{style}{func_name}{style:#}",
                 style = self.trace_style(trace),
-                addr = trace.decoded.label.as_deref().unwrap_or(address.as_str()),
+                addr = label.as_deref().unwrap_or(address.as_str()),
             )?;
 
             if !trace.value.is_zero() {
-                write!(self.writer, "{{value: {}}}", trace.value)?;
+                write!(self.writer, "{{value: {}}}", self.decorator.fmt_value(trace.value))?;
             }
 
             write!(self.writer, "({inputs})")?;
 
-            let action = match trace.kind {
-                CallKind::Call => None,
-                CallKind::StaticCall => Some(" [staticcall]"),
-                CallKind::CallCode => Some(" [callcode]"),
-                CallKind::DelegateCall => Some(" [delegatecall]"),
-                CallKind::AuthCall => Some(" [authcall]"),
-                CallKind::Create | CallKind::Create2 => unreachable!(),
-            };
-            if let Some(action) = action {
-                write!(self.writer, "{trace_kind_style}{action}{trace_kind_style:#}")?;
+            if let Some(kind) = self.decorator.call_kind(trace.kind) {
+                write!(self.writer, "{trace_kind_style} [{kind}]{trace_kind_style:#}")?;
             }
         }
 
         Ok(())
     }
 
-    fn write_log(&mut self, log: &CallLog) -> io::Result<()> {
+    fn write_log(&mut self, log: &CallLog) -> Result<(), W::Error> {
         let log_style = self.log_style();
         self.write_branch()?;
 
@@ -324,7 +812,7 @@ impl<W: Write> TraceWriter<W> {
             if let Some(params) = &log.decoded.params {
                 for (i, (param_name, value)) in params.iter().enumerate() {
                     if i > 0 {
-                        self.writer.write_all(b", ")?;
+                        self.writer.write_str(", ")?;
                     }
                     write!(self.writer, "{param_name}: {value}")?;
                 }
@@ -333,7 +821,7 @@ impl<W: Write> TraceWriter<W> {
         } else {
             for (i, topic) in log.raw_log.topics().iter().enumerate() {
                 if i == 0 {
-                    self.writer.write_all(b" emit topic 0")?;
+                    self.writer.write_str(" emit topic 0")?;
                 } else {
                     self.write_pipes()?;
                     write!(self.writer, "       topic {i}")?;
@@ -362,7 +850,7 @@ impl<W: Write> TraceWriter<W> {
         node_idx: usize,
         item_idx: usize,
         step_idx: usize,
-    ) -> io::Result<usize> {
+    ) -> Result<usize, W::Error> {
         let node = &nodes[node_idx];
         let step = &node.trace.steps[step_idx];
 
@@ -370,6 +858,7 @@ impl<W: Write> TraceWriter<W> {
             // We only write explicitly decoded steps to avoid bloating the output.
             return Ok(item_idx + 1);
         };
+        self.write_elided()?;
 
         match decoded {
             DecodedTraceStep::InternalCall(call, end_idx) => {
@@ -414,7 +903,7 @@ impl<W: Write> TraceWriter<W> {
     }
 
     /// Writes the footer of a call trace.
-    fn write_trace_footer(&mut self, trace: &CallTrace) -> io::Result<()> {
+    fn write_trace_footer(&mut self, trace: &CallTrace) -> Result<(), W::Error> {
         write!(
             self.writer,
             "{style}{RETURN}[{status:?}]{style:#}",
@@ -422,48 +911,39 @@ impl<W: Write> TraceWriter<W> {
             status = trace.status.unwrap_or(InstructionResult::Stop),
         )?;
 
-        if let Some(decoded) = &trace.decoded.return_data {
-            write!(self.writer, " ")?;
-            return self.writer.write_all(decoded.as_bytes());
-        }
-
-        if !self.config.write_bytecodes
-            && (trace.kind.is_any_create() && trace.status.is_none_or(|status| status.is_ok()))
-        {
-            write!(self.writer, " {} bytes of code", trace.output.len())?;
-        } else if !trace.output.is_empty() {
-            write!(self.writer, " {}", trace.output)?;
+        if let Some(output) = fmt_output(trace, self.config.write_bytecodes) {
+            write!(self.writer, " {output}")?;
         }
 
         Ok(())
     }
 
-    fn write_indentation(&mut self) -> io::Result<()> {
-        self.writer.write_all(b"  ")?;
+    fn write_indentation(&mut self) -> Result<(), W::Error> {
+        self.writer.write_str("  ")?;
         for _ in 1..self.indentation_level {
-            self.writer.write_all(PIPE.as_bytes())?;
+            self.writer.write_str(PIPE)?;
         }
         Ok(())
     }
 
     #[doc(alias = "left_prefix")]
-    fn write_branch(&mut self) -> io::Result<()> {
+    fn write_branch(&mut self) -> Result<(), W::Error> {
         self.write_indentation()?;
         if self.indentation_level != 0 {
-            self.writer.write_all(BRANCH.as_bytes())?;
+            self.writer.write_str(BRANCH)?;
         }
         Ok(())
     }
 
     #[doc(alias = "right_prefix")]
-    fn write_pipes(&mut self) -> io::Result<()> {
+    fn write_pipes(&mut self) -> Result<(), W::Error> {
         self.write_indentation()?;
-        self.writer.write_all(PIPE.as_bytes())
+        self.writer.write_str(PIPE)
     }
 
-    fn write_edge(&mut self) -> io::Result<()> {
+    fn write_edge(&mut self) -> Result<(), W::Error> {
         self.write_indentation()?;
-        self.writer.write_all(EDGE.as_bytes())
+        self.writer.write_str(EDGE)
     }
 
     fn trace_style(&self, trace: &CallTrace) -> Style {
@@ -494,41 +974,30 @@ impl<W: Write> TraceWriter<W> {
         LOG_STYLE
     }
 
-    fn write_storage_changes(&mut self, node: &CallTraceNode) -> io::Result<()> {
-        let mut changes_map = HashMap::new();
-
-        // For each call trace, compact the results so we do not write the intermediate storage
-        // writes
-        for step in &node.trace.steps {
-            if let Some(change) = &step.storage_change {
-                let (_first, last) = changes_map.entry(&change.key).or_insert((change, change));
-                *last = change;
-            }
-        }
-
-        let changes = changes_map
-            .iter()
-            .filter_map(|(&&key, &(first, last))| {
-                let value_before = first.had_value.unwrap_or_default();
-                let value_after = last.value;
-                if value_before == value_after {
-                    return None;
-                }
-                Some((key, value_before, value_after))
-            })
-            .collect::<Vec<_>>();
+    fn write_storage_changes(&mut self, node: &CallTraceNode) -> Result<(), W::Error> {
+        self.write_storage_changes_section(node, "storage changes", false)?;
+        self.write_storage_changes_section(node, "transient storage changes", true)
+    }
 
+    /// Writes the compacted persistent or transient storage changes of the node, if any.
+    fn write_storage_changes_section(
+        &mut self,
+        node: &CallTraceNode,
+        title: &str,
+        transient: bool,
+    ) -> Result<(), W::Error> {
+        let changes = storage_changes(node, transient);
         if !changes.is_empty() {
             self.write_branch()?;
-            writeln!(self.writer, " storage changes:")?;
+            writeln!(self.writer, " {title}:")?;
             for (key, value_before, value_after) in changes {
                 self.write_pipes()?;
                 writeln!(
                     self.writer,
                     "  @ {key}: {value_before} → {value_after}",
-                    key = num_or_hex(key),
-                    value_before = num_or_hex(value_before),
-                    value_after = num_or_hex(value_after),
+                    key = self.decorator.fmt_storage_word(key),
+                    value_before = self.decorator.fmt_storage_word(value_before),
+                    value_after = self.decorator.fmt_storage_word(value_after),
                 )?;
             }
         }
@@ -538,17 +1007,196 @@ impl<W: Write> TraceWriter<W> {
 }
 
 fn use_colors(choice: ColorChoice) -> bool {
-    use io::IsTerminal;
     match choice {
-        ColorChoice::Auto => io::stdout().is_terminal(),
+        #[cfg(feature = "std")]
+        ColorChoice::Auto => io::IsTerminal::is_terminal(&io::stdout()),
+        // there is no terminal to detect without `std`
+        #[cfg(not(feature = "std"))]
+        ColorChoice::Auto => false,
         ColorChoice::AlwaysAnsi | ColorChoice::Always => true,
         ColorChoice::Never => false,
     }
 }
 
+/// Items of a call that are not written, see [TraceWriterConfig].
+#[derive(Clone, Copy, Debug)]
+struct Elided {
+    calls: usize,
+    logs: usize,
+    reason: ElisionReason,
+}
+
+impl fmt::Display for Elided {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        f.write_str("… ")?;
+        if self.calls > 0 {
+            write!(f, "{} call{}", self.calls, plural(self.calls))?;
+        }
+        if self.logs > 0 {
+            if self.calls > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} log{}", self.logs, plural(self.logs))?;
+        }
+        write!(f, " hidden ({})", self.reason)
+    }
+}
+
+/// Why items are not written, see [Elided].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ElisionReason {
+    MaxDepth,
+    MinGas(u64),
+    Successful,
+    RevertPath,
+}
+
+impl fmt::Display for ElisionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MaxDepth => f.write_str("max depth"),
+            Self::MinGas(gas) => write!(f, "below {gas} gas"),
+            Self::Successful => f.write_str("successful"),
+            Self::RevertPath => f.write_str("not on revert path"),
+        }
+    }
+}
+
+/// Returns the number of calls, including the given one, and logs in the subtree of the node.
+fn count_items(nodes: &[CallTraceNode], idx: usize) -> (usize, usize) {
+    let mut counts = (0, 0);
+    let mut stack = vec![idx];
+    while let Some(idx) = stack.pop() {
+        counts.0 += 1;
+        counts.1 += nodes[idx].logs.len();
+        stack.extend_from_slice(&nodes[idx].children);
+    }
+    counts
+}
+
+/// Returns `true` if the node and all of its sub-calls succeeded.
+fn subtree_succeeded(nodes: &[CallTraceNode], idx: usize) -> bool {
+    nodes[idx].trace.success
+        && nodes[idx].children.iter().all(|child| subtree_succeeded(nodes, *child))
+}
+
+/// Returns `true` if the calls have the same kind, target, input, value, status and output.
+fn is_repeated_call(a: &CallTrace, b: &CallTrace) -> bool {
+    a.kind == b.kind
+        && a.address == b.address
+        && a.data == b.data
+        && a.value == b.value
+        && a.status == b.status
+        && a.output == b.output
+}
+
+/// Returns whether each node is on the path from the root to the first call that failed, or an
+/// empty list if no call failed.
+///
+/// The first call that failed is the first call that finished with a failure, i.e. the first
+/// failed call in post-order.
+fn revert_path(nodes: &[CallTraceNode]) -> Vec<bool> {
+    fn first_failed(nodes: &[CallTraceNode], idx: usize) -> Option<usize> {
+        let node = &nodes[idx];
+        node.children
+            .iter()
+            .find_map(|child| first_failed(nodes, *child))
+            .or_else(|| (!node.trace.success).then_some(idx))
+    }
+
+    let Some(mut idx) = first_failed(nodes, 0) else { return Vec::new() };
+    let mut path = vec![false; nodes.len()];
+    loop {
+        path[idx] = true;
+        match nodes[idx].parent {
+            Some(parent) => idx = parent,
+            None => return path,
+        }
+    }
+}
+
+/// Formats a log of a [CallTraceDiff].
+fn fmt_diff_log(log: Option<&LogData>) -> String {
+    let Some(log) = log else { return "<none>".to_string() };
+    let topics = log.topics().iter().map(|topic| topic.to_string()).collect::<Vec<_>>();
+    format!("[{}] {}", topics.join(", "), log.data)
+}
+
+/// Returns the function name and the formatted inputs of a call.
+pub(crate) fn function_and_inputs(trace: &CallTrace) -> (String, String) {
+    match &trace.decoded.call_data {
+        Some(DecodedCallData { signature, args }) => {
+            let name = signature.split('(').next().unwrap();
+            (name.to_string(), args.join(", "))
+        }
+        None => {
+            if trace.data.len() < 4 {
+                ("fallback".to_string(), hex::encode(&trace.data))
+            } else {
+                let (selector, data) = trace.data.split_at(4);
+                (hex::encode(selector), hex::encode(data))
+            }
+        }
+    }
+}
+
+/// Formats the output of a call: the decoded return data, the revert reason, the size of the
+/// deployed code unless `write_bytecodes` is set, or the raw output.
+pub(crate) fn fmt_output(trace: &CallTrace, write_bytecodes: bool) -> Option<String> {
+    if let Some(decoded) = &trace.decoded.return_data {
+        return Some(decoded.clone());
+    }
+
+    if let Some(reason) = trace.revert_reason() {
+        return Some(reason.to_string());
+    }
+
+    if !write_bytecodes
+        && (trace.kind.is_any_create() && trace.status.is_none_or(|status| status.is_ok()))
+    {
+        Some(format!("{} bytes of code", trace.output.len()))
+    } else {
+        (!trace.output.is_empty()).then(|| trace.output.to_string())
+    }
+}
+
+/// Returns the key, the value before and the value after of every persistent or transient storage
+/// slot that was changed by the node.
+///
+/// Intermediate writes are compacted, and slots that were restored to their original value are
+/// skipped.
+pub(crate) fn storage_changes(node: &CallTraceNode, transient: bool) -> Vec<(U256, U256, U256)> {
+    let mut changes_map = HashMap::new();
+
+    // For each call trace, compact the results so we do not write the intermediate storage
+    // writes
+    for step in &node.trace.steps {
+        if let Some(change) = &step.storage_change {
+            if change.reason.is_transient() != transient {
+                continue;
+            }
+            let (_first, last) = changes_map.entry(&change.key).or_insert((change, change));
+            *last = change;
+        }
+    }
+
+    changes_map
+        .iter()
+        .filter_map(|(&&key, &(first, last))| {
+            let value_before = first.had_value.unwrap_or_default();
+            let value_after = last.value;
+            if value_before == value_after {
+                return None;
+            }
+            Some((key, value_before, value_after))
+        })
+        .collect()
+}
+
 /// Formats the given U256 as a decimal number if it is short, otherwise as a hexadecimal
 /// byte-array.
-fn num_or_hex(x: U256) -> String {
+pub(crate) fn num_or_hex(x: U256) -> String {
     if x < U256::from(1e6 as u128) {
         x.to_string()
     } else {
@@ -0,0 +1,127 @@
+//! ABI decoder tests
+
+use alloy_json_abi::JsonAbi;
+use alloy_primitives::{address, hex, keccak256, Address, B256, U256};
+use alloy_sol_types::SolValue;
+use revm::{
+    bytecode::{opcode, Bytecode},
+    context::TxEnv,
+    context_interface::TransactTo,
+    database::CacheDB,
+    database_interface::EmptyDB,
+    state::AccountInfo,
+    Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{AbiDecoder, TraceWriter, TracingInspector, TracingInspectorConfig};
+
+const ROUTER_ABI: &str = r#"[
+    {
+        "type": "function",
+        "name": "transfer",
+        "inputs": [
+            { "name": "to", "type": "address" },
+            { "name": "amount", "type": "uint256" }
+        ],
+        "outputs": [{ "name": "", "type": "uint256" }],
+        "stateMutability": "nonpayable"
+    },
+    {
+        "type": "event",
+        "name": "Transfer",
+        "inputs": [
+            { "name": "from", "type": "address", "indexed": true },
+            { "name": "to", "type": "address", "indexed": true },
+            { "name": "value", "type": "uint256", "indexed": false }
+        ],
+        "anonymous": false
+    }
+]"#;
+
+const VAULT_ABI: &str = r#"[
+    {
+        "type": "error",
+        "name": "InsufficientBalance",
+        "inputs": [
+            { "name": "available", "type": "uint256" },
+            { "name": "required", "type": "uint256" }
+        ]
+    }
+]"#;
+
+/// Appends `PUSH32 value` to the code.
+fn push32(code: &mut Vec<u8>, value: B256) {
+    code.push(opcode::PUSH32);
+    code.extend_from_slice(value.as_slice());
+}
+
+#[test]
+fn test_abi_decoder() {
+    let router = address!("00000000000000000000000000000000000000aa");
+    let vault = address!("00000000000000000000000000000000000000bb");
+
+    // CALL vault, emit Transfer(0xaa, 0xbb, 42) and return 42
+    let mut code = hex!("6000600060006000600060bb5af150").to_vec();
+    // PUSH1 0x2a PUSH1 0 MSTORE PUSH1 0xbb PUSH1 0xaa
+    code.extend(hex!("602a600052 60bb 60aa"));
+    push32(&mut code, keccak256("Transfer(address,address,uint256)"));
+    // PUSH1 0x20 PUSH1 0 LOG3 PUSH1 0x20 PUSH1 0 RETURN
+    code.extend(hex!("60206000a3 60206000f3"));
+
+    // revert with InsufficientBalance(100, 200)
+    let mut vault_code = Vec::new();
+    push32(
+        &mut vault_code,
+        B256::right_padding_from(&keccak256("InsufficientBalance(uint256,uint256)")[..4]),
+    );
+    // PUSH1 0 MSTORE PUSH1 100 PUSH1 4 MSTORE PUSH1 200 PUSH1 0x24 MSTORE PUSH1 0x44 PUSH1 0 REVERT
+    vault_code.extend(hex!("600052 6064600452 60c8602452 60446000fd"));
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            for (address, code) in [(router, &code), (vault, &vault_code)] {
+                db.insert_account_info(
+                    address,
+                    AccountInfo {
+                        code: Some(Bytecode::new_raw(code.clone().into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(
+            TracingInspectorConfig::default_parity().record_logs(),
+        ));
+    let mut input = hex!("a9059cbb").to_vec();
+    input.extend((vault, U256::from(42)).abi_encode_params());
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(router),
+            data: input.into(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+    let mut traces = evm.into_inspector().into_traces();
+
+    let decoder = AbiDecoder::new()
+        .with_abi(router, serde_json::from_str::<JsonAbi>(ROUTER_ABI).unwrap())
+        .with_abi(vault, serde_json::from_str::<JsonAbi>(VAULT_ABI).unwrap())
+        .with_label(vault, "Vault");
+    decoder.decode_arena(&mut traces);
+
+    let mut writer = TraceWriter::new(Vec::new());
+    writer.write_arena(&traces).unwrap();
+    let output = String::from_utf8(writer.into_writer()).unwrap();
+    assert_eq!(
+        output,
+        "  [4453] 0x00000000000000000000000000000000000000AA::transfer(0x00000000000000000000000000000000000000bb, 42)
+    ├─ [42] Vault::fallback()
+    │   └─ ← [Revert] InsufficientBalance(100, 200)
+    ├─ emit Transfer(from: 0x00000000000000000000000000000000000000AA, to: 0x00000000000000000000000000000000000000bb, value: 42)
+    └─ ← [Return] 42
+"
+    );
+}
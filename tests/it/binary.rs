@@ -0,0 +1,90 @@
+//! Binary encoding tests
+
+use alloy_primitives::{address, hex, Address};
+use revm::{
+    bytecode::Bytecode, context::TxEnv, context_interface::TransactTo, database::CacheDB,
+    database_interface::EmptyDB, inspector::InspectorEvmTr, state::AccountInfo, Context,
+    InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{
+    BinaryTraceError, CallTraceArena, CallTraceArenaReader, TracingInspector,
+    TracingInspectorConfig,
+};
+
+#[test]
+fn test_binary_roundtrip() {
+    // PUSH1 0x2a PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 LOG0 PUSH1 1 PUSH1 0 SSTORE
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP
+    // PUSH4 0x60015000 PUSH1 0 MSTORE PUSH1 4 PUSH1 28 PUSH1 0 CREATE POP STOP
+    let code = hex!(
+        "602a60005260206000a06001600055"
+        "6000600060006000600060bb5af150"
+        "63600150006000526004601c6000f05000"
+    );
+    let contract = address!("00000000000000000000000000000000000000aa");
+    // PUSH1 1 POP STOP
+    let callee_code = hex!("60015000");
+    let callee = address!("00000000000000000000000000000000000000bb");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+            db.insert_account_info(
+                callee,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(callee_code.into())),
+                    ..Default::default()
+                },
+            );
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(TracingInspectorConfig::all()));
+
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contract),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let mut arena = evm.inspector().traces().clone();
+    assert_eq!(arena.nodes().len(), 3);
+    arena.nodes_mut()[1].trace.decoded.label = Some("Callee".to_string());
+
+    let encoded = arena.encode_binary();
+    assert_eq!(CallTraceArena::decode_binary(&encoded).unwrap(), arena);
+
+    // every step stores the full stack and memory, the encoding only stores the differences
+    let snapshots = arena
+        .nodes()
+        .iter()
+        .flat_map(|node| &node.trace.steps)
+        .map(|step| {
+            step.memory.as_ref().map_or(0, |memory| memory.len())
+                + step.stack.as_ref().map_or(0, |stack| stack.len() * 32)
+        })
+        .sum::<usize>();
+    let mut stripped = arena.clone();
+    for step in stripped.nodes_mut().iter_mut().flat_map(|node| &mut node.trace.steps) {
+        step.memory = None;
+        step.stack = None;
+    }
+    let snapshots_encoded = encoded.len() - stripped.encode_binary().len();
+    assert!(snapshots_encoded < snapshots / 4, "{snapshots_encoded} >= {snapshots} / 4");
+
+    // nodes can be decoded individually
+    let reader = CallTraceArenaReader::new(&encoded).unwrap();
+    assert_eq!(reader.len(), 3);
+    assert_eq!(reader.node(1).unwrap(), arena.nodes()[1]);
+    assert_eq!(reader.node(2).unwrap(), arena.nodes()[2]);
+    assert!(matches!(reader.node(3), Err(BinaryTraceError::NodeOutOfBounds(3))));
+
+    // truncated input is rejected
+    assert!(CallTraceArena::decode_binary(&encoded[..encoded.len() - 1]).is_err());
+}
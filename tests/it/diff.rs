@@ -0,0 +1,103 @@
+//! Call trace diff tests
+
+use alloy_primitives::{address, hex, Address};
+use revm::{
+    bytecode::{opcode::OpCode, Bytecode},
+    context::TxEnv,
+    context_interface::TransactTo,
+    database::CacheDB,
+    database_interface::EmptyDB,
+    interpreter::InstructionResult,
+    state::AccountInfo,
+    Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{
+    CallDiff, CallTraceArena, CallTraceDiffConfig, StepDivergence, TraceWriter, TracingInspector,
+    TracingInspectorConfig,
+};
+
+/// Executes a call to `0xaa` with the given code of `0xaa` and `0xbb`, and returns the traces.
+///
+/// `0xcc` only contains `STOP`.
+fn trace_call(code: &[u8], callee_code: &[u8]) -> CallTraceArena {
+    let contract = address!("00000000000000000000000000000000000000aa");
+    let callee = address!("00000000000000000000000000000000000000bb");
+    let other_callee = address!("00000000000000000000000000000000000000cc");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            for (address, code) in [(contract, code), (callee, callee_code), (other_callee, &[0])] {
+                db.insert_account_info(
+                    address,
+                    AccountInfo {
+                        code: Some(Bytecode::new_raw(code.to_vec().into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(TracingInspectorConfig::all()));
+    evm.inspect_tx(TxEnv {
+        caller: Address::ZERO,
+        gas_limit: 1000000,
+        kind: TransactTo::Call(contract),
+        ..Default::default()
+    })
+    .unwrap();
+    evm.into_inspector().into_traces()
+}
+
+#[test]
+fn test_trace_diff() {
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP STOP
+    let left = trace_call(
+        &hex!("6000600060006000600060bb5af15000"),
+        // PUSH1 0x2a PUSH1 0 MSTORE STOP
+        &hex!("602a60005200"),
+    );
+    // same as above, but additionally calls 0xcc, while 0xbb reverts
+    let right = trace_call(
+        &hex!("6000600060006000600060bb5af1506000600060006000600060cc5af15000"),
+        // PUSH1 0 PUSH1 0 REVERT
+        &hex!("60006000fd"),
+    );
+
+    assert!(left.diff(&left, CallTraceDiffConfig::new().compare_steps(true)).is_empty());
+
+    let diff = left.diff(&right, CallTraceDiffConfig::new().compare_steps(true));
+    assert!(!diff.is_empty());
+    let CallDiff::Matched(root) = diff.root() else { panic!("root is always matched") };
+    assert_eq!(root.changes.first_diverging_step.unwrap().index, 9);
+    assert_eq!(root.children.len(), 2);
+    assert_eq!(root.children[1], CallDiff::Added(2));
+
+    let CallDiff::Matched(callee) = &root.children[0] else { panic!("callee is matched") };
+    assert_eq!(
+        callee.changes.status,
+        Some((Some(InstructionResult::Stop), Some(InstructionResult::Revert)))
+    );
+    assert_eq!(
+        callee.changes.first_diverging_step,
+        Some(StepDivergence {
+            index: 2,
+            left: Some((4, OpCode::MSTORE)),
+            right: Some((4, OpCode::REVERT)),
+        })
+    );
+
+    let mut writer = TraceWriter::new(Vec::new());
+    writer.write_diff(&diff).unwrap();
+    let output = String::from_utf8(writer.into_writer()).unwrap();
+    assert_eq!(
+        output,
+        "  ~ [5250] 0x00000000000000000000000000000000000000AA::fallback() (+2616 gas)
+    ├─ ~ step 9: STOP @ 15 → PUSH1 @ 15
+    ├─ ~ [6] 0x00000000000000000000000000000000000000bb::fallback() (-6 gas)
+    │   ├─ ~ step 2: MSTORE @ 4 → REVERT @ 4
+    │   └─ ← [Revert] (was Stop)
+    ├─ + [0] 0x00000000000000000000000000000000000000cc::fallback()
+    └─ ← [Stop]
+"
+    );
+}
@@ -0,0 +1,121 @@
+//! EIP-3155 tests
+
+use alloy_primitives::{address, hex, Address};
+use revm::{
+    bytecode::Bytecode,
+    context::TxEnv,
+    context_interface::{result::ExecutionResult, TransactTo},
+    database::CacheDB,
+    database_interface::EmptyDB,
+    state::AccountInfo,
+    Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{
+    Eip3155Summary, Eip3155Writer, StepSink, TracingInspector, TracingInspectorConfig,
+};
+use serde_json::Value;
+
+/// Executes a call that calls another contract with the given inspector.
+fn inspect_nested_call<S: StepSink>(
+    inspector: TracingInspector<S>,
+) -> (ExecutionResult, TracingInspector<S>) {
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP STOP
+    let code = hex!("6000600060006000600060bb5af15000");
+    let contract = address!("00000000000000000000000000000000000000aa");
+    // PUSH1 0x2a PUSH1 0 MSTORE STOP
+    let callee_code = hex!("602a60005200");
+    let callee = address!("00000000000000000000000000000000000000bb");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+            db.insert_account_info(
+                callee,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(callee_code.into())),
+                    ..Default::default()
+                },
+            );
+        })
+        .build_mainnet_with_inspector(inspector);
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contract),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+    (res.result, evm.into_inspector())
+}
+
+#[test]
+fn test_eip3155_writer() {
+    let config = TracingInspectorConfig::default_geth().memory_snapshots();
+
+    // write the recorded arena
+    let (result, inspector) = inspect_nested_call(TracingInspector::new(config));
+    let mut writer = Eip3155Writer::new(Vec::new()).write_memory(true);
+    writer.write_arena(inspector.traces()).unwrap();
+    writer.write_summary(&Eip3155Summary::from(&result)).unwrap();
+    let output = writer.into_inner().unwrap();
+
+    // stream the steps straight from the inspector
+    let (result, mut inspector) = inspect_nested_call(
+        TracingInspector::new(config)
+            .with_step_sink(Eip3155Writer::new(Vec::new()).write_memory(true)),
+    );
+    assert!(inspector.traces().nodes().iter().all(|node| node.trace.steps.is_empty()));
+    let mut writer = inspector.take_step_sink().unwrap();
+    writer.write_summary(&Eip3155Summary::from(&result)).unwrap();
+    assert_eq!(writer.into_inner().unwrap(), output);
+
+    let lines = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    // 10 steps of the caller, 4 of the callee and the summary
+    assert_eq!(lines.len(), 15);
+
+    assert_eq!(
+        lines[0],
+        serde_json::json!({
+            "pc": 0,
+            "op": 0x60,
+            // 1000000 - 21000 intrinsic gas
+            "gas": "0xef038",
+            "gasCost": "0x3",
+            "memory": "0x",
+            "memSize": 0,
+            "stack": [],
+            "depth": 1,
+            "refund": 0,
+            "opName": "PUSH1",
+        })
+    );
+
+    // the steps of the callee follow the CALL
+    assert_eq!(lines[7]["opName"], "CALL");
+    assert_eq!(lines[7]["stack"].as_array().unwrap().len(), 7);
+    let callee_ops =
+        lines[8..12].iter().map(|line| (line["opName"].clone(), line["depth"].clone()));
+    assert!(callee_ops.clone().all(|(_, depth)| depth == 2));
+    assert_eq!(
+        callee_ops.map(|(op, _)| op).collect::<Vec<_>>(),
+        ["PUSH1", "PUSH1", "MSTORE", "STOP"]
+    );
+    assert_eq!(lines[11]["memSize"], 32);
+    assert_eq!(lines[12]["opName"], "POP");
+    assert_eq!(lines[12]["depth"], 1);
+
+    let summary = &lines[14];
+    assert_eq!(summary["pass"], true);
+    assert_eq!(summary["output"], "0x");
+    assert_eq!(summary["gasUsed"], format!("{:#x}", result.gas_used()));
+}
@@ -0,0 +1,112 @@
+//! Flamegraph tests
+
+use alloy_primitives::{address, hex, Address, U256};
+use revm::{
+    bytecode::Bytecode, context::TxEnv, context_interface::TransactTo, database::CacheDB,
+    database_interface::EmptyDB, state::AccountInfo, Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{
+    types::{DecodedInternalCall, DecodedTraceStep},
+    CallTraceArena, FlamegraphConfig, FlamegraphWriter, TracingInspector, TracingInspectorConfig,
+};
+
+/// Executes `0xaa`, which calls `0xbb` with value and then `0xcc`.
+fn traces() -> CallTraceArena {
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 1 PUSH1 0xbb GAS CALL POP
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xcc GAS CALL POP STOP
+    let code = hex!("6000600060006000600160bb5af1506000600060006000600060cc5af15000");
+    let contracts = [
+        (address!("00000000000000000000000000000000000000aa"), &code[..]),
+        // PUSH1 1 POP STOP
+        (address!("00000000000000000000000000000000000000bb"), &hex!("60015000")[..]),
+        // PUSH1 0x2a PUSH1 0 MSTORE STOP
+        (address!("00000000000000000000000000000000000000cc"), &hex!("602a60005200")[..]),
+    ];
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            for (address, code) in contracts {
+                db.insert_account_info(
+                    address,
+                    AccountInfo {
+                        balance: U256::from(1),
+                        code: Some(Bytecode::new_raw(code.to_vec().into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        .build_mainnet_with_inspector(
+            TracingInspector::new(TracingInspectorConfig::default_geth()),
+        );
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contracts[0].0),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let mut traces = evm.into_inspector().into_traces();
+    traces.nodes_mut()[1].trace.decoded.label = Some("Callee".to_string());
+    traces
+}
+
+fn folded(traces: &CallTraceArena, config: FlamegraphConfig) -> String {
+    let mut writer = FlamegraphWriter::with_config(String::new(), config);
+    writer.write_arena(traces).unwrap();
+    writer.into_writer()
+}
+
+/// Returns the sum of the weights of all lines.
+fn total_gas(folded: &str) -> u64 {
+    folded.lines().map(|line| line.rsplit_once(' ').unwrap().1.parse::<u64>().unwrap()).sum()
+}
+
+#[test]
+fn test_flamegraph() {
+    let mut traces = traces();
+    let gas_used = traces.nodes()[0].trace.gas_used;
+
+    let calls = folded(&traces, FlamegraphConfig::new());
+    assert_eq!(
+        calls,
+        "\
+0x00000000000000000000000000000000000000AA::fallback 11944
+0x00000000000000000000000000000000000000AA::fallback;Callee::fallback 5
+0x00000000000000000000000000000000000000AA::fallback;0x00000000000000000000000000000000000000cc::fallback 12
+"
+    );
+    assert_eq!(total_gas(&calls), gas_used);
+
+    // the call opcodes are charged without the forwarded gas, the first call transfers value, so
+    // the stipend that is returned to the caller is deducted from its cost
+    let opcodes = folded(&traces, FlamegraphConfig::new().opcodes(true));
+    assert!(opcodes.contains("\n0x00000000000000000000000000000000000000AA::fallback;CALL 11900\n"));
+    assert_eq!(total_gas(&opcodes), gas_used);
+
+    // the second call is made from an internal function
+    let call = DecodedInternalCall { func_name: "f".to_string(), args: None, return_data: None };
+    traces.nodes_mut()[0].trace.steps[9].decoded = Some(DecodedTraceStep::InternalCall(call, 17));
+    let internal = folded(&traces, FlamegraphConfig::new().internal_calls(true));
+    assert_eq!(
+        internal,
+        "\
+0x00000000000000000000000000000000000000AA::fallback 9322
+0x00000000000000000000000000000000000000AA::fallback;Callee::fallback 5
+0x00000000000000000000000000000000000000AA::fallback;f 2622
+0x00000000000000000000000000000000000000AA::fallback;f;0x00000000000000000000000000000000000000cc::fallback 12
+"
+    );
+    assert_eq!(total_gas(&internal), gas_used);
+
+    let internal = folded(&traces, FlamegraphConfig::new().internal_calls(true).opcodes(true));
+    assert!(internal.contains("\n0x00000000000000000000000000000000000000AA::fallback;CALL 9300\n"));
+    assert!(
+        internal.contains("\n0x00000000000000000000000000000000000000AA::fallback;f;CALL 2600\n")
+    );
+    assert_eq!(total_gas(&internal), gas_used);
+}
@@ -1,12 +1,15 @@
 //! Geth tests
 use crate::utils::deploy_contract;
-use alloy_primitives::{hex, map::HashMap, Address, Bytes, TxKind};
+use alloy_primitives::{address, hex, map::HashMap, Address, Bytes, TxKind, B256, U256};
 use alloy_rpc_types_eth::TransactionInfo;
 use alloy_rpc_types_trace::geth::{
-    mux::MuxConfig, CallConfig, FlatCallConfig, GethDebugBuiltInTracerType, GethDebugTracerConfig,
-    GethTrace, PreStateConfig, PreStateFrame,
+    erc7562::{CallFrameType, ContractSize, Erc7562Config},
+    mux::MuxConfig,
+    CallConfig, FlatCallConfig, GethDebugBuiltInTracerType, GethDebugTracerConfig,
+    GethDefaultTracingOptions, GethTrace, PreStateConfig, PreStateFrame,
 };
 use revm::{
+    bytecode::{opcode, Bytecode},
     context::TxEnv,
     context_interface::{ContextTr, TransactTo},
     database::CacheDB,
@@ -14,9 +17,13 @@ use revm::{
     handler::EvmTr,
     inspector::InspectorEvmTr,
     primitives::hardfork::SpecId,
+    state::AccountInfo,
     Context, InspectEvm, MainBuilder, MainContext,
 };
-use revm_inspectors::tracing::{MuxInspector, TracingInspector, TracingInspectorConfig};
+use revm_inspectors::tracing::{
+    types::{CallTraceNode, CallTraceStep},
+    GethTraceBuilder, MuxInspector, TracingInspector, TracingInspectorConfig,
+};
 
 #[test]
 fn test_geth_calltracer_logs() {
@@ -231,8 +238,8 @@ fn test_geth_mux_tracer() {
         GethTrace::FlatCallTracer(traces) => {
             assert_eq!(traces.len(), 6);
             assert!(traces[0].trace.error.is_none());
-            assert!(traces[1].trace.error.is_some());
-            assert!(traces[2].trace.error.is_some());
+            assert_eq!(traces[1].trace.error.as_deref(), Some("Reverted"));
+            assert_eq!(traces[2].trace.error.as_deref(), Some("Reverted"));
             assert!(traces[3].trace.error.is_none());
             assert!(traces[4].trace.error.is_none());
             assert!(traces[5].trace.error.is_none());
@@ -241,6 +248,134 @@ fn test_geth_mux_tracer() {
     }
 }
 
+#[test]
+fn test_geth_flat_call_tracer() {
+    let contract = address!("0x00000000000000000000000000000000000000aa");
+    let code = hex!(
+        // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 4 GAS STATICCALL POP
+        "600060006000600060045afa50"
+        // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP STOP
+        "6000600060006000600060bb5af15000"
+    );
+    // PUSH1 0 PUSH1 0 REVERT
+    let reverter = address!("0x00000000000000000000000000000000000000bb");
+    let reverter_code = hex!("60006000fd");
+
+    let context =
+        Context::mainnet().with_db(CacheDB::<EmptyDB>::default()).modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+            db.insert_account_info(
+                reverter,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(reverter_code.into())),
+                    ..Default::default()
+                },
+            );
+        });
+    let tx = TxEnv::builder()
+        .caller(Address::ZERO)
+        .gas_limit(1000000)
+        .gas_price(Default::default())
+        .kind(TxKind::Call(contract))
+        .build_fill();
+
+    let flat_call_traces = |config: FlatCallConfig| {
+        let mut evm = context.clone().build_mainnet_with_inspector(TracingInspector::new(
+            TracingInspectorConfig::from_flat_call_config(&config),
+        ));
+        let res = evm.inspect_tx(tx.clone()).unwrap();
+        assert!(res.result.is_success());
+        evm.into_inspector()
+            .into_parity_builder()
+            .into_flat_call_traces(&config, TransactionInfo::default())
+    };
+
+    // precompiles are excluded and errors are reported in geth style by default
+    let traces = flat_call_traces(FlatCallConfig::default());
+    assert_eq!(traces.len(), 2);
+    assert_eq!(traces[0].trace.subtraces, 1);
+    assert_eq!(traces[1].trace.trace_address, vec![0]);
+    assert_eq!(traces[1].trace.error.as_deref(), Some("execution reverted"));
+
+    let traces = flat_call_traces(FlatCallConfig::default().parity_errors());
+    assert_eq!(traces.len(), 2);
+    assert_eq!(traces[1].trace.error.as_deref(), Some("Reverted"));
+
+    let traces =
+        flat_call_traces(FlatCallConfig { include_precompiles: Some(true), ..Default::default() });
+    assert_eq!(traces.len(), 3);
+    assert_eq!(traces[0].trace.subtraces, 2);
+    assert_eq!(traces[1].trace.trace_address, vec![0]);
+    assert!(traces[1].trace.error.is_none());
+    assert_eq!(traces[2].trace.trace_address, vec![1]);
+    assert_eq!(traces[2].trace.error.as_deref(), Some("execution reverted"));
+}
+
+#[test]
+fn test_geth_prestate_accessed_state() {
+    let contract = address!("0x00000000000000000000000000000000000000aa");
+    // PUSH1 3 SLOAD POP PUSH1 0xcc BALANCE POP PUSH1 0xdd EXTCODESIZE POP PUSH1 1 BALANCE POP
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 4 GAS STATICCALL POP STOP
+    let code = hex!("6003545060cc315060dd3b5060013150600060006000600060045afa5000");
+    let other = address!("0x00000000000000000000000000000000000000cc");
+    let empty = address!("0x00000000000000000000000000000000000000dd");
+
+    let mut db = CacheDB::<EmptyDB>::default();
+    db.insert_account_info(
+        contract,
+        AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+    );
+    db.insert_account_storage(contract, U256::from(3), U256::from(9)).unwrap();
+    db.insert_account_info(other, AccountInfo { balance: U256::from(100), ..Default::default() });
+    let tx = TxEnv::builder()
+        .caller(Address::ZERO)
+        .gas_limit(1000000)
+        .gas_price(Default::default())
+        .kind(TxKind::Call(contract))
+        .build_fill();
+
+    let prestate_config = PreStateConfig::default();
+    let mut evm = Context::mainnet().with_db(db).build_mainnet_with_inspector(
+        TracingInspector::new(TracingInspectorConfig::from_geth_prestate_config(&prestate_config)),
+    );
+    let mut res = evm.inspect_tx(tx).unwrap();
+    assert!(res.result.is_success());
+
+    let (ctx, inspector) = evm.ctx_inspector();
+    let root = &inspector.traces().nodes()[0];
+    // precompiles are not recorded
+    assert_eq!(
+        root.accessed_state.keys().copied().collect::<Vec<_>>(),
+        vec![Address::ZERO, contract, other, empty]
+    );
+    assert_eq!(
+        root.accessed_state[&contract].iter().copied().collect::<Vec<_>>(),
+        vec![B256::with_last_byte(3)]
+    );
+    assert!(root.accessed_state[&other].is_empty());
+    assert!(root.accessed_state[&empty].is_empty());
+
+    // the prestate doesn't depend on the accounts and slots kept in the state
+    res.state.clear();
+    let frame = inspector
+        .geth_builder()
+        .geth_prestate_traces(&res, &prestate_config, ctx.db_ref())
+        .unwrap();
+    let PreStateFrame::Default(prestate) = frame else { panic!("expected default prestate") };
+    assert_eq!(
+        prestate.0.keys().copied().collect::<Vec<_>>(),
+        vec![Address::ZERO, contract, other, empty]
+    );
+    assert_eq!(
+        prestate.0[&contract].storage.get(&B256::with_last_byte(3)),
+        Some(&B256::with_last_byte(9))
+    );
+    assert_eq!(prestate.0[&other].balance, Some(U256::from(100)));
+}
+
 #[test]
 fn test_geth_inspector_reset() {
     let insp = TracingInspector::new(TracingInspectorConfig::default_geth());
@@ -294,3 +429,261 @@ fn test_geth_inspector_reset() {
         1000000
     );
 }
+
+#[test]
+fn test_geth_step_sink() {
+    // callee: SSTORE(0, 1)
+    let callee = address!("0x00000000000000000000000000000000000000bb");
+    let callee_code = hex!("600160005500");
+    // caller: CALL(gas, callee, 0, 0, 0, 0, 0) followed by a LOG0
+    let caller = address!("0x00000000000000000000000000000000000000aa");
+    let caller_code =
+        hex!("600060006000600060007300000000000000000000000000000000000000bb5af160006000a000");
+
+    let context =
+        Context::mainnet().with_db(CacheDB::<EmptyDB>::default()).modify_db_chained(|db| {
+            db.insert_account_info(
+                callee,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(callee_code.into())),
+                    ..Default::default()
+                },
+            );
+            db.insert_account_info(
+                caller,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(caller_code.into())),
+                    ..Default::default()
+                },
+            );
+        });
+    let tx = TxEnv::builder()
+        .caller(Address::ZERO)
+        .gas_limit(1000000)
+        .gas_price(Default::default())
+        .kind(TxKind::Call(caller))
+        .build_fill();
+    let config = TracingInspectorConfig::default_geth().set_record_logs(true);
+
+    let mut evm = context.clone().build_mainnet_with_inspector(TracingInspector::new(config));
+    let res = evm.inspect_tx(tx.clone()).unwrap();
+    assert!(res.result.is_success());
+    let recorded = evm.into_inspector().into_traces();
+
+    let mut streamed = Vec::new();
+    let sink = |node: &CallTraceNode, step_idx: usize, step: CallTraceStep| {
+        streamed.push((node.idx, step_idx, step));
+    };
+    let mut evm =
+        context.build_mainnet_with_inspector(TracingInspector::new(config).with_step_sink(sink));
+    let res = evm.inspect_tx(tx).unwrap();
+    assert!(res.result.is_success());
+    let streamed_traces = evm.into_inspector().into_traces();
+
+    // steps are not stored in the arena, but everything else is
+    assert!(streamed_traces.nodes().iter().all(|node| node.trace.steps.is_empty()));
+    assert_eq!(streamed_traces.nodes().len(), recorded.nodes().len());
+    for (streamed, recorded) in streamed_traces.nodes().iter().zip(recorded.nodes()) {
+        assert_eq!(streamed.ordering, recorded.ordering);
+        assert_eq!(streamed.logs, recorded.logs);
+        assert_eq!(streamed.children, recorded.children);
+    }
+
+    // streamed steps match the recorded ones and arrive in execution order
+    let struct_logs = GethTraceBuilder::new(recorded.nodes().to_vec())
+        .geth_traces(0, Bytes::default(), Default::default())
+        .struct_logs;
+    assert_eq!(struct_logs.len(), streamed.len());
+    for ((idx, step_idx, step), log) in streamed.iter().zip(&struct_logs) {
+        assert_eq!(&recorded.nodes()[*idx].trace.steps[*step_idx], step);
+        assert_eq!(log.op, step.op.to_string());
+        assert_eq!(log.depth, step.depth);
+    }
+}
+
+#[test]
+fn test_geth_struct_logger_limit() {
+    // PUSH1 1 PUSH1 0 SSTORE PUSH1 1 PUSH1 0 RETURN
+    let contract = address!("0x00000000000000000000000000000000000000aa");
+    let code = hex!("600160005560016000f3");
+    // PUSH1 1 PUSH1 0 MSTORE INVALID
+    let invalid = address!("0x00000000000000000000000000000000000000bb");
+    let invalid_code = hex!("6001600052fe");
+
+    let context =
+        Context::mainnet().with_db(CacheDB::<EmptyDB>::default()).modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+            db.insert_account_info(
+                invalid,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(invalid_code.into())),
+                    ..Default::default()
+                },
+            );
+        });
+    let tx = |to| {
+        TxEnv::builder()
+            .caller(Address::ZERO)
+            .gas_limit(1000000)
+            .gas_price(Default::default())
+            .kind(TxKind::Call(to))
+            .build_fill()
+    };
+    let opts = GethDefaultTracingOptions::default().with_limit(3);
+
+    // the limit is enforced while recording
+    let config = TracingInspectorConfig::from_geth_config(&opts);
+    let mut evm = context.clone().build_mainnet_with_inspector(TracingInspector::new(config));
+    let res = evm.inspect_tx(tx(contract)).unwrap();
+    assert!(res.result.is_success());
+    let output = res.result.output().cloned().unwrap_or_default();
+    let insp = evm.into_inspector();
+    assert_eq!(insp.traces().nodes()[0].trace.steps.len(), 3);
+    let frame = insp.geth_builder().geth_traces(res.result.gas_used(), output.clone(), opts);
+    assert_eq!(frame.struct_logs.len(), 3);
+    assert!(!frame.failed);
+    assert_eq!(frame.return_value, output);
+
+    // and when building the output from an unbounded recording
+    let config = TracingInspectorConfig::default_geth();
+    let mut evm = context.clone().build_mainnet_with_inspector(TracingInspector::new(config));
+    let res = evm.inspect_tx(tx(contract)).unwrap();
+    let insp = evm.into_inspector();
+    assert_eq!(insp.traces().nodes()[0].trace.steps.len(), 6);
+    let frame = insp.geth_builder().geth_traces(res.result.gas_used(), output.clone(), opts);
+    assert_eq!(frame.struct_logs.len(), 3);
+    let frame = insp.geth_builder().geth_traces(res.result.gas_used(), output, Default::default());
+    assert_eq!(frame.struct_logs.len(), 6);
+
+    // the return value is empty if the transaction failed without reverting
+    let mut evm = context.build_mainnet_with_inspector(TracingInspector::new(config));
+    let res = evm.inspect_tx(tx(invalid)).unwrap();
+    assert!(!res.result.is_success());
+    let frame = evm.into_inspector().geth_builder().geth_traces(
+        res.result.gas_used(),
+        Bytes::from_static(&[1]),
+        opts,
+    );
+    assert!(frame.failed);
+    assert!(frame.return_value.is_empty());
+    assert_eq!(frame.struct_logs.len(), 3);
+}
+
+#[test]
+fn test_geth_erc7562_tracer() {
+    let contract = address!("0x00000000000000000000000000000000000000aa");
+    let code = hex!(
+        // PUSH1 0 SLOAD POP
+        "60005450"
+        // PUSH1 1 PUSH1 0 SSTORE
+        "6001600055"
+        // PUSH1 2 PUSH1 1 TSTORE
+        "600260015d"
+        // PUSH1 1 TLOAD POP
+        "60015c50"
+        // PUSH1 0x20 PUSH1 0 KECCAK256 POP
+        "602060002050"
+        // PUSH1 0xbb EXTCODESIZE POP
+        "60bb3b50"
+        // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP STOP
+        "6000600060006000600060bb5af15000"
+    );
+    // PUSH1 5 SLOAD STOP
+    let callee = address!("0x00000000000000000000000000000000000000bb");
+    let callee_code = hex!("60055400");
+
+    let mut db = CacheDB::<EmptyDB>::default();
+    db.insert_account_info(
+        contract,
+        AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+    );
+    db.insert_account_storage(contract, U256::ZERO, U256::from(7)).unwrap();
+    db.insert_account_info(
+        callee,
+        AccountInfo { code: Some(Bytecode::new_raw(callee_code.into())), ..Default::default() },
+    );
+    let context = Context::mainnet().with_db(db);
+    let tx = TxEnv::builder()
+        .caller(Address::ZERO)
+        .gas_limit(1000000)
+        .gas_price(Default::default())
+        .kind(TxKind::Call(contract))
+        .build_fill();
+
+    let config = Erc7562Config::default();
+    let mut evm = context.build_mainnet_with_inspector(TracingInspector::new(
+        TracingInspectorConfig::from_geth_erc7562_config(&config),
+    ));
+    let res = evm.inspect_tx(tx.clone()).unwrap();
+    assert!(res.result.is_success());
+    let (ctx, inspector) = evm.ctx_inspector();
+    let frame = inspector
+        .geth_builder()
+        .geth_erc7562_traces(&config, res.result.gas_used(), &res, ctx.db_ref())
+        .unwrap();
+
+    assert_eq!(frame.call_frame_type, CallFrameType::Call);
+    assert_eq!(frame.gas_used, res.result.gas_used());
+    assert!(!frame.out_of_gas);
+    assert_eq!(
+        frame.accessed_slots.reads,
+        HashMap::from_iter([(B256::ZERO, vec![B256::from(U256::from(7))])])
+    );
+    assert_eq!(frame.accessed_slots.writes, HashMap::from_iter([(B256::ZERO, 1)]));
+    assert_eq!(
+        frame.accessed_slots.transient_writes,
+        HashMap::from_iter([(B256::with_last_byte(1), 1)])
+    );
+    assert_eq!(
+        frame.accessed_slots.transient_reads,
+        HashMap::from_iter([(B256::with_last_byte(1), 1)])
+    );
+    assert_eq!(frame.ext_code_access_info, vec![callee.to_string()]);
+    assert_eq!(
+        frame.contract_size,
+        HashMap::from_iter([(
+            callee,
+            ContractSize { contract_size: callee_code.len() as u64, opcode: opcode::EXTCODESIZE }
+        )])
+    );
+    // stack manipulation opcodes are ignored and GAS is followed by a call
+    assert_eq!(
+        frame.used_opcodes,
+        HashMap::from_iter(
+            [
+                opcode::SLOAD,
+                opcode::SSTORE,
+                opcode::TSTORE,
+                opcode::TLOAD,
+                opcode::KECCAK256,
+                opcode::EXTCODESIZE,
+                opcode::CALL,
+                opcode::STOP,
+            ]
+            .map(|op| (op, 1))
+        )
+    );
+    assert_eq!(frame.keccak, vec![Bytes::from(vec![0u8; 32])]);
+
+    assert_eq!(frame.calls.len(), 1);
+    let call = &frame.calls[0];
+    assert_eq!(call.to, Some(callee));
+    assert_eq!(
+        call.accessed_slots.reads,
+        HashMap::from_iter([(B256::with_last_byte(5), vec![B256::ZERO])])
+    );
+    assert_eq!(call.used_opcodes, HashMap::from_iter([(opcode::SLOAD, 1), (opcode::STOP, 1)]));
+    assert!(call.keccak.is_empty());
+
+    // the same frame is produced by the mux tracer
+    let mux_config =
+        MuxConfig(HashMap::from_iter([(GethDebugBuiltInTracerType::Erc7562Tracer, None)]));
+    let mut evm = evm.with_inspector(MuxInspector::try_from_config(mux_config).unwrap());
+    let res = evm.inspect_tx(tx).unwrap();
+    let (ctx, inspector) = evm.ctx_inspector();
+    let mux = inspector.try_into_mux_frame(&res, ctx.db_ref(), TransactionInfo::default()).unwrap();
+    assert_eq!(mux.0[&GethDebugBuiltInTracerType::Erc7562Tracer], GethTrace::from(frame));
+}
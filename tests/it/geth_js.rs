@@ -1,17 +1,23 @@
 //! Geth Js tracer tests
 
 use crate::utils::deploy_contract;
-use alloy_primitives::{address, hex, Address};
+use alloy_primitives::{address, hex, map::HashMap, Address, TxKind};
+use alloy_rpc_types_eth::TransactionInfo;
+use alloy_rpc_types_trace::geth::{
+    mux::MuxConfig, CallConfig, GethDebugBuiltInTracerType, GethDebugTracerConfig, GethTrace,
+};
 use revm::{
+    bytecode::Bytecode,
     context::TxEnv,
     context_interface::{ContextTr, TransactTo},
     database::CacheDB,
     database_interface::EmptyDB,
     inspector::InspectorEvmTr,
     primitives::hardfork::SpecId,
+    state::AccountInfo,
     Context, InspectEvm, MainBuilder, MainContext,
 };
-use revm_inspectors::tracing::js::JsInspector;
+use revm_inspectors::tracing::{js::JsInspector, MuxInspector};
 use serde_json::json;
 
 #[test]
@@ -176,3 +182,86 @@ fn test_geth_jstracer_proxy_contract() {
     let result = insp.json_result(res, context.tx(), context.block(), context.db_ref()).unwrap();
     assert_eq!(result, json!([{"event": "Transfer", "token": proxy_addr, "caller": deployer}]));
 }
+
+#[test]
+fn test_geth_jstracer_mux() {
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL STOP
+    let contract = address!("0x00000000000000000000000000000000000000aa");
+    let code = hex!("6000600060006000600060bb5af100");
+
+    let mut db = CacheDB::<EmptyDB>::default();
+    db.insert_account_info(
+        contract,
+        AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+    );
+
+    let config = MuxConfig(HashMap::from_iter([(
+        GethDebugBuiltInTracerType::CallTracer,
+        Some(GethDebugTracerConfig(serde_json::to_value(CallConfig::default()).unwrap())),
+    )]));
+    let calls = r#"
+{
+    calls: 0,
+    enter: function() { this.calls++; },
+    fault: function() {},
+    result: function() { return this.calls; },
+}"#;
+    let steps = r#"
+{
+    steps: 0,
+    step: function() { this.steps++; },
+    fault: function() {},
+    result: function() { return this.steps; },
+}"#;
+    let insp = MuxInspector::try_from_config(config)
+        .unwrap()
+        .with_js_tracer("calls", JsInspector::new(calls.to_string(), json!({})).unwrap())
+        .with_js_tracer("steps", JsInspector::new(steps.to_string(), json!({})).unwrap());
+
+    let mut evm = Context::mainnet().with_db(db).build_mainnet_with_inspector(insp);
+    let tx = TxEnv::builder()
+        .caller(Address::ZERO)
+        .gas_limit(1000000)
+        .gas_price(Default::default())
+        .kind(TxKind::Call(contract))
+        .build_fill();
+    let res = evm.inspect_tx(tx.clone()).unwrap();
+    assert!(res.result.is_success());
+
+    let (context, insp) = evm.ctx_inspector();
+    let trace = insp
+        .try_into_mux_trace(
+            &res,
+            context.tx(),
+            context.block(),
+            context.db_ref(),
+            TransactionInfo::default(),
+        )
+        .unwrap();
+    let GethTrace::JS(frame) = trace else { panic!("expected a JSON mux frame") };
+    assert_eq!(frame["callTracer"]["calls"].as_array().unwrap().len(), 1);
+    assert_eq!(frame["calls"], json!(1));
+    assert_eq!(frame["steps"], json!(9));
+
+    // JavaScript tracers can be configured by their code, like in geth
+    let config = json!({
+        "callTracer": {},
+        (calls): {},
+    });
+    let insp = MuxInspector::try_from_js_config(GethDebugTracerConfig(config)).unwrap();
+    let mut evm = evm.with_inspector(insp);
+    let res = evm.inspect_tx(tx).unwrap();
+    let (context, insp) = evm.ctx_inspector();
+    let trace = insp
+        .try_into_mux_trace(
+            &res,
+            context.tx(),
+            context.block(),
+            context.db_ref(),
+            TransactionInfo::default(),
+        )
+        .unwrap();
+    let GethTrace::JS(frame) = trace else { panic!("expected a JSON mux frame") };
+    assert_eq!(frame["callTracer"]["calls"].as_array().unwrap().len(), 1);
+    assert_eq!(frame[calls], json!(1));
+}
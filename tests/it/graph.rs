@@ -0,0 +1,175 @@
+//! Call graph tests
+
+use alloy_primitives::{address, hex, Address};
+use revm::{
+    bytecode::Bytecode, context::TxEnv, context_interface::TransactTo, database::CacheDB,
+    database_interface::EmptyDB, state::AccountInfo, Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{
+    CallGraphConfig, CallGraphWriter, CallTraceArena, TracingInspector, TracingInspectorConfig,
+};
+
+/// Executes `0xaa`, which calls `0xbb` twice and then `0xcc`, which reverts.
+fn traces() -> CallTraceArena {
+    // (PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 <callee> GAS CALL POP) x 3 STOP
+    let code = hex!(
+        "6000600060006000600060bb5af150"
+        "6000600060006000600060bb5af150"
+        "6000600060006000600060cc5af150"
+        "00"
+    );
+    let mut traces = inspect(&[
+        (address!("00000000000000000000000000000000000000aa"), &code[..]),
+        // PUSH1 1 POP STOP
+        (address!("00000000000000000000000000000000000000bb"), &hex!("60015000")[..]),
+        // PUSH1 0 PUSH1 0 REVERT
+        (address!("00000000000000000000000000000000000000cc"), &hex!("60006000fd")[..]),
+    ]);
+    traces.nodes_mut()[1].trace.decoded.label = Some("Callee".to_string());
+    traces
+}
+
+/// Executes the first of the given contracts.
+fn inspect(contracts: &[(Address, &[u8])]) -> CallTraceArena {
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            for (address, code) in contracts {
+                db.insert_account_info(
+                    *address,
+                    AccountInfo {
+                        code: Some(Bytecode::new_raw(code.to_vec().into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        .build_mainnet_with_inspector(
+            TracingInspector::new(TracingInspectorConfig::default_geth()),
+        );
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contracts[0].0),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    evm.into_inspector().into_traces()
+}
+
+#[test]
+fn test_call_graph_dot() {
+    let dot = |config| {
+        let mut writer = CallGraphWriter::with_config(String::new(), config);
+        writer.write_dot(&traces()).unwrap();
+        writer.into_writer()
+    };
+
+    assert_eq!(
+        dot(CallGraphConfig::new()),
+        r#"digraph calls {
+  node [shape=box];
+  sender [label="0x0000000000000000000000000000000000000000"];
+  n0 [label="0x00000000000000000000000000000000000000AA\nfallback"];
+  n1 [label="Callee\nfallback"];
+  n2 [label="0x00000000000000000000000000000000000000bb\nfallback"];
+  n3 [label="0x00000000000000000000000000000000000000cc\nfallback", color=red, fontcolor=red, style=dashed];
+  sender -> n0 [label="CALL\ngas: 5382"];
+  n0 -> n1 [label="CALL\ngas: 5"];
+  n0 -> n2 [label="CALL\ngas: 5"];
+  n0 -> n3 [label="CALL\ngas: 6", color=red, fontcolor=red, style=dashed];
+}
+"#
+    );
+
+    assert_eq!(
+        dot(CallGraphConfig::new().collapse_repeated(true)),
+        r#"digraph calls {
+  node [shape=box];
+  sender [label="0x0000000000000000000000000000000000000000"];
+  n0 [label="0x00000000000000000000000000000000000000AA\nfallback"];
+  n1 [label="Callee\nfallback"];
+  n3 [label="0x00000000000000000000000000000000000000cc\nfallback", color=red, fontcolor=red, style=dashed];
+  sender -> n0 [label="CALL\ngas: 5382"];
+  n0 -> n1 [label="2x CALL\ngas: 10"];
+  n0 -> n3 [label="CALL\ngas: 6", color=red, fontcolor=red, style=dashed];
+}
+"#
+    );
+
+    assert_eq!(
+        dot(CallGraphConfig::new().max_depth(0)),
+        r#"digraph calls {
+  node [shape=box];
+  sender [label="0x0000000000000000000000000000000000000000"];
+  n0 [label="0x00000000000000000000000000000000000000AA\nfallback\n+3 hidden calls"];
+  sender -> n0 [label="CALL\ngas: 5382"];
+}
+"#
+    );
+}
+
+#[test]
+fn test_call_graph_collapse_hidden_calls() {
+    // (PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP) x 2 STOP
+    let code = hex!(
+        "6000600060006000600060bb5af150"
+        "6000600060006000600060bb5af150"
+        "00"
+    );
+    let traces = inspect(&[
+        (address!("00000000000000000000000000000000000000aa"), &code[..]),
+        // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xdd GAS CALL POP STOP
+        (
+            address!("00000000000000000000000000000000000000bb"),
+            &hex!("6000600060006000600060dd5af15000")[..],
+        ),
+        // STOP
+        (address!("00000000000000000000000000000000000000dd"), &hex!("00")[..]),
+    ]);
+
+    let mut writer =
+        CallGraphWriter::with_config(String::new(), CallGraphConfig::new().collapse_repeated(true));
+    writer.write_dot(&traces).unwrap();
+    // the sub-call of the second call to `0xbb` is hidden
+    assert_eq!(
+        writer.into_writer(),
+        r#"digraph calls {
+  node [shape=box];
+  sender [label="0x0000000000000000000000000000000000000000"];
+  n0 [label="0x00000000000000000000000000000000000000AA\nfallback"];
+  n1 [label="0x00000000000000000000000000000000000000bb\nfallback\n+1 hidden calls"];
+  n2 [label="0x00000000000000000000000000000000000000dd\nfallback"];
+  sender -> n0 [label="CALL\ngas: 5488"];
+  n0 -> n1 [label="2x CALL\ngas: 2744"];
+  n1 -> n2 [label="CALL\ngas: 0"];
+}
+"#
+    );
+}
+
+#[test]
+fn test_call_graph_mermaid() {
+    let mut writer = CallGraphWriter::new(String::new());
+    writer.write_mermaid(&traces()).unwrap();
+    assert_eq!(
+        writer.into_writer(),
+        r#"flowchart TD
+  sender["0x0000000000000000000000000000000000000000"]
+  n0["0x00000000000000000000000000000000000000AA<br/>fallback"]
+  n1["Callee<br/>fallback"]
+  n2["0x00000000000000000000000000000000000000bb<br/>fallback"]
+  n3["0x00000000000000000000000000000000000000cc<br/>fallback"]
+  sender -->|"CALL<br/>gas: 5382"| n0
+  n0 -->|"CALL<br/>gas: 5"| n1
+  n0 -->|"CALL<br/>gas: 5"| n2
+  n0 -->|"CALL<br/>gas: 6"| n3
+  classDef failed stroke:#d00,color:#d00,stroke-dasharray:4
+  class n3 failed
+  linkStyle 3 stroke:#d00,color:#d00,stroke-dasharray:4
+"#
+    );
+}
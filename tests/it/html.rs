@@ -0,0 +1,65 @@
+//! HTML writer tests
+
+use alloy_primitives::{address, hex, Address};
+use revm::{
+    bytecode::Bytecode, context::TxEnv, context_interface::TransactTo, database::CacheDB,
+    database_interface::EmptyDB, state::AccountInfo, Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{
+    types::DecodedTraceStep, HtmlTraceWriter, TraceWriterConfig, TracingInspector,
+    TracingInspectorConfig,
+};
+use snapbox::{assert_data_eq, Data};
+
+const OUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/it/html/");
+
+#[test]
+fn test_html_writer() {
+    // PUSH1 1 PUSH1 0 SSTORE PUSH1 0x2a PUSH1 0 PUSH1 0 LOG1
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP STOP
+    let code = hex!("6001600055602a60006000a16000600060006000600060bb5af15000");
+    let contracts = [
+        (address!("00000000000000000000000000000000000000aa"), &code[..]),
+        // reverts with `Panic(0x12)`
+        (
+            address!("00000000000000000000000000000000000000bb"),
+            &hex!("634e487b7160e01b600052601260045260246000fd")[..],
+        ),
+    ];
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            for (address, code) in contracts {
+                db.insert_account_info(
+                    address,
+                    AccountInfo {
+                        code: Some(Bytecode::new_raw(code.to_vec().into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(TracingInspectorConfig::all()));
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contracts[0].0),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let mut traces = evm.into_inspector().into_traces();
+    let nodes = traces.nodes_mut();
+    nodes[0].trace.decoded.label = Some("Caller".to_string());
+    nodes[0].trace.steps[2].decoded = Some(DecodedTraceStep::Line("store <1>".to_string()));
+    nodes[1].trace.decoded.label = Some("<Callee>".to_string());
+
+    let config = TraceWriterConfig::new().write_storage_changes(true);
+    let mut writer = HtmlTraceWriter::with_config(Vec::new(), config);
+    writer.write_arena(&traces).unwrap();
+    let html = String::from_utf8(writer.into_writer()).unwrap();
+    assert_data_eq!(html, Data::read_from(&std::path::Path::new(OUT_DIR).join("trace.html"), None));
+}
@@ -4,15 +4,35 @@ pub mod accesslist;
 #[cfg(feature = "std")]
 pub mod utils;
 
+#[cfg(all(feature = "std", feature = "abi-decoder"))]
+mod abi;
+#[cfg(feature = "std")]
+mod binary;
+#[cfg(feature = "std")]
+mod diff;
 #[cfg(feature = "std")]
 mod edge_cov;
 #[cfg(feature = "std")]
+mod eip3155;
+#[cfg(feature = "std")]
+mod flamegraph;
+#[cfg(feature = "std")]
 mod geth;
 #[cfg(feature = "js-tracer")]
 mod geth_js;
 #[cfg(feature = "std")]
+mod graph;
+#[cfg(feature = "std")]
+mod html;
+#[cfg(feature = "std")]
 mod parity;
 #[cfg(feature = "std")]
+mod sourcemap;
+#[cfg(feature = "std")]
+mod state_changes;
+#[cfg(feature = "std")]
+mod step_scope;
+#[cfg(feature = "std")]
 mod transfer;
 #[cfg(feature = "std")]
 mod writer;
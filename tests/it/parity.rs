@@ -1,12 +1,13 @@
 //! Parity tests
 
 use crate::utils::{deploy_contract, inspect_deploy_contract, print_traces};
-use alloy_primitives::{address, hex, map::HashSet, Address, U256};
+use alloy_primitives::{address, hex, map::HashSet, Address, Bytes, U256};
 use alloy_rpc_types_eth::TransactionInfo;
 use alloy_rpc_types_trace::parity::{
-    Action, CallAction, CallType, CreationMethod, SelfdestructAction, TraceType,
+    Action, CallAction, CallType, CreationMethod, SelfdestructAction, TraceType, VmTrace,
 };
 use revm::{
+    bytecode::Bytecode,
     context::TxEnv,
     context_interface::{
         block::BlobExcessGasAndPrice,
@@ -22,7 +23,9 @@ use revm::{
     Context, DatabaseCommit, InspectEvm, MainBuilder, MainContext,
 };
 use revm_inspectors::tracing::{
-    parity::populate_state_diff, TracingInspector, TracingInspectorConfig,
+    parity::{populate_state_diff, ParityTraceBuilder},
+    types::StorageChangeReason,
+    TracingInspector, TracingInspectorConfig,
 };
 
 #[test]
@@ -460,3 +463,283 @@ fn test_parity_delegatecall_selfdestruct() {
     assert_eq!(action2.address, delegate_addr);
     assert_eq!(action2.refund_address, deployer);
 }
+
+#[test]
+fn test_parity_vm_trace_transient_storage() {
+    // TSTORE(1, 2) TSTORE(1, 2) TLOAD(1) POP STOP
+    let code = hex!("600260015d600260015d60015c5000");
+    let contract = address!("00000000000000000000000000000000000000aa");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+        })
+        .modify_cfg_chained(|cfg| cfg.spec = SpecId::CANCUN)
+        .build_mainnet_with_inspector(TracingInspector::new(
+            TracingInspectorConfig::parity_vm_trace().set_state_diffs(true),
+        ));
+
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contract),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let steps = &evm.inspector.traces().nodes()[0].trace.steps;
+    let changes = steps.iter().filter_map(|step| step.storage_change).collect::<Vec<_>>();
+    assert_eq!(changes.len(), 2);
+    assert!(changes.iter().all(|change| change.reason == StorageChangeReason::TSTORE));
+    assert_eq!(changes[0].had_value, Some(U256::ZERO));
+    assert_eq!(changes[0].value, U256::from(2));
+    // the second write doesn't change the value
+    assert_eq!(changes[1].had_value, Some(U256::from(2)));
+
+    let vm_trace = evm.inspector.into_parity_builder().vm_trace();
+    let stores = vm_trace
+        .ops
+        .iter()
+        .filter_map(|op| op.ex.as_ref()?.store.as_ref())
+        .map(|store| (store.key, store.val))
+        .collect::<Vec<_>>();
+    assert_eq!(stores, vec![(U256::from(1), U256::from(2)); 2]);
+}
+
+#[test]
+fn test_parity_vm_trace_code() {
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP
+    // PUSH4 0x60015000 PUSH1 0 MSTORE PUSH1 4 PUSH1 28 PUSH1 0 CREATE POP STOP
+    let code = hex!("6000600060006000600060bb5af15063600150006000526004601c6000f05000");
+    let contract = address!("00000000000000000000000000000000000000aa");
+    // PUSH1 1 POP STOP
+    let callee_code = hex!("60015000");
+    let callee = address!("00000000000000000000000000000000000000bb");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+            db.insert_account_info(
+                callee,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(callee_code.into())),
+                    ..Default::default()
+                },
+            );
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(
+            TracingInspectorConfig::parity_vm_trace(),
+        ));
+
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contract),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let assert_code = |vm_trace: VmTrace| {
+        assert_eq!(vm_trace.code, code[..]);
+        let subs = vm_trace.ops.iter().filter_map(|op| op.sub.as_ref()).collect::<Vec<_>>();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].code, callee_code[..]);
+        // the init code of the created contract
+        assert_eq!(subs[1].code, callee_code[..]);
+    };
+
+    let builder = evm.inspector.clone().into_parity_builder();
+    assert_code(builder.vm_trace());
+
+    // without captured code, the code is loaded from the database
+    let mut nodes = evm.inspector.traces().nodes().to_vec();
+    for node in &mut nodes {
+        node.trace.code = None;
+    }
+    let builder = ParityTraceBuilder::new(nodes, None, TracingInspectorConfig::parity_vm_trace());
+    assert_eq!(builder.vm_trace().code, Bytes::new());
+    assert_code(builder.vm_trace_with_db(evm.db_ref()).unwrap());
+}
+
+#[test]
+fn test_parity_vm_trace_memory_deltas() {
+    // PUSH1 0x2a PUSH1 0 MSTORE PUSH1 0xff PUSH1 0x3f MSTORE8 PUSH1 0 MLOAD POP
+    // PUSH1 0x40 PUSH1 0x60 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS STATICCALL POP STOP
+    let code = hex!("602a60005260ff603f5360005150604060606000600060bb5afa5000");
+    let contract = address!("00000000000000000000000000000000000000aa");
+    // PUSH1 0x2b PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+    let callee_code = hex!("602b60005260206000f3");
+    let callee = address!("00000000000000000000000000000000000000bb");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+            db.insert_account_info(
+                callee,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(callee_code.into())),
+                    ..Default::default()
+                },
+            );
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(
+            TracingInspectorConfig::parity_vm_trace(),
+        ));
+
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contract),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    // no full memory snapshots are recorded
+    assert!(evm.inspector.traces().nodes()[0].trace.steps.iter().all(|step| step.memory.is_none()));
+
+    let vm_trace = evm.inspector.clone().into_parity_builder().vm_trace();
+    let mem = vm_trace
+        .ops
+        .iter()
+        .filter_map(|op| {
+            let mem = op.ex.as_ref()?.mem.as_ref()?;
+            Some((op.op.clone().unwrap(), mem.off, mem.data.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        mem,
+        vec![
+            ("MSTORE".to_string(), 0, Bytes::from(U256::from(0x2a).to_be_bytes_vec())),
+            ("MSTORE8".to_string(), 0x3f, Bytes::from_static(&[0xff])),
+            // only the returned word is written into the 64 byte return data range
+            ("STATICCALL".to_string(), 0x60, Bytes::from(U256::from(0x2b).to_be_bytes_vec())),
+        ]
+    );
+}
+
+#[test]
+fn test_parity_vm_trace_memory_deltas_unrecorded_call() {
+    // PUSH1 0x20 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS STATICCALL POP
+    // PUSH1 0x20 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xcc GAS STATICCALL POP STOP
+    let code = hex!("602060006000600060bb5afa50602060006000600060cc5afa5000");
+    let contract = address!("00000000000000000000000000000000000000aa");
+    let callees = [
+        // PUSH1 1 PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+        (address!("00000000000000000000000000000000000000bb"), hex!("600160005260206000f3")),
+        // PUSH1 2 PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+        (address!("00000000000000000000000000000000000000cc"), hex!("600260005260206000f3")),
+    ];
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+            for (callee, callee_code) in callees {
+                db.insert_account_info(
+                    callee,
+                    AccountInfo {
+                        code: Some(Bytecode::new_raw(callee_code.into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        // only the steps up to the first STATICCALL are recorded
+        .build_mainnet_with_inspector(TracingInspector::new(
+            TracingInspectorConfig::parity_vm_trace().set_steps_limit(Some(7)),
+        ));
+
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contract),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    // the output of the second call is not attached to the step of the first call
+    let steps = &evm.inspector.traces().nodes()[0].trace.steps;
+    assert_eq!(steps.len(), 7);
+    let step = steps.last().unwrap();
+    assert_eq!(step.op.as_str(), "STATICCALL");
+    assert_eq!(step.memory_change.as_ref().unwrap().data, U256::from(1).to_be_bytes_vec());
+}
+
+#[test]
+fn test_parity_revert_reasons() {
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP
+    // RETURNDATASIZE PUSH1 0 PUSH1 0 RETURNDATACOPY RETURNDATASIZE PUSH1 0 REVERT
+    let code = hex!("6000600060006000600060bb5af1503d600060003e3d6000fd");
+    let contract = address!("00000000000000000000000000000000000000aa");
+    // reverts with `Panic(0x12)`
+    // PUSH4 0x4e487b71 PUSH1 0xe0 SHL PUSH1 0 MSTORE PUSH1 0x12 PUSH1 4 MSTORE
+    // PUSH1 0x24 PUSH1 0 REVERT
+    let callee_code = hex!("634e487b7160e01b600052601260045260246000fd");
+    let callee = address!("00000000000000000000000000000000000000bb");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+            db.insert_account_info(
+                callee,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(callee_code.into())),
+                    ..Default::default()
+                },
+            );
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(
+            TracingInspectorConfig::default_parity(),
+        ));
+
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contract),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(!res.result.is_success());
+
+    // the revert was bubbled up from the callee
+    assert_eq!(evm.inspector.traces().revert_origin(0), Some(1));
+
+    let errors = |builder: ParityTraceBuilder| {
+        builder.into_transaction_traces().into_iter().map(|trace| trace.error).collect::<Vec<_>>()
+    };
+    let builder = evm.inspector.clone().into_parity_builder();
+    assert_eq!(errors(builder), [Some("Reverted".to_string()), Some("Reverted".to_string())]);
+
+    let builder = evm.inspector.clone().into_parity_builder().with_revert_reasons(true);
+    let reason = "Reverted: panic: division or modulo by zero (0x12)".to_string();
+    assert_eq!(errors(builder), [Some(reason.clone()), Some(reason)]);
+}
@@ -0,0 +1,84 @@
+//! Source map decoder tests
+
+use alloy_primitives::{address, hex, Address};
+use revm::{
+    bytecode::Bytecode, context::TxEnv, context_interface::TransactTo, database::CacheDB,
+    database_interface::EmptyDB, state::AccountInfo, Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{
+    types::{DecodedInternalCall, DecodedTraceStep},
+    ContractSourceMap, FunctionRange, SourceMapDecoder, TraceWriter, TracingInspector,
+    TracingInspectorConfig,
+};
+
+#[test]
+fn test_decode_internal_calls() {
+    // The compiled equivalent of calling `f(42)`, with
+    // `function f(uint256 x) internal returns (uint256) { return x + 1; }` at 0x0a:
+    //
+    // 0x00 PUSH1 0x07 PUSH1 0x2a PUSH1 0x0a JUMP [in]
+    // 0x07 JUMPDEST POP STOP
+    // 0x0a JUMPDEST PUSH1 0x01 ADD SWAP1 JUMP [out]
+    let code = hex!("6007602a600a565b50005b6001019056");
+    let source_map = "0:100:0:-;;;:::i;:::-;;;10:50;40:5;;;:::o";
+    let function = FunctionRange {
+        name: "C::f".to_string(),
+        index: 0,
+        offset: 10,
+        length: 50,
+        parameters: vec!["uint256".to_string()],
+        returns: vec!["uint256".to_string()],
+    };
+    let contract = address!("00000000000000000000000000000000000000aa");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            db.insert_account_info(
+                contract,
+                AccountInfo { code: Some(Bytecode::new_raw(code.into())), ..Default::default() },
+            );
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(TracingInspectorConfig::all()));
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contract),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+    let mut traces = evm.into_inspector().into_traces();
+
+    let decoder = SourceMapDecoder::new().with_contract(
+        contract,
+        ContractSourceMap::new(&code, source_map, vec![function]).unwrap(),
+    );
+    decoder.decode_arena(&mut traces);
+
+    let steps = &traces.nodes()[0].trace.steps;
+    assert_eq!(
+        steps[3].decoded,
+        Some(DecodedTraceStep::InternalCall(
+            DecodedInternalCall {
+                func_name: "C::f".to_string(),
+                args: Some(vec!["42".to_string()]),
+                return_data: Some(vec!["43".to_string()]),
+            },
+            8
+        ))
+    );
+    assert!(steps.iter().enumerate().all(|(idx, step)| idx == 3 || step.decoded.is_none()));
+
+    let mut writer = TraceWriter::new(Vec::new());
+    writer.write_arena(&traces).unwrap();
+    assert_eq!(
+        String::from_utf8(writer.into_writer()).unwrap(),
+        "  [38] 0x00000000000000000000000000000000000000AA::fallback()
+    ├─ [18] C::f(42)
+    │   └─ ← 43
+    └─ ← [Stop]
+"
+    );
+}
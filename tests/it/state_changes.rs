@@ -0,0 +1,105 @@
+//! Call state changes tests
+
+use alloy_primitives::{address, hex, Address, U256};
+use revm::{
+    bytecode::Bytecode, context::TxEnv, context_interface::TransactTo, database::CacheDB,
+    database_interface::EmptyDB, state::AccountInfo, Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{
+    types::{CallStateChange, CallStateChangeKind},
+    TracingInspector, TracingInspectorConfig,
+};
+
+#[test]
+fn test_call_state_changes() {
+    let caller = address!("00000000000000000000000000000000000000aa");
+    let reverting = address!("00000000000000000000000000000000000000bb");
+    let storing = address!("00000000000000000000000000000000000000cc");
+
+    // CALL(gas, reverting, 1, 0, 0, 0, 0) POP CALL(gas, storing, 0, 0, 0, 0, 0) POP STOP
+    let caller_code = hex!("600060006000600060017300000000000000000000000000000000000000bb5af150600060006000600060007300000000000000000000000000000000000000cc5af15000");
+    // SSTORE(0, 1) REVERT(0, 0)
+    let reverting_code = hex!("600160005560006000fd");
+    // SSTORE(0, 2) STOP
+    let storing_code = hex!("600260005500");
+
+    let context =
+        Context::mainnet().with_db(CacheDB::<EmptyDB>::default()).modify_db_chained(|db| {
+            db.insert_account_info(
+                caller,
+                AccountInfo {
+                    balance: U256::from(10),
+                    code: Some(Bytecode::new_raw(caller_code.into())),
+                    ..Default::default()
+                },
+            );
+            db.insert_account_info(
+                reverting,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(reverting_code.into())),
+                    ..Default::default()
+                },
+            );
+            db.insert_account_info(
+                storing,
+                AccountInfo {
+                    code: Some(Bytecode::new_raw(storing_code.into())),
+                    ..Default::default()
+                },
+            );
+        });
+
+    let mut evm = context.build_mainnet_with_inspector(TracingInspector::new(
+        TracingInspectorConfig::default_parity().record_call_state_changes(),
+    ));
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(caller),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let traces = evm.into_inspector().into_traces();
+    let nodes = traces.nodes();
+    assert_eq!(nodes.len(), 3);
+
+    // the root call itself didn't change anything, the nonce bump of the sender is not part of it
+    assert!(nodes[0].state_changes.is_empty());
+
+    // the value transfer and the storage write of the reverted call are marked as reverted
+    assert_eq!(
+        nodes[1].state_changes,
+        vec![
+            CallStateChange {
+                address: caller,
+                kind: CallStateChangeKind::Transfer { to: reverting, value: U256::from(1) },
+                reverted: true,
+            },
+            CallStateChange {
+                address: reverting,
+                kind: CallStateChangeKind::Storage {
+                    key: U256::ZERO,
+                    had_value: U256::ZERO,
+                    value: U256::from(1),
+                },
+                reverted: true,
+            },
+        ]
+    );
+
+    assert_eq!(
+        nodes[2].state_changes,
+        vec![CallStateChange {
+            address: storing,
+            kind: CallStateChangeKind::Storage {
+                key: U256::ZERO,
+                had_value: U256::ZERO,
+                value: U256::from(2),
+            },
+            reverted: false,
+        }]
+    );
+}
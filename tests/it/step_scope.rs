@@ -0,0 +1,82 @@
+//! Step scope tests
+
+use alloy_primitives::{address, hex, keccak256, Address};
+use revm::{
+    bytecode::Bytecode, context::TxEnv, context_interface::TransactTo, database::CacheDB,
+    database_interface::EmptyDB, state::AccountInfo, Context, InspectEvm, MainBuilder, MainContext,
+};
+use revm_inspectors::tracing::{StepScope, TracingInspector, TracingInspectorConfig};
+
+// PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP STOP
+const CODE: [u8; 16] = hex!("6000600060006000600060bb5af15000");
+// PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xcc GAS CALL POP STOP
+const CALLEE_CODE: [u8; 16] = hex!("6000600060006000600060cc5af15000");
+// PUSH1 0x2a PUSH1 0 MSTORE STOP
+const NESTED_CALLEE_CODE: [u8; 6] = hex!("602a60005200");
+
+/// Executes the call chain `0xaa -> 0xbb -> 0xcc` with the given scope, and returns the number of
+/// recorded steps per call.
+fn recorded_steps(scope: StepScope) -> Vec<usize> {
+    let contracts = [
+        (address!("00000000000000000000000000000000000000aa"), &CODE[..]),
+        (address!("00000000000000000000000000000000000000bb"), &CALLEE_CODE[..]),
+        (address!("00000000000000000000000000000000000000cc"), &NESTED_CALLEE_CODE[..]),
+    ];
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            for (address, code) in contracts {
+                db.insert_account_info(
+                    address,
+                    AccountInfo {
+                        code: Some(Bytecode::new_raw(code.to_vec().into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        .build_mainnet_with_inspector(
+            TracingInspector::new(TracingInspectorConfig::default_geth()).with_steps_scope(scope),
+        );
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(contracts[0].0),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let traces = evm.into_inspector().into_traces();
+    for node in traces.nodes() {
+        assert!(node.trace.steps.iter().all(|step| step.contract == node.trace.address));
+    }
+    traces.nodes().iter().map(|node| node.trace.steps.len()).collect()
+}
+
+#[test]
+fn test_step_scope() {
+    assert_eq!(recorded_steps(StepScope::new()), [10, 10, 4]);
+    assert_eq!(
+        recorded_steps(
+            StepScope::new().address(address!("00000000000000000000000000000000000000bb"))
+        ),
+        [0, 10, 0]
+    );
+    assert_eq!(
+        recorded_steps(StepScope::new().code_hash(keccak256(NESTED_CALLEE_CODE))),
+        [0, 0, 4]
+    );
+    assert_eq!(recorded_steps(StepScope::new().max_depth(1)), [10, 10, 0]);
+    assert_eq!(
+        recorded_steps(
+            StepScope::new()
+                .address(address!("00000000000000000000000000000000000000aa"))
+                .code_hash(keccak256(NESTED_CALLEE_CODE))
+                .max_depth(1)
+        ),
+        [10, 0, 0]
+    );
+}
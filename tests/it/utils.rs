@@ -11,7 +11,7 @@ use revm::{
     primitives::hardfork::SpecId,
     Context, Database, DatabaseCommit, ExecuteCommitEvm, InspectCommitEvm, Inspector, Journal,
 };
-use revm_inspectors::tracing::{TraceWriter, TraceWriterConfig, TracingInspector};
+use revm_inspectors::tracing::{FmtWriter, TraceWriter, TraceWriterConfig, TracingInspector};
 
 pub type ContextDb<DB> = Context<BlockEnv, TxEnv, CfgEnv, DB, Journal<DB>, ()>;
 
@@ -19,10 +19,16 @@ pub fn write_traces(tracer: &TracingInspector) -> String {
     write_traces_with(tracer, TraceWriterConfig::new().color_choice(ColorChoice::Never))
 }
 
+/// Writes the traces to both an `io::Write` and a `fmt::Write` writer, which must be identical.
 pub fn write_traces_with(tracer: &TracingInspector, config: TraceWriterConfig) -> String {
-    let mut w = TraceWriter::with_config(Vec::<u8>::new(), config);
+    let mut w = TraceWriter::with_config(Vec::<u8>::new(), config.clone());
     w.write_arena(tracer.traces()).expect("failed to write traces to Vec<u8>");
-    String::from_utf8(w.into_writer()).expect("trace writer wrote invalid UTF-8")
+    let s = String::from_utf8(w.into_writer()).expect("trace writer wrote invalid UTF-8");
+
+    let mut w = TraceWriter::with_config(FmtWriter::new(String::new()), config);
+    w.write_arena(tracer.traces()).expect("failed to write traces to String");
+    assert_eq!(w.into_writer().into_inner(), s, "io::Write and fmt::Write outputs differ");
+    s
 }
 
 pub fn print_traces(tracer: &TracingInspector) {
@@ -1,15 +1,19 @@
 use crate::utils::{inspect_deploy_contract, write_traces_with};
-use alloy_primitives::{address, b256, bytes, hex, Address, B256, U256};
+use alloy_primitives::{
+    address, b256, bytes, hex, map::HashMap, utils::format_ether, Address, B256, U256,
+};
 use alloy_sol_types::{sol, SolCall};
 use colorchoice::ColorChoice;
 use revm::{
-    context::TxEnv, context_interface::TransactTo, database::CacheDB, database_interface::EmptyDB,
-    inspector::InspectorEvmTr, primitives::hardfork::SpecId, Context, InspectCommitEvm, InspectEvm,
-    MainBuilder, MainContext,
+    bytecode::Bytecode, context::TxEnv, context_interface::TransactTo, database::CacheDB,
+    database_interface::EmptyDB, inspector::InspectorEvmTr, primitives::hardfork::SpecId,
+    state::AccountInfo, Context, InspectCommitEvm, InspectEvm, MainBuilder, MainContext,
 };
 use revm_inspectors::tracing::{
-    types::{DecodedCallData, DecodedInternalCall, DecodedTraceStep},
-    TraceWriterConfig, TracingInspector, TracingInspectorConfig,
+    types::{
+        CallKind, CallTrace, CallTraceNode, DecodedCallData, DecodedInternalCall, DecodedTraceStep,
+    },
+    TraceDecorator, TraceWriter, TraceWriterConfig, TracingInspector, TracingInspectorConfig,
 };
 use snapbox::{assert_data_eq, data::DataFormat};
 use std::path::Path;
@@ -111,6 +115,239 @@ fn deploy_fail() {
     assert_traces(base_path, Some("decoded"), None, evm.inspector());
 }
 
+#[test]
+fn transient_storage() {
+    let base_path = &Path::new(OUT_DIR).join("transient_storage");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::new(EmptyDB::default()))
+        .build_mainnet_with_inspector(TracingInspector::new(TracingInspectorConfig::all()));
+
+    // TSTORE(1, 2) TSTORE(1, 3) SSTORE(0, 7)
+    inspect_deploy_contract(
+        &mut evm,
+        bytes!("600260015d600360015d600760005500"),
+        Address::default(),
+        SpecId::CANCUN,
+    );
+
+    assert_traces(base_path, Some("raw"), None, evm.inspector());
+}
+
+#[test]
+fn trace_decorator() {
+    struct Decorator(HashMap<Address, String>);
+
+    impl TraceDecorator for Decorator {
+        fn label(&self, trace: &CallTrace) -> Option<String> {
+            self.0.get(&trace.address).cloned()
+        }
+
+        fn fmt_value(&self, value: U256) -> String {
+            format!("{} ether", format_ether(value))
+        }
+
+        fn call_kind(&self, kind: CallKind) -> Option<String> {
+            Some(kind.to_string())
+        }
+
+        fn fmt_storage_word(&self, word: U256) -> String {
+            B256::from(word).to_string()
+        }
+
+        fn annotation(&self, nodes: &[CallTraceNode], idx: usize) -> Option<String> {
+            let share = nodes[idx].trace.gas_used * 100 / nodes[0].trace.gas_used;
+            Some(format!("({share}% gas)"))
+        }
+    }
+
+    let caller = address!("00000000000000000000000000000000000000aa");
+    let callee = address!("00000000000000000000000000000000000000bb");
+    // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH8 1e18 PUSH1 0xbb GAS CALL POP STOP
+    let code = hex!("600060006000600067016345785d8a000060bb5af15000");
+    // PUSH1 1 PUSH1 0 SSTORE STOP
+    let callee_code = hex!("600160005500");
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            for (address, code) in [(caller, &code[..]), (callee, &callee_code[..])] {
+                db.insert_account_info(
+                    address,
+                    AccountInfo {
+                        balance: U256::from(10).pow(U256::from(18)),
+                        code: Some(Bytecode::new_raw(code.to_vec().into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(TracingInspectorConfig::all()));
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(caller),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let decorator = Decorator(HashMap::from_iter([(callee, "Callee".to_string())]));
+    let config =
+        TraceWriterConfig::new().color_choice(ColorChoice::Never).write_storage_changes(true);
+    let mut writer = TraceWriter::with_config(Vec::new(), config).with_decorator(decorator);
+    writer.write_arena(evm.inspector.traces()).unwrap();
+    let s = String::from_utf8(writer.into_writer()).unwrap();
+    assert_eq!(
+        s,
+        "  [31428] 0x00000000000000000000000000000000000000AA::fallback() [CALL] (100% gas)
+    ├─ [22106] Callee::fallback{value: 0.100000000000000000 ether}() [CALL] (70% gas)
+    │   ├─  storage changes:
+    │   │   @ 0x0000000000000000000000000000000000000000000000000000000000000000: 0x0000000000000000000000000000000000000000000000000000000000000000 → 0x0000000000000000000000000000000000000000000000000000000000000001
+    │   └─ ← [Stop]
+    └─ ← [Stop]
+"
+    );
+}
+
+#[test]
+fn trace_filters() {
+    let call = |address: u8| {
+        // PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 <address> GAS CALL POP
+        [&hex!("6000600060006000600060")[..], &[address], &hex!("5af150")[..]].concat()
+    };
+    let contracts = [
+        // calls 0xbb three times, then 0xcc and 0xee
+        (0xaa, [call(0xbb), call(0xbb), call(0xbb), call(0xcc), call(0xee), vec![0x00]].concat()),
+        // PUSH1 0 PUSH1 0 LOG0 STOP
+        (0xbb, hex!("60006000a000").to_vec()),
+        // calls 0xdd, then STOP
+        (0xcc, [call(0xdd), vec![0x00]].concat()),
+        // PUSH1 0 PUSH1 0 REVERT
+        (0xdd, hex!("60006000fd").to_vec()),
+        (0xee, hex!("60006000fd").to_vec()),
+    ];
+
+    let mut evm = Context::mainnet()
+        .with_db(CacheDB::<EmptyDB>::default())
+        .modify_db_chained(|db| {
+            for (address, code) in &contracts {
+                db.insert_account_info(
+                    Address::with_last_byte(*address),
+                    AccountInfo {
+                        code: Some(Bytecode::new_raw(code.clone().into())),
+                        ..Default::default()
+                    },
+                );
+            }
+        })
+        .build_mainnet_with_inspector(TracingInspector::new(
+            TracingInspectorConfig::default_geth().record_logs(),
+        ));
+    let res = evm
+        .inspect_tx(TxEnv {
+            caller: Address::ZERO,
+            gas_limit: 1000000,
+            kind: TransactTo::Call(Address::with_last_byte(0xaa)),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(res.result.is_success());
+
+    let write = |config: TraceWriterConfig| {
+        let mut tracer = evm.inspector.clone();
+        for node in tracer.traces_mut().nodes_mut() {
+            let address = node.trace.address;
+            node.trace.decoded.label = Some(format!("C{:x}", address.0[19]));
+        }
+        write_traces_with(&tracer, config.color_choice(ColorChoice::Never))
+    };
+    for (config, expected) in [
+        (
+            TraceWriterConfig::new().max_depth(1),
+            "  [11887] Caa::fallback()
+    ├─ [381] Cbb::fallback()
+    │   ├─ … 1 log hidden (max depth)
+    │   └─ ← [Stop]
+    ├─ [381] Cbb::fallback()
+    │   ├─ … 1 log hidden (max depth)
+    │   └─ ← [Stop]
+    ├─ [381] Cbb::fallback()
+    │   ├─ … 1 log hidden (max depth)
+    │   └─ ← [Stop]
+    ├─ [2628] Ccc::fallback()
+    │   ├─ … 1 call hidden (max depth)
+    │   └─ ← [Stop]
+    ├─ [6] Cee::fallback()
+    │   └─ ← [Revert]
+    └─ ← [Stop]
+",
+        ),
+        (
+            TraceWriterConfig::new().min_gas(1000),
+            "  [11887] Caa::fallback()
+    ├─ … 3 calls, 3 logs hidden (below 1000 gas)
+    ├─ [2628] Ccc::fallback()
+    │   ├─ … 1 call hidden (below 1000 gas)
+    │   └─ ← [Stop]
+    ├─ … 1 call hidden (below 1000 gas)
+    └─ ← [Stop]
+",
+        ),
+        (
+            TraceWriterConfig::new().collapse_successful(true),
+            "  [11887] Caa::fallback()
+    ├─ [381] Cbb::fallback()
+    │   ├─ … 1 log hidden (successful)
+    │   └─ ← [Stop]
+    ├─ [381] Cbb::fallback()
+    │   ├─ … 1 log hidden (successful)
+    │   └─ ← [Stop]
+    ├─ [381] Cbb::fallback()
+    │   ├─ … 1 log hidden (successful)
+    │   └─ ← [Stop]
+    ├─ [2628] Ccc::fallback()
+    │   ├─ [6] Cdd::fallback()
+    │   │   └─ ← [Revert]
+    │   └─ ← [Stop]
+    ├─ [6] Cee::fallback()
+    │   └─ ← [Revert]
+    └─ ← [Stop]
+",
+        ),
+        (
+            TraceWriterConfig::new().revert_path_only(true),
+            "  [11887] Caa::fallback()
+    ├─ … 3 calls, 3 logs hidden (not on revert path)
+    ├─ [2628] Ccc::fallback()
+    │   ├─ [6] Cdd::fallback()
+    │   │   └─ ← [Revert]
+    │   └─ ← [Stop]
+    ├─ … 1 call hidden (not on revert path)
+    └─ ← [Stop]
+",
+        ),
+        (
+            TraceWriterConfig::new().elide_repeated(true),
+            "  [11887] Caa::fallback()
+    ├─ [381] Cbb::fallback() ×3
+    │   ├─           data: 0x
+    │   └─ ← [Stop]
+    ├─ [2628] Ccc::fallback()
+    │   ├─ [6] Cdd::fallback()
+    │   │   └─ ← [Revert]
+    │   └─ ← [Stop]
+    ├─ [6] Cee::fallback()
+    │   └─ ← [Revert]
+    └─ ← [Stop]
+",
+        ),
+    ] {
+        assert_eq!(write(config), expected);
+    }
+}
+
 // (name, address)
 const LABELS: &[(&str, Address)] =
     &[("Counter", address!("Bd770416a3345F91E4B34576cb804a576fa48EB1"))];